@@ -0,0 +1,302 @@
+///
+///  Elevations from digital elevation models (SRTM .hgt and GeoTIFF)
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn dem_set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+/// Void samples of SRTM tiles
+const DEM_HGT_VOID: i16 = -32768;
+/// GeoKeys of the GeoKeyDirectoryTag
+const DEM_GEOKEY_MODEL_TYPE: u16 = 1024;
+const DEM_GEOKEY_RASTER_TYPE: u16 = 1025;
+const DEM_MODEL_GEOGRAPHIC: u16 = 2;
+const DEM_RASTER_PIXEL_IS_POINT: u16 = 2;
+
+/// Elevation samples on a regular latitude/longitude grid. West and
+/// north give the position of the first sample, void samples are NaN.
+#[derive(Debug)]
+pub(crate) struct DemRaster {
+    width: usize,
+    height: usize,
+    west: f64,
+    north: f64,
+    dx: f64,
+    dy: f64,
+    samples: Vec<f32>,
+}
+
+impl DemRaster {
+    /// Bilinear interpolation between the four samples around the
+    /// position. None outside the raster or next to a void, samples
+    /// without weight don't count.
+    pub(crate) fn elevation(&self, lat: f64, lon: f64) -> Option<f64> {
+        let fx = (lon - self.west) / self.dx;
+        let fy = (self.north - lat) / self.dy;
+        let (max_x, max_y) = ((self.width - 1) as f64, (self.height - 1) as f64);
+        if !(0.0..=max_x).contains(&fx) || !(0.0..=max_y).contains(&fy) {
+            return None;
+        }
+        let x0 = (fx.floor() as usize).min(self.width - 2);
+        let y0 = (fy.floor() as usize).min(self.height - 2);
+        let (tx, ty) = (fx - x0 as f64, fy - y0 as f64);
+        let elevation: f64 = [
+            (x0, y0, (1.0 - tx) * (1.0 - ty)),
+            (x0 + 1, y0, tx * (1.0 - ty)),
+            (x0, y0 + 1, (1.0 - tx) * ty),
+            (x0 + 1, y0 + 1, tx * ty),
+        ]
+        .iter()
+        .filter(|(_, _, weight)| *weight > 0.0)
+        .map(|(x, y, weight)| self.samples[y * self.width + x] as f64 * weight)
+        .sum();
+        if elevation.is_nan() {
+            None
+        } else {
+            Some(elevation)
+        }
+    }
+}
+
+/// South west corner of the tile from names like N50E010.hgt
+fn dem_hgt_corner(name: &str) -> Option<(i32, i32)> {
+    let name = name.to_ascii_uppercase();
+    let lat: i32 = name.get(1..3)?.parse().ok()?;
+    let lon: i32 = name.get(4..7)?.parse().ok()?;
+    let lat = match name.get(0..1)? {
+        "N" => lat,
+        "S" => -lat,
+        _ => return None,
+    };
+    let lon = match name.get(3..4)? {
+        "E" => lon,
+        "W" => -lon,
+        _ => return None,
+    };
+    Some((lat, lon))
+}
+
+/// SRTM tile of n x n big endian samples, 1201 (3") or 3601 (1"),
+/// rows from north to south
+pub(crate) fn dem_read_hgt(data: &[u8], corner: (i32, i32)) -> Result<DemRaster> {
+    let n = ((data.len() / 2) as f64).sqrt() as usize;
+    if n < 2 || n * n * 2 != data.len() {
+        return Err(anyhow!("dem: invalid hgt size: {}", data.len()));
+    }
+    let samples = data
+        .chunks_exact(2)
+        .map(|c| match i16::from_be_bytes([c[0], c[1]]) {
+            DEM_HGT_VOID => f32::NAN,
+            v => v as f32,
+        })
+        .collect();
+    Ok(DemRaster {
+        width: n,
+        height: n,
+        west: corner.1 as f64,
+        north: corner.0 as f64 + 1.0,
+        dx: 1.0 / (n - 1) as f64,
+        dy: 1.0 / (n - 1) as f64,
+        samples,
+    })
+}
+
+/// Single band GeoTIFF in geographic coordinates
+pub(crate) fn dem_read_geotiff(data: &[u8]) -> Result<DemRaster> {
+    let mut decoder = Decoder::new(Cursor::new(data)).with_context(|| "dem: tiff header")?;
+    let (width, height) = decoder.dimensions().with_context(|| "dem: tiff size")?;
+    let (width, height) = (width as usize, height as usize);
+    let scale = decoder
+        .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+        .with_context(|| "dem: missing ModelPixelScaleTag")?;
+    let tiepoint = decoder
+        .get_tag_f64_vec(Tag::ModelTiepointTag)
+        .with_context(|| "dem: missing ModelTiepointTag")?;
+    if scale.len() < 2 || tiepoint.len() < 6 || width < 2 || height < 2 {
+        return Err(anyhow!("dem: invalid georeference"));
+    }
+    let mut pixel_is_point = false;
+    if let Ok(keys) = decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag) {
+        for key in keys.chunks_exact(4).skip(1) {
+            match (key[0], key[3]) {
+                (DEM_GEOKEY_MODEL_TYPE, model) if model != DEM_MODEL_GEOGRAPHIC => {
+                    return Err(anyhow!("dem: projected GeoTIFF not supported"));
+                }
+                (DEM_GEOKEY_RASTER_TYPE, raster) => {
+                    pixel_is_point = raster == DEM_RASTER_PIXEL_IS_POINT;
+                }
+                _ => (),
+            }
+        }
+    }
+    let nodata = decoder
+        .get_tag_ascii_string(Tag::GdalNodata)
+        .ok()
+        .and_then(|s| s.trim_matches(char::from(0)).trim().parse::<f64>().ok());
+    let image = decoder.read_image().with_context(|| "dem: tiff image")?;
+    let mut samples: Vec<f32> = match image {
+        DecodingResult::U8(v) => v.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::I8(v) => v.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::U16(v) => v.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::I16(v) => v.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::U32(v) => v.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::I32(v) => v.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::F32(v) => v,
+        DecodingResult::F64(v) => v.into_iter().map(|s| s as f32).collect(),
+        _ => return Err(anyhow!("dem: unsupported tiff sample format")),
+    };
+    if samples.len() != width * height {
+        return Err(anyhow!("dem: tiff with more than one band"));
+    }
+    if let Some(nodata) = nodata {
+        let nodata = nodata as f32;
+        samples
+            .iter_mut()
+            .filter(|s| **s == nodata)
+            .for_each(|s| *s = f32::NAN);
+    }
+    // The tiepoint refers to the corner of the pixel unless pixels
+    // are points
+    let offset = if pixel_is_point { 0.0 } else { 0.5 };
+    Ok(DemRaster {
+        width,
+        height,
+        west: tiepoint[3] + (offset - tiepoint[0]) * scale[0],
+        north: tiepoint[4] - (offset - tiepoint[1]) * scale[1],
+        dx: scale[0],
+        dy: scale[1],
+        samples,
+    })
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+/// Elevation models from SRTM tiles and GeoTIFF files. Tiles are
+/// loaded when first needed, GeoTIFF files right away.
+#[derive(Debug, Default)]
+pub struct Dem {
+    tiles: HashMap<(i32, i32), PathBuf>,
+    loaded: RefCell<HashMap<(i32, i32), Option<DemRaster>>>,
+    rasters: Vec<DemRaster>,
+}
+
+impl Dem {
+    /// Models from .hgt and .tif files or directories holding them
+    pub fn open(paths: &[String]) -> Result<Self> {
+        let mut dem = Dem::default();
+        for path in paths.iter().map(Path::new) {
+            if path.is_dir() {
+                let entries = fs::read_dir(path).with_context(|| format!("dem: {:?}", path))?;
+                for entry in entries.flatten() {
+                    dem.add_file(&entry.path(), false)?;
+                }
+            } else {
+                dem.add_file(path, true)?;
+            }
+        }
+        if get_debug() >= 1 {
+            eprintln!(
+                "dem: {} hgt tiles, {} GeoTIFF rasters",
+                dem.tiles.len(),
+                dem.rasters.len()
+            );
+        }
+        Ok(dem)
+    }
+
+    fn add_file(&mut self, path: &Path, explicit: bool) -> Result<()> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        match extension.as_str() {
+            "hgt" => match dem_hgt_corner(&name) {
+                Some(corner) => {
+                    self.tiles.insert(corner, path.to_path_buf());
+                }
+                None if explicit => return Err(anyhow!("dem: invalid hgt name: {:?}", path)),
+                None => (),
+            },
+            "tif" | "tiff" => {
+                let data = fs::read(path).with_context(|| format!("dem: {:?}", path))?;
+                let raster = dem_read_geotiff(&data).with_context(|| format!("dem: {:?}", path))?;
+                self.rasters.push(raster);
+            }
+            _ if explicit => return Err(anyhow!("dem: unknown file type: {:?}", path)),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Elevation at the position, NaN where no model covers it.
+    /// GeoTIFF rasters are preferred over SRTM tiles.
+    pub fn elevation(&self, lat: f64, lon: f64) -> f64 {
+        if let Some(elevation) = self.rasters.iter().find_map(|r| r.elevation(lat, lon)) {
+            return elevation;
+        }
+        // Positions on the edge are also found in the neighbouring
+        // tiles to the south and west
+        let lats = [lat.floor() as i32, lat.ceil() as i32 - 1];
+        let lons = [lon.floor() as i32, lon.ceil() as i32 - 1];
+        lats.iter()
+            .flat_map(|lat| lons.iter().map(move |lon| (*lat, *lon)))
+            .find_map(|corner| self.tile_elevation(corner, lat, lon))
+            .unwrap_or(f64::NAN)
+    }
+
+    fn tile_elevation(&self, corner: (i32, i32), lat: f64, lon: f64) -> Option<f64> {
+        let path = self.tiles.get(&corner)?;
+        let mut loaded = self.loaded.borrow_mut();
+        let tile = loaded.entry(corner).or_insert_with(|| {
+            let tile = fs::read(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|data| dem_read_hgt(&data, corner));
+            match tile {
+                Ok(tile) => Some(tile),
+                Err(err) => {
+                    eprintln!("dem: skipping {:?}: {}", path, err);
+                    None
+                }
+            }
+        });
+        tile.as_ref().and_then(|t| t.elevation(lat, lon))
+    }
+}
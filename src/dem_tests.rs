@@ -0,0 +1,108 @@
+///
+///  dem test cases
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use tiff::encoder::{colortype, TiffEncoder};
+    use tiff::tags::Tag;
+
+    use crate::dem::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dem_tests_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).expect("temp dir");
+        dir
+    }
+
+    /// 3x3 tile, rows from north to south, south east sample void
+    fn hgt_tile() -> Vec<u8> {
+        [100i16, 200, 300, 400, 500, 600, 700, 800, -32768]
+            .iter()
+            .flat_map(|s| s.to_be_bytes())
+            .collect()
+    }
+
+    /// 2x2 raster with pixels of 0.5 degrees, north west corner at
+    /// 52N 10E, one sample marked as nodata
+    fn geotiff(nodata: i16) -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        let mut tiff = TiffEncoder::new(&mut buffer).expect("encoder");
+        let mut image = tiff.new_image::<colortype::GrayI16>(2, 2).expect("image");
+        let encoder = image.encoder();
+        encoder
+            .write_tag(Tag::ModelPixelScaleTag, &[0.5f64, 0.5, 0.0][..])
+            .expect("scale");
+        encoder
+            .write_tag(
+                Tag::ModelTiepointTag,
+                &[0.0f64, 0.0, 0.0, 10.0, 52.0, 0.0][..],
+            )
+            .expect("tiepoint");
+        encoder
+            .write_tag(
+                Tag::GeoKeyDirectoryTag,
+                &[1u16, 1, 0, 2, 1024, 0, 1, 2, 1025, 0, 1, 1][..],
+            )
+            .expect("geokeys");
+        encoder.write_tag(Tag::GdalNodata, "-9999").expect("nodata");
+        image.write_data(&[10, 20, 30, nodata]).expect("data");
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn test_hgt() {
+        let dir = temp_dir("hgt");
+        fs::write(dir.join("N50E010.hgt"), hgt_tile()).expect("tile");
+        fs::write(dir.join("README.txt"), "not a tile").expect("readme");
+        let dem = Dem::open(&[dir.to_string_lossy().into_owned()]).expect("dem");
+        assert_eq!(dem.elevation(51.0, 10.0), 100.0);
+        assert_eq!(dem.elevation(50.0, 10.5), 800.0);
+        assert!((dem.elevation(50.75, 10.25) - 300.0).abs() < 1e-9);
+        assert!((dem.elevation(50.5, 10.125) - 425.0).abs() < 1e-9);
+        assert!(dem.elevation(50.25, 10.75).is_nan());
+        assert!(dem.elevation(52.5, 10.5).is_nan());
+        fs::remove_dir_all(&dir).expect("cleanup");
+    }
+
+    #[test]
+    fn test_geotiff() {
+        let dir = temp_dir("geotiff");
+        let path = dir.join("dem.tif");
+        fs::write(&path, geotiff(40)).expect("geotiff");
+        let dem = Dem::open(&[path.to_string_lossy().into_owned()]).expect("dem");
+        assert!((dem.elevation(51.75, 10.25) - 10.0).abs() < 1e-9);
+        assert!((dem.elevation(51.5, 10.5) - 25.0).abs() < 1e-9);
+        assert!(dem.elevation(51.0, 10.5).is_nan());
+
+        fs::write(&path, geotiff(-9999)).expect("geotiff");
+        let dem = Dem::open(&[path.to_string_lossy().into_owned()]).expect("dem");
+        assert!(dem.elevation(51.5, 10.5).is_nan());
+        assert!((dem.elevation(51.75, 10.5) - 15.0).abs() < 1e-9);
+
+        fs::write(dir.join("dem.txt"), "").expect("text");
+        let text = dir.join("dem.txt").to_string_lossy().into_owned();
+        assert!(Dem::open(&[text]).is_err());
+        fs::remove_dir_all(&dir).expect("cleanup");
+    }
+}
@@ -0,0 +1,416 @@
+///
+///  Decoder for Windows device independent bitmaps (DIB)
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use anyhow::{anyhow, Context, Result};
+
+/// BITMAPCOREHEADER of OS/2 bitmaps with 16 bit sizes
+const DIB_CORE_HEADER: u32 = 12;
+/// BITMAPINFOHEADER, V4 and V5 headers are extensions of it
+const DIB_INFO_HEADER: u32 = 40;
+const DIB_FILE_HEADER: u32 = 14;
+const DIB_MAX_SIZE: u32 = 8192;
+
+const DIB_RGB: u32 = 0;
+const DIB_RLE8: u32 = 1;
+const DIB_RLE4: u32 = 2;
+const DIB_BITFIELDS: u32 = 3;
+const DIB_ALPHABITFIELDS: u32 = 6;
+
+/// Decoded image, rows from top to bottom as RGBA pixels
+#[derive(Debug)]
+pub(crate) struct DibImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl DibImage {
+    pub(crate) fn width(&self) -> u32 {
+        self.width
+    }
+    pub(crate) fn height(&self) -> u32 {
+        self.height
+    }
+    pub(crate) fn pixels(&self) -> &Vec<[u8; 4]> {
+        &self.pixels
+    }
+}
+
+/// Fields of the info header needed for decoding
+#[derive(Debug)]
+struct DibHeader {
+    size: u32,
+    width: u32,
+    height: u32,
+    top_down: bool,
+    bits: u16,
+    compression: u32,
+    colors: u32,
+    masks: [u32; 4],
+}
+
+impl DibHeader {
+    /// Palette entries are RGBTRIPLE for core headers, RGBQUAD otherwise
+    fn palette_entry(&self) -> usize {
+        if self.size == DIB_CORE_HEADER {
+            3
+        } else {
+            4
+        }
+    }
+    /// Info headers with bit fields compression are followed by the masks
+    fn masks_len(&self) -> usize {
+        match (self.size, self.compression) {
+            (DIB_INFO_HEADER, DIB_BITFIELDS) => 12,
+            (DIB_INFO_HEADER, DIB_ALPHABITFIELDS) => 16,
+            _ => 0,
+        }
+    }
+    fn palette_len(&self) -> usize {
+        self.colors as usize * self.palette_entry()
+    }
+    /// Offset of the pixel data from the start of the DIB
+    fn offset(&self) -> usize {
+        self.size as usize + self.masks_len() + self.palette_len()
+    }
+}
+
+fn dib_u16(buf: &[u8], pos: usize) -> Result<u16> {
+    buf.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .with_context(|| format!("dib: truncated at {}", pos))
+}
+
+fn dib_u32(buf: &[u8], pos: usize) -> Result<u32> {
+    buf.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .with_context(|| format!("dib: truncated at {}", pos))
+}
+
+fn dib_read_header(dib: &[u8]) -> Result<DibHeader> {
+    let size = dib_u32(dib, 0)?;
+    let (width, height, bits, compression, used) = match size {
+        DIB_CORE_HEADER => (
+            dib_u16(dib, 4)? as i32,
+            dib_u16(dib, 6)? as i16 as i32,
+            dib_u16(dib, 10)?,
+            DIB_RGB,
+            0,
+        ),
+        40 | 52 | 56 | 64 | 108 | 124 => (
+            dib_u32(dib, 4)? as i32,
+            dib_u32(dib, 8)? as i32,
+            dib_u16(dib, 14)?,
+            dib_u32(dib, 16)?,
+            dib_u32(dib, 32)?,
+        ),
+        _ => return Err(anyhow!("dib: unsupported header size {}", size)),
+    };
+    if !matches!(bits, 1 | 4 | 8 | 16 | 24 | 32) {
+        return Err(anyhow!("dib: unsupported bit count {}", bits));
+    }
+    let valid = match compression {
+        DIB_RGB => true,
+        DIB_RLE8 => bits == 8,
+        DIB_RLE4 => bits == 4,
+        DIB_BITFIELDS | DIB_ALPHABITFIELDS => bits == 16 || bits == 32,
+        _ => false,
+    };
+    if !valid {
+        return Err(anyhow!(
+            "dib: unsupported compression {} for {} bits",
+            compression,
+            bits
+        ));
+    }
+    if width <= 0 || height == 0 || width as u32 > DIB_MAX_SIZE {
+        return Err(anyhow!("dib: invalid size {}x{}", width, height));
+    }
+    if height.unsigned_abs() > DIB_MAX_SIZE {
+        return Err(anyhow!("dib: invalid size {}x{}", width, height));
+    }
+    if height < 0 && matches!(compression, DIB_RLE8 | DIB_RLE4) {
+        return Err(anyhow!("dib: top-down bitmaps can't be compressed"));
+    }
+    // Palettes are required up to 8 bits and optional above
+    let max_colors = if bits <= 8 { 1 << bits } else { 0 };
+    let colors = match used {
+        0 => max_colors,
+        n if bits <= 8 && n > max_colors => {
+            return Err(anyhow!("dib: {} colors for {} bits", n, bits));
+        }
+        n => n,
+    };
+    let mut header = DibHeader {
+        size,
+        width: width as u32,
+        height: height.unsigned_abs(),
+        top_down: height < 0,
+        bits,
+        compression,
+        colors,
+        masks: match bits {
+            16 => [0x7c00, 0x03e0, 0x001f, 0],
+            _ => [0x00ff0000, 0x0000ff00, 0x000000ff, 0],
+        },
+    };
+    if matches!(compression, DIB_BITFIELDS | DIB_ALPHABITFIELDS) {
+        let pos = DIB_INFO_HEADER as usize;
+        let count = if compression == DIB_ALPHABITFIELDS || size >= 56 {
+            4
+        } else {
+            3
+        };
+        for i in 0..count {
+            header.masks[i] = dib_u32(dib, pos + i * 4)?;
+        }
+    }
+    Ok(header)
+}
+
+fn dib_read_palette(dib: &[u8], header: &DibHeader) -> Result<Vec<[u8; 4]>> {
+    let start = header.size as usize + header.masks_len();
+    let palette = dib
+        .get(start..start + header.palette_len())
+        .with_context(|| format!("dib: palette of {} colors truncated", header.colors))?;
+    Ok(palette
+        .chunks_exact(header.palette_entry())
+        .map(|c| [c[2], c[1], c[0], 255])
+        .collect())
+}
+
+/// Scale a masked channel value to 8 bits
+fn dib_channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let bits = mask.count_ones();
+    let value = (value & mask) >> mask.trailing_zeros();
+    let max = if bits >= 32 {
+        u32::MAX
+    } else {
+        (1 << bits) - 1
+    };
+    ((value as u64 * 255 + max as u64 / 2) / max as u64) as u8
+}
+
+fn dib_color(palette: &[[u8; 4]], index: u8) -> Result<[u8; 4]> {
+    palette
+        .get(index as usize)
+        .copied()
+        .with_context(|| format!("dib: color {} outside palette", index))
+}
+
+/// Uncompressed rows padded to 32 bits
+fn dib_decode_rows(
+    data: &[u8],
+    header: &DibHeader,
+    palette: &[[u8; 4]],
+    pixels: &mut [[u8; 4]],
+) -> Result<()> {
+    let bits = header.bits as usize;
+    let width = header.width as usize;
+    let stride = (width * bits).div_ceil(32) * 4;
+    let [rmask, gmask, bmask, amask] = header.masks;
+    for row in 0..header.height as usize {
+        let line = data
+            .get(row * stride..(row + 1) * stride)
+            .with_context(|| format!("dib: row {} truncated", row))?;
+        let y = if header.top_down {
+            row
+        } else {
+            header.height as usize - 1 - row
+        };
+        for x in 0..width {
+            let pixel = match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits;
+                    let index = (line[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8;
+                    dib_color(palette, index)?
+                }
+                24 => [line[x * 3 + 2], line[x * 3 + 1], line[x * 3], 255],
+                _ => {
+                    let value = if bits == 16 {
+                        u16::from_le_bytes([line[x * 2], line[x * 2 + 1]]) as u32
+                    } else {
+                        u32::from_le_bytes([
+                            line[x * 4],
+                            line[x * 4 + 1],
+                            line[x * 4 + 2],
+                            line[x * 4 + 3],
+                        ])
+                    };
+                    let alpha = if amask == 0 {
+                        255
+                    } else {
+                        dib_channel(value, amask)
+                    };
+                    [
+                        dib_channel(value, rmask),
+                        dib_channel(value, gmask),
+                        dib_channel(value, bmask),
+                        alpha,
+                    ]
+                }
+            };
+            pixels[y * width + x] = pixel;
+        }
+    }
+    Ok(())
+}
+
+/// Run length encoded rows of 4 or 8 bits. Pixels skipped with delta
+/// escapes stay transparent.
+fn dib_decode_rle(
+    data: &[u8],
+    header: &DibHeader,
+    palette: &[[u8; 4]],
+    pixels: &mut [[u8; 4]],
+) -> Result<()> {
+    let width = header.width as usize;
+    let height = header.height as usize;
+    let four = header.compression == DIB_RLE4;
+    let (mut x, mut row) = (0usize, 0usize);
+    let mut pos = 0;
+    let mut put = |x: usize, row: usize, index: u8| -> Result<()> {
+        if x < width && row < height {
+            pixels[(height - 1 - row) * width + x] = dib_color(palette, index)?;
+        }
+        Ok(())
+    };
+    while pos + 1 < data.len() && row < height {
+        let (count, value) = (data[pos] as usize, data[pos + 1]);
+        pos += 2;
+        if count > 0 {
+            for i in 0..count {
+                let index = match (four, i % 2) {
+                    (false, _) => value,
+                    (true, 0) => value >> 4,
+                    (true, _) => value & 0x0f,
+                };
+                put(x, row, index)?;
+                x += 1;
+            }
+            continue;
+        }
+        match value {
+            0 => {
+                x = 0;
+                row += 1;
+            }
+            1 => break,
+            2 => {
+                let delta = data
+                    .get(pos..pos + 2)
+                    .with_context(|| "dib: rle delta truncated")?;
+                x += delta[0] as usize;
+                row += delta[1] as usize;
+                pos += 2;
+            }
+            n => {
+                let n = n as usize;
+                let len = if four { n.div_ceil(2) } else { n };
+                let run = data
+                    .get(pos..pos + len)
+                    .with_context(|| "dib: rle run truncated")?;
+                for i in 0..n {
+                    let index = match four {
+                        false => run[i],
+                        true if i % 2 == 0 => run[i / 2] >> 4,
+                        true => run[i / 2] & 0x0f,
+                    };
+                    put(x, row, index)?;
+                    x += 1;
+                }
+                // Absolute runs are padded to 16 bits
+                pos += len + len % 2;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decode a DIB as stored in overlay files, i.e. a bitmap file without
+/// the file header
+pub(crate) fn dib_decode(dib: &[u8]) -> Result<DibImage> {
+    let header = dib_read_header(dib)?;
+    let palette = dib_read_palette(dib, &header)?;
+    let data = dib
+        .get(header.offset()..)
+        .with_context(|| "dib: pixel data missing")?;
+    let (width, height) = (header.width, header.height);
+    let mut pixels = vec![[0u8; 4]; (width * height) as usize];
+    match header.compression {
+        DIB_RLE8 | DIB_RLE4 => dib_decode_rle(data, &header, &palette, &mut pixels)?,
+        _ => dib_decode_rows(data, &header, &palette, &mut pixels)?,
+    }
+    Ok(DibImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Decode a bitmap file
+pub(crate) fn dib_decode_bmp(bmp: &[u8]) -> Result<DibImage> {
+    if bmp.get(0..2) != Some(b"BM") {
+        return Err(anyhow!("dib: BM signature missing"));
+    }
+    let dib = bmp
+        .get(DIB_FILE_HEADER as usize..)
+        .with_context(|| "dib: header missing")?;
+    let header = dib_read_header(dib)?;
+    // The file header may place the pixel data after a gap
+    let offset = dib_u32(bmp, 10)? as usize;
+    let start = header.offset() + DIB_FILE_HEADER as usize;
+    if offset > start && offset <= bmp.len() {
+        let mut data = bmp[DIB_FILE_HEADER as usize..start].to_vec();
+        data.extend_from_slice(&bmp[offset..]);
+        return dib_decode(&data);
+    }
+    dib_decode(dib)
+}
+
+/// Prepend the file header to a DIB to get a bitmap file
+pub(crate) fn dib_bmp_file(dib: &[u8]) -> Result<Vec<u8>> {
+    let header = dib_read_header(dib)?;
+    let size = DIB_FILE_HEADER + dib.len() as u32;
+    let offset = DIB_FILE_HEADER + header.offset() as u32;
+    let mut data: Vec<u8> = Vec::with_capacity(size as usize);
+    data.extend_from_slice(b"BM");
+    data.extend_from_slice(&size.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&offset.to_le_bytes());
+    data.extend_from_slice(dib);
+    Ok(data)
+}
+
+/// Encode a decoded bitmap as PNG
+pub(crate) fn dib_png(image: &DibImage) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let mut encoder = ::png::Encoder::new(&mut buffer, image.width, image.height);
+    encoder.set_color(::png::ColorType::Rgba);
+    encoder.set_depth(::png::BitDepth::Eight);
+    let mut writer = encoder.write_header().with_context(|| "png header")?;
+    writer
+        .write_image_data(image.pixels.as_flattened())
+        .with_context(|| "png image data")?;
+    writer.finish().with_context(|| "png finish")?;
+    Ok(buffer)
+}
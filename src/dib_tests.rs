@@ -0,0 +1,149 @@
+///
+///  dib test cases
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::dib::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    /// BITMAPINFOHEADER of the given size followed by the rest
+    fn info(size: u32, width: i32, height: i32, bits: u16, compression: u32) -> Vec<u8> {
+        let mut dib = Vec::new();
+        dib.extend_from_slice(&size.to_le_bytes());
+        dib.extend_from_slice(&width.to_le_bytes());
+        dib.extend_from_slice(&height.to_le_bytes());
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&bits.to_le_bytes());
+        dib.extend_from_slice(&compression.to_le_bytes());
+        dib.resize(size as usize, 0);
+        dib
+    }
+
+    /// Palette of red and blue as RGBQUAD
+    fn palette(dib: &mut Vec<u8>, colors: usize) {
+        dib.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+        dib.resize(dib.len() + (colors - 2) * 4, 0);
+    }
+
+    #[test]
+    fn test_decode_palette() {
+        // 1 bit, bottom-up: blue row below a red/blue row
+        let mut dib = info(40, 2, 2, 1, 0);
+        palette(&mut dib, 2);
+        dib.extend_from_slice(&[0b1100_0000, 0, 0, 0, 0b0100_0000, 0, 0, 0]);
+        let image = dib_decode(&dib).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(image.pixels(), &vec![RED, BLUE, BLUE, BLUE]);
+
+        // 4 bit, top-down, index outside the palette of 2 colors
+        let mut dib = info(40, 2, -1, 4, 0);
+        dib[32] = 2;
+        palette(&mut dib, 2);
+        dib.extend_from_slice(&[0x10, 0, 0, 0]);
+        assert_eq!(dib_decode(&dib).unwrap().pixels(), &vec![BLUE, RED]);
+        dib[48] = 0x12;
+        assert!(dib_decode(&dib).is_err());
+        // Palette larger than the bit count allows
+        dib[32] = 17;
+        assert!(dib_decode(&dib).is_err());
+    }
+
+    #[test]
+    fn test_decode_core() {
+        // BITMAPCOREHEADER with 8 bits and RGBTRIPLE palette
+        let mut dib = Vec::new();
+        dib.extend_from_slice(&12u32.to_le_bytes());
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&8u16.to_le_bytes());
+        dib.extend_from_slice(&[0, 0, 255, 255, 0, 0]);
+        dib.resize(12 + 256 * 3, 0);
+        dib.extend_from_slice(&[1, 0, 0, 0]);
+        assert_eq!(dib_decode(&dib).unwrap().pixels(), &vec![BLUE]);
+    }
+
+    #[test]
+    fn test_decode_truecolor() {
+        // 16 bit 5-5-5
+        let mut dib = info(40, 2, 1, 16, 0);
+        dib.extend_from_slice(&[0x00, 0x7c, 0x1f, 0x00]);
+        assert_eq!(dib_decode(&dib).unwrap().pixels(), &vec![RED, BLUE]);
+
+        // 24 bit BGR
+        let mut dib = info(40, 1, 1, 24, 0);
+        dib.extend_from_slice(&[255, 0, 0, 0]);
+        assert_eq!(dib_decode(&dib).unwrap().pixels(), &vec![BLUE]);
+
+        // 32 bit V5 header with alpha mask
+        let mut dib = info(124, 1, 1, 32, 3);
+        for (i, mask) in [0xff000000u32, 0xff0000, 0xff00, 0xff].iter().enumerate() {
+            dib[40 + i * 4..44 + i * 4].copy_from_slice(&mask.to_le_bytes());
+        }
+        dib.extend_from_slice(&[0x80, 0, 0, 255]);
+        assert_eq!(dib_decode(&dib).unwrap().pixels(), &vec![[255, 0, 0, 128]]);
+
+        // 16 bit 5-6-5 from masks after the info header
+        let mut dib = info(40, 1, 1, 16, 3);
+        for mask in [0xf800u32, 0x07e0, 0x001f] {
+            dib.extend_from_slice(&mask.to_le_bytes());
+        }
+        dib.extend_from_slice(&[0xe0, 0x07, 0, 0]);
+        assert_eq!(dib_decode(&dib).unwrap().pixels(), &vec![[0, 255, 0, 255]]);
+    }
+
+    #[test]
+    fn test_decode_rle() {
+        // RLE8: run of 3 red, end of line, delta skipping one pixel,
+        // absolute run of blue, end of bitmap
+        let mut dib = info(40, 3, 2, 8, 1);
+        palette(&mut dib, 256);
+        dib.extend_from_slice(&[3, 0, 0, 0, 0, 2, 1, 0, 0, 3, 1, 1, 1, 0, 0, 1]);
+        let image = dib_decode(&dib).unwrap();
+        assert_eq!(image.pixels(), &vec![CLEAR, BLUE, BLUE, RED, RED, RED]);
+
+        // RLE4: run of 3 alternating pixels and absolute run of 3
+        let mut dib = info(40, 3, 2, 4, 2);
+        palette(&mut dib, 16);
+        dib.extend_from_slice(&[3, 0x01, 0, 0, 0, 3, 0x11, 0x00, 0, 1]);
+        let image = dib_decode(&dib).unwrap();
+        assert_eq!(image.pixels(), &vec![BLUE, BLUE, RED, RED, BLUE, RED]);
+    }
+
+    #[test]
+    fn test_bmp_file() {
+        let mut dib = info(108, 1, 1, 24, 0);
+        dib.extend_from_slice(&[0, 0, 255, 0]);
+        let bmp = dib_bmp_file(&dib).unwrap();
+        assert_eq!(&bmp[0..2], b"BM");
+        assert_eq!(
+            u32::from_le_bytes(bmp[10..14].try_into().unwrap()),
+            14 + 108
+        );
+        let image = dib_decode_bmp(&bmp).unwrap();
+        assert_eq!(image.pixels(), &vec![RED]);
+
+        let png = dib_png(&image).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
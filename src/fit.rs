@@ -0,0 +1,483 @@
+///
+///  Support for Garmin FIT course writing
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::env;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+
+use crate::format::Format;
+use crate::geodata::Geodata;
+use crate::geodata::Waypoint;
+use crate::geodata::WaypointList;
+use crate::geodata::MIN_SPEED;
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+/*
+ * A FIT file consists of a 14 byte header, a sequence of records and
+ * a trailing CRC over header and records:
+ *
+ * struct header {
+ *     u8   size;              // 14
+ *     u8   protocol_version;  // 0x10 = 1.0
+ *     u16  profile_version;
+ *     u32  data_size;         // size of records, without header and CRC
+ *     char magic[4];          // ".FIT"
+ *     u16  crc;               // CRC over the first 12 bytes
+ * };
+ *
+ * Every record starts with a header byte. Bit 6 marks a definition
+ * message, the lower 4 bits hold the local message type. A definition
+ * message describes the layout of subsequent data messages with the
+ * same local message type:
+ *
+ * struct definition {
+ *     u8   reserved;
+ *     u8   architecture;      // 0 = little endian
+ *     u16  global_message;
+ *     u8   field_count;
+ *     struct {
+ *         u8 number;
+ *         u8 size;
+ *         u8 base_type;
+ *     } fields[field_count];
+ * };
+ *
+ * All values are stored little endian. Timestamps count seconds since
+ * 1989-12-31 00:00:00 UTC, positions are stored as semicircles.
+ */
+
+const FIT_HEADER_SIZE: u8 = 14;
+const FIT_PROTOCOL_VERSION: u8 = 0x10;
+const FIT_PROFILE_VERSION: u16 = 2132;
+const FIT_DEFINITION: u8 = 0x40;
+/// Offset between the FIT epoch and the unix epoch in seconds
+const FIT_EPOCH: i64 = 631065600;
+
+const FIT_ENUM: u8 = 0x00;
+const FIT_UINT16: u8 = 0x84;
+const FIT_SINT32: u8 = 0x85;
+const FIT_UINT32: u8 = 0x86;
+const FIT_STRING: u8 = 0x07;
+
+const FIT_MESG_FILE_ID: u16 = 0;
+const FIT_MESG_LAP: u16 = 19;
+const FIT_MESG_RECORD: u16 = 20;
+const FIT_MESG_COURSE: u16 = 31;
+const FIT_MESG_COURSE_POINT: u16 = 32;
+
+const FIT_LOCAL_FILE_ID: u8 = 0;
+const FIT_LOCAL_COURSE: u8 = 1;
+const FIT_LOCAL_LAP: u8 = 2;
+const FIT_LOCAL_RECORD: u8 = 3;
+const FIT_LOCAL_COURSE_POINT: u8 = 4;
+
+const FIT_FILE_COURSE: u8 = 6;
+const FIT_MANUFACTURER_DEVELOPMENT: u16 = 255;
+const FIT_SPORT_GENERIC: u8 = 0;
+const FIT_COURSE_POINT_GENERIC: u8 = 0;
+const FIT_INVALID_UINT16: u16 = 0xffff;
+
+/// Size of string fields including the terminating zero
+const FIT_NAME_SIZE: u8 = 16;
+const FIT_DEFAULT_SPEED: f64 = 10.0;
+const FIT_DEFAULT_PROXIMITY: f64 = 50.0;
+
+const FIT_CRC_TABLE: [u16; 16] = [
+    0x0000, 0xcc01, 0xd801, 0x1400, 0xf001, 0x3c00, 0x2800, 0xe401, 0xa001, 0x6c00, 0x7800, 0xb401,
+    0x5000, 0x9c01, 0x8801, 0x4400,
+];
+
+pub fn fit_crc(crc: u16, data: &[u8]) -> u16 {
+    let mut crc = crc;
+    for byte in data {
+        let tmp = FIT_CRC_TABLE[(crc & 0xf) as usize];
+        crc = ((crc >> 4) & 0x0fff) ^ tmp ^ FIT_CRC_TABLE[(byte & 0xf) as usize];
+        let tmp = FIT_CRC_TABLE[(crc & 0xf) as usize];
+        crc = ((crc >> 4) & 0x0fff) ^ tmp ^ FIT_CRC_TABLE[((byte >> 4) & 0xf) as usize];
+    }
+    crc
+}
+
+fn fit_semicircles(degrees: f64) -> i32 {
+    (degrees * (2f64.powi(31) / 180.0)).round() as i32
+}
+
+fn fit_time(time: &DateTime<Utc>) -> u32 {
+    (time.timestamp() - FIT_EPOCH).clamp(0, u32::MAX as i64) as u32
+}
+
+fn fit_distance(meters: f64) -> u32 {
+    (meters * 100.0).round() as u32
+}
+
+fn fit_altitude(meters: f64) -> u16 {
+    if meters.is_nan() {
+        FIT_INVALID_UINT16
+    } else {
+        ((meters + 500.0) * 5.0).round().clamp(0.0, 65534.0) as u16
+    }
+}
+
+/// Record encoder collecting the data part of a FIT file
+struct FitEncoder {
+    buf: Vec<u8>,
+}
+
+impl FitEncoder {
+    fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+    fn definition(&mut self, local: u8, global: u16, fields: &[(u8, u8, u8)]) {
+        if get_debug() >= 2 {
+            eprintln!(
+                "fit: definition local {} global {:3} fields {}",
+                local,
+                global,
+                fields.len()
+            );
+        }
+        self.buf.push(FIT_DEFINITION | local);
+        self.buf.push(0);
+        self.buf.push(0);
+        self.buf.extend_from_slice(&global.to_le_bytes());
+        self.buf.push(fields.len() as u8);
+        for (number, size, base_type) in fields {
+            self.buf.extend_from_slice(&[*number, *size, *base_type]);
+        }
+    }
+    fn data(&mut self, local: u8) -> &mut Self {
+        self.buf.push(local);
+        self
+    }
+    fn u8(&mut self, value: u8) -> &mut Self {
+        self.buf.push(value);
+        self
+    }
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+    fn i32(&mut self, value: i32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+    /// Zero padded string truncated to size - 1 bytes at a character
+    /// boundary
+    fn string(&mut self, value: &str, size: u8) -> &mut Self {
+        let mut len = 0;
+        for (i, c) in value.char_indices() {
+            if i + c.len_utf8() >= size as usize {
+                break;
+            }
+            len = i + c.len_utf8();
+        }
+        self.buf.extend_from_slice(&value.as_bytes()[..len]);
+        self.buf.resize(self.buf.len() + size as usize - len, 0);
+        self
+    }
+    fn position(&mut self, waypoint: &Waypoint) -> &mut Self {
+        self.i32(fit_semicircles(waypoint.latitude()))
+            .i32(fit_semicircles(waypoint.longitude()))
+    }
+    fn finish(self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.buf.len() + FIT_HEADER_SIZE as usize + 2);
+        result.push(FIT_HEADER_SIZE);
+        result.push(FIT_PROTOCOL_VERSION);
+        result.extend_from_slice(&FIT_PROFILE_VERSION.to_le_bytes());
+        result.extend_from_slice(&(self.buf.len() as u32).to_le_bytes());
+        result.extend_from_slice(b".FIT");
+        let crc = fit_crc(0, &result);
+        result.extend_from_slice(&crc.to_le_bytes());
+        result.extend_from_slice(&self.buf);
+        let crc = fit_crc(0, &result);
+        result.extend_from_slice(&crc.to_le_bytes());
+        result
+    }
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FitFormat {
+    debug: u8,
+    testmode: bool,
+    course: usize,
+    speed: f64,
+    proximity: f64,
+}
+
+impl Format for FitFormat {
+    fn probe(&self, _buf: &[u8]) -> bool {
+        false
+    }
+    fn read(&self, _buf: &[u8]) -> Result<Geodata> {
+        Err(anyhow!("fit read not supported"))
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        // A course file holds exactly one course
+        let lists: Vec<&WaypointList> = geodata
+            .tracks()
+            .iter()
+            .chain(geodata.routes().iter())
+            .filter(|l| l.len() > 0)
+            .collect();
+        let Some(list) = lists.get(self.course - 1) else {
+            return Err(anyhow!(
+                "fit: course {} not found ({} tracks and routes)",
+                self.course,
+                lists.len()
+            ));
+        };
+        if self.debug >= 1 && lists.len() > 1 {
+            eprintln!(
+                "fit: writing course {} of {} tracks and routes",
+                self.course,
+                lists.len()
+            );
+        }
+        let start = if self.testmode {
+            DateTime::from_timestamp_secs(FIT_EPOCH).expect("invalid timestmap")
+        } else {
+            Utc::now()
+        };
+        let name = if list.name().is_empty() {
+            format!("Course {}", self.course)
+        } else {
+            list.name()
+        };
+        Ok(self.write_course(&name, list, geodata, &start))
+    }
+    fn name<'a>(&self) -> &'a str {
+        "fit"
+    }
+    fn can_read(&self) -> bool {
+        false
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+    fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "course" => {
+                let parsed = value
+                    .parse::<usize>()
+                    .with_context(|| format!("fit: {}: {}", key, value))?;
+                if parsed == 0 {
+                    return Err(anyhow!("fit: {} out of range: {}", key, value));
+                }
+                self.course = parsed;
+            }
+            "speed" | "proximity" => {
+                let parsed = value
+                    .parse::<f64>()
+                    .with_context(|| format!("fit: {}: {}", key, value))?;
+                match key {
+                    "speed" if parsed >= MIN_SPEED => self.speed = parsed,
+                    "proximity" if parsed >= 0.0 => self.proximity = parsed,
+                    _ => return Err(anyhow!("fit: {} out of range: {}", key, value)),
+                }
+            }
+            _ => return Err(anyhow!("fit: unknown option: {}", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for FitFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FitFormat {
+    pub fn new() -> Self {
+        Self {
+            debug: 0,
+            testmode: env::var("GGVTOGPX_TESTMODE").is_ok(),
+            course: 1,
+            speed: FIT_DEFAULT_SPEED,
+            proximity: FIT_DEFAULT_PROXIMITY,
+        }
+    }
+
+    fn write_course(
+        &self,
+        name: &str,
+        list: &WaypointList,
+        geodata: &Geodata,
+        start: &DateTime<Utc>,
+    ) -> Vec<u8> {
+        // Courses without recorded times are timed at a constant
+        // speed (km/h), distances are computed from the geometry.
+        let distances = list.distances();
+        let total = *distances.last().unwrap_or(&0.0);
+        let times: Vec<u32> = list
+            .times()
+            .unwrap_or_else(|| list.synthetic_times(*start, self.speed))
+            .iter()
+            .map(fit_time)
+            .collect();
+        let first = list.waypoints().first().expect("empty course");
+        let last = list.waypoints().last().expect("empty course");
+        let start_time = times[0];
+        let end_time = *times.last().expect("empty course");
+        if self.debug >= 1 {
+            eprintln!(
+                "fit: writing course (len: {:3}, name: \"{}\", distance: {:.0}m)",
+                list.len(),
+                name,
+                total
+            );
+        }
+
+        let mut encoder = FitEncoder::new();
+        encoder.definition(
+            FIT_LOCAL_FILE_ID,
+            FIT_MESG_FILE_ID,
+            &[
+                (0, 1, FIT_ENUM),
+                (1, 2, FIT_UINT16),
+                (2, 2, FIT_UINT16),
+                (4, 4, FIT_UINT32),
+            ],
+        );
+        encoder
+            .data(FIT_LOCAL_FILE_ID)
+            .u8(FIT_FILE_COURSE)
+            .u16(FIT_MANUFACTURER_DEVELOPMENT)
+            .u16(0)
+            .u32(start_time);
+
+        encoder.definition(
+            FIT_LOCAL_COURSE,
+            FIT_MESG_COURSE,
+            &[(4, 1, FIT_ENUM), (5, FIT_NAME_SIZE, FIT_STRING)],
+        );
+        encoder
+            .data(FIT_LOCAL_COURSE)
+            .u8(FIT_SPORT_GENERIC)
+            .string(name, FIT_NAME_SIZE);
+
+        encoder.definition(
+            FIT_LOCAL_LAP,
+            FIT_MESG_LAP,
+            &[
+                (253, 4, FIT_UINT32),
+                (2, 4, FIT_UINT32),
+                (3, 4, FIT_SINT32),
+                (4, 4, FIT_SINT32),
+                (5, 4, FIT_SINT32),
+                (6, 4, FIT_SINT32),
+                (7, 4, FIT_UINT32),
+                (8, 4, FIT_UINT32),
+                (9, 4, FIT_UINT32),
+            ],
+        );
+        let elapsed = end_time.saturating_sub(start_time).saturating_mul(1000);
+        encoder
+            .data(FIT_LOCAL_LAP)
+            .u32(end_time)
+            .u32(start_time)
+            .position(first)
+            .position(last)
+            .u32(elapsed)
+            .u32(elapsed)
+            .u32(fit_distance(total));
+
+        encoder.definition(
+            FIT_LOCAL_RECORD,
+            FIT_MESG_RECORD,
+            &[
+                (253, 4, FIT_UINT32),
+                (0, 4, FIT_SINT32),
+                (1, 4, FIT_SINT32),
+                (2, 2, FIT_UINT16),
+                (5, 4, FIT_UINT32),
+            ],
+        );
+        for (i, waypoint) in list.waypoints().iter().enumerate() {
+            encoder
+                .data(FIT_LOCAL_RECORD)
+                .u32(times[i])
+                .position(waypoint)
+                .u16(fit_altitude(waypoint.elevation()))
+                .u32(fit_distance(distances[i]));
+        }
+
+        let points = list.course_points(geodata.waypoints().waypoints(), self.proximity);
+        if get_debug() >= 2 {
+            for (index, waypoint) in points.iter() {
+                eprintln!(
+                    "fit: course point \"{}\" at index {} ({:.1}m)",
+                    waypoint.name(),
+                    index,
+                    list.waypoints()[*index].distance(waypoint)
+                );
+            }
+        }
+        if !points.is_empty() {
+            encoder.definition(
+                FIT_LOCAL_COURSE_POINT,
+                FIT_MESG_COURSE_POINT,
+                &[
+                    (254, 2, FIT_UINT16),
+                    (1, 4, FIT_UINT32),
+                    (2, 4, FIT_SINT32),
+                    (3, 4, FIT_SINT32),
+                    (4, 4, FIT_UINT32),
+                    (5, 1, FIT_ENUM),
+                    (6, FIT_NAME_SIZE, FIT_STRING),
+                ],
+            );
+        }
+        for (i, (index, waypoint)) in points.iter().enumerate() {
+            encoder
+                .data(FIT_LOCAL_COURSE_POINT)
+                .u16(i as u16)
+                .u32(times[*index])
+                .position(waypoint)
+                .u32(fit_distance(distances[*index]))
+                .u8(FIT_COURSE_POINT_GENERIC)
+                .string(&waypoint.name(), FIT_NAME_SIZE);
+        }
+        encoder.finish()
+    }
+}
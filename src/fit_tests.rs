@@ -0,0 +1,107 @@
+///
+///  fit test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::fit::*;
+    use crate::format::Format;
+    use crate::geodata::*;
+
+    #[test]
+    fn test_crc() {
+        // check value of the CRC-16/ARC algorithm used by FIT
+        assert_eq!(fit_crc(0, b"123456789"), 0xbb3d);
+    }
+
+    #[test]
+    fn test_write_course() {
+        let mut geodata = Geodata::new();
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.1));
+        geodata.add_track(track);
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.1)
+                .with_name("End"),
+        );
+
+        let data = FitFormat::new().write(&geodata).unwrap();
+        assert_eq!(data[0], 14);
+        assert_eq!(&data[8..12], b".FIT");
+        let size = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        assert_eq!(data.len(), 14 + size + 2);
+        assert_eq!(fit_crc(0, &data[..14]), 0);
+        assert_eq!(fit_crc(0, &data), 0);
+        // course point name
+        assert!(data.windows(4).any(|w| w == b"End\0"));
+    }
+
+    #[test]
+    fn test_long_course() {
+        // Elapsed time in milliseconds no longer fits into 32 bits
+        let start = chrono::DateTime::parse_from_rfc3339("2024-05-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let mut track = WaypointList::new();
+        track.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.0)
+                .with_time(start),
+        );
+        track.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.1)
+                .with_time(start + chrono::TimeDelta::days(60)),
+        );
+        let mut geodata = Geodata::new();
+        geodata.add_track(track);
+        assert!(FitFormat::new().write(&geodata).is_ok());
+    }
+
+    #[test]
+    fn test_no_course() {
+        let format = FitFormat::new();
+        assert!(format.write(&Geodata::new()).is_err());
+    }
+
+    #[test]
+    fn test_speed() {
+        let mut format = FitFormat::new();
+        assert!(format.set_option("speed", "1e-300").is_err());
+        assert!(format.set_option("speed", "0.1").is_ok());
+
+        // Long tracks at the slowest speed stay in range
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(-80.0).with_lon(0.0));
+        track.add_waypoint(Waypoint::new().with_lat(80.0).with_lon(179.0));
+        let start = chrono::DateTime::UNIX_EPOCH;
+        let times = track.synthetic_times(start, MIN_SPEED);
+        assert!(times[1] > start);
+        assert_eq!(
+            track.synthetic_times(start, 1e-300)[1],
+            chrono::DateTime::<chrono::Utc>::MAX_UTC
+        );
+    }
+}
@@ -18,14 +18,22 @@
 ///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 ///
 use crate::geodata::Geodata;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 pub trait Format {
     fn probe(&self, buf: &[u8]) -> bool;
     fn read(&self, buf: &[u8]) -> Result<Geodata>;
-    fn write(&self, geodata: &Geodata) -> Result<String>;
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>>;
     fn name<'a>(&self) -> &'a str;
     fn can_read(&self) -> bool;
     fn can_write(&self) -> bool;
     fn set_debug(&mut self, debug: u8);
+    /// File name extension used when writing files in batch mode
+    fn extension<'a>(&self) -> &'a str {
+        self.name()
+    }
+    /// Set format specific option given as key=value on the command line
+    fn set_option(&mut self, key: &str, _value: &str) -> Result<()> {
+        Err(anyhow!("{}: unknown option: {}", self.name(), key))
+    }
 }
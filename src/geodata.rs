@@ -17,6 +17,199 @@
 ///  along with this program; if not, write to the Free Software
 ///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 ///
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Color {
+    red: u8,
+    green: u8,
+    blue: u8,
+}
+
+impl Color {
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+    pub fn red(&self) -> u8 {
+        self.red
+    }
+    pub fn green(&self) -> u8 {
+        self.green
+    }
+    pub fn blue(&self) -> u8 {
+        self.blue
+    }
+}
+
+/// Drawing attributes of an object as found in the overlay file.
+/// Width is the line width in pixels. Zoom tells whether the object
+/// scales with the map zoom, rounded lines are drawn as curves.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Style {
+    color: Option<Color>,
+    width: Option<u16>,
+    zoom: Option<bool>,
+    rounded: bool,
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+    pub fn with_width(mut self, width: u16) -> Self {
+        self.width = Some(width);
+        self
+    }
+    pub fn with_zoom(mut self, zoom: bool) -> Self {
+        self.zoom = Some(zoom);
+        self
+    }
+    pub fn with_rounded(mut self, rounded: bool) -> Self {
+        self.rounded = rounded;
+        self
+    }
+    pub fn color(&self) -> Option<Color> {
+        self.color
+    }
+    pub fn width(&self) -> Option<u16> {
+        self.width
+    }
+    pub fn zoom(&self) -> Option<bool> {
+        self.zoom
+    }
+    pub fn rounded(&self) -> bool {
+        self.rounded
+    }
+}
+
+/// Geometric symbols an overlay can draw around a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShapeKind {
+    Rectangle,
+    Circle,
+    Triangle,
+}
+
+/// Symbol drawn at a waypoint. Width and height are in pixels, the
+/// angle is the rotation in degrees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    kind: ShapeKind,
+    width: u16,
+    height: u16,
+    angle: u16,
+}
+
+impl Shape {
+    pub fn new(kind: ShapeKind, width: u16, height: u16, angle: u16) -> Self {
+        Self {
+            kind,
+            width,
+            height,
+            angle,
+        }
+    }
+    pub fn kind(&self) -> ShapeKind {
+        self.kind
+    }
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+    pub fn angle(&self) -> u16 {
+        self.angle
+    }
+}
+
+/// Hyperlink attached to a waypoint or the whole file
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Link {
+    href: String,
+    text: String,
+}
+
+impl Link {
+    pub fn new(href: &str, text: &str) -> Self {
+        Self {
+            href: href.to_string(),
+            text: text.to_string(),
+        }
+    }
+    pub fn href(&self) -> &str {
+        &self.href
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Properties of the source format without a counterpart in the data
+/// model as key/value pairs in file order. They are kept to write the
+/// same format again without losing information.
+pub type Attributes = Vec<(String, String)>;
+
+/// Typefaces of text labels
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum LabelFont {
+    #[default]
+    Arial,
+    Courier,
+    Times,
+    Comic,
+}
+
+/// Text drawn at a waypoint, showing the waypoint name in the waypoint
+/// color. The size is the font size in pixels, the angle is the
+/// rotation in degrees and the background is the fill behind the text,
+/// if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    font: LabelFont,
+    size: u16,
+    angle: u16,
+    background: Option<Color>,
+}
+
+impl Label {
+    pub fn new(font: LabelFont, size: u16, angle: u16) -> Self {
+        Self {
+            font,
+            size,
+            angle,
+            background: None,
+        }
+    }
+    pub fn with_background(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+    pub fn font(&self) -> LabelFont {
+        self.font
+    }
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+    pub fn angle(&self) -> u16 {
+        self.angle
+    }
+    pub fn background(&self) -> Option<Color> {
+        self.background
+    }
+}
+
+/// Mean earth radius in meters
+pub const EARTH_RADIUS: f64 = 6371008.8;
+
+/// Slowest speed in km/h accepted for synthesized times
+pub const MIN_SPEED: f64 = 0.1;
 
 #[derive(Debug, Default, Clone)]
 pub struct Waypoint {
@@ -24,6 +217,18 @@ pub struct Waypoint {
     longitude: f64,
     elevation: f64,
     name: String,
+    comment: String,
+    description: String,
+    symbol: String,
+    kind: String,
+    time: Option<DateTime<Utc>>,
+    links: Vec<Link>,
+    style: Style,
+    shape: Option<Shape>,
+    label: Option<Label>,
+    group: Option<u16>,
+    index: Option<usize>,
+    attributes: Attributes,
 }
 
 impl Waypoint {
@@ -33,6 +238,18 @@ impl Waypoint {
             longitude: f64::NAN,
             elevation: f64::NAN,
             name: String::from(""),
+            comment: String::new(),
+            description: String::new(),
+            symbol: String::new(),
+            kind: String::new(),
+            time: None,
+            links: Vec::new(),
+            style: Style::default(),
+            shape: None,
+            label: None,
+            group: None,
+            index: None,
+            attributes: Attributes::new(),
         }
     }
     pub fn with_lat(mut self, lat: f64) -> Self {
@@ -51,6 +268,54 @@ impl Waypoint {
         self.name = name.to_string();
         self
     }
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.comment = comment.to_string();
+        self
+    }
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+    pub fn with_symbol(mut self, symbol: &str) -> Self {
+        self.symbol = symbol.to_string();
+        self
+    }
+    pub fn with_kind(mut self, kind: &str) -> Self {
+        self.kind = kind.to_string();
+        self
+    }
+    pub fn with_time(mut self, time: DateTime<Utc>) -> Self {
+        self.time = Some(time);
+        self
+    }
+    pub fn with_link(mut self, link: Link) -> Self {
+        self.links.push(link);
+        self
+    }
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.label = Some(label);
+        self
+    }
+    pub fn with_shape(mut self, shape: Shape) -> Self {
+        self.shape = Some(shape);
+        self
+    }
+    pub fn with_group(mut self, group: u16) -> Self {
+        self.group = Some(group);
+        self
+    }
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+    pub fn with_attributes(mut self, attributes: Attributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
     pub fn latitude(&self) -> f64 {
         self.latitude
     }
@@ -66,12 +331,147 @@ impl Waypoint {
     pub fn set_name(&mut self, name: &str) {
         self.name = name.to_owned();
     }
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+    pub fn set_comment(&mut self, comment: &str) {
+        self.comment = comment.to_owned();
+    }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    pub fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+    /// Free form classification of the waypoint (GPX type)
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        self.time
+    }
+    pub fn set_time(&mut self, time: DateTime<Utc>) {
+        self.time = Some(time);
+    }
+    pub fn set_elevation(&mut self, ele: f64) {
+        self.elevation = ele;
+    }
+    pub fn links(&self) -> &Vec<Link> {
+        &self.links
+    }
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+    pub fn shape(&self) -> Option<&Shape> {
+        self.shape.as_ref()
+    }
+    pub fn label(&self) -> Option<&Label> {
+        self.label.as_ref()
+    }
+    /// Overlay group number the object belongs to
+    pub fn group(&self) -> Option<u16> {
+        self.group
+    }
+    /// Position among the symbols of the ASCII overlay the object was
+    /// read from
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+    /// Great circle distance in meters (haversine formula)
+    pub fn distance(&self, other: &Waypoint) -> f64 {
+        let lat1 = self.latitude.to_radians();
+        let lat2 = other.latitude.to_radians();
+        let dlat = lat2 - lat1;
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
+/// Catmull-Rom spline through the points. Inserted points get an
+/// elevation interpolated between their neighbours.
+fn geodata_spline(points: &[Waypoint], steps: usize, closed: bool) -> Vec<Waypoint> {
+    let mut points = points;
+    if closed
+        && points.len() > 1
+        && points[0].latitude() == points[points.len() - 1].latitude()
+        && points[0].longitude() == points[points.len() - 1].longitude()
+    {
+        points = &points[..points.len() - 1];
+    }
+    let n = points.len();
+    if n < 3 || steps < 2 {
+        return points.to_vec();
+    }
+    let point = |i: isize| {
+        let i = if closed {
+            i.rem_euclid(n as isize)
+        } else {
+            i.clamp(0, n as isize - 1)
+        };
+        &points[i as usize]
+    };
+    let spline = |t: f64, p0: f64, p1: f64, p2: f64, p3: f64| {
+        0.5 * (2.0 * p1
+            + (p2 - p0) * t
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t * t
+            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t * t * t)
+    };
+    let count = if closed { n } else { n - 1 };
+    let mut result = Vec::with_capacity(count * steps + 1);
+    for i in 0..count as isize {
+        let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+        result.push(p1.clone());
+        for step in 1..steps {
+            let t = step as f64 / steps as f64;
+            let lat = spline(
+                t,
+                p0.latitude(),
+                p1.latitude(),
+                p2.latitude(),
+                p3.latitude(),
+            );
+            let lon = spline(
+                t,
+                p0.longitude(),
+                p1.longitude(),
+                p2.longitude(),
+                p3.longitude(),
+            );
+            let ele = p1.elevation() + (p2.elevation() - p1.elevation()) * t;
+            result.push(
+                Waypoint::new()
+                    .with_lat(lat)
+                    .with_lon(lon)
+                    .with_elevation(ele),
+            );
+        }
+    }
+    if !closed {
+        result.push(points[n - 1].clone());
+    }
+    result
 }
 
 #[derive(Debug, Default)]
 pub struct WaypointList {
     waypoints: Vec<Waypoint>,
     name: String,
+    comment: String,
+    description: String,
+    style: Style,
+    area: bool,
+    group: Option<u16>,
+    links: Vec<Link>,
+    segments: Vec<usize>,
+    index: Option<usize>,
+    attributes: Attributes,
 }
 
 impl WaypointList {
@@ -87,6 +487,123 @@ impl WaypointList {
     pub fn set_name(&mut self, name: &str) {
         self.name = name.to_owned()
     }
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+    pub fn set_comment(&mut self, comment: &str) {
+        self.comment = comment.to_owned()
+    }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    pub fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned()
+    }
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style
+    }
+    /// True if the list describes a closed, filled polygon rather
+    /// than a line.
+    pub fn area(&self) -> bool {
+        self.area
+    }
+    pub fn set_area(&mut self, area: bool) {
+        self.area = area
+    }
+    /// Overlay group number the object belongs to
+    pub fn group(&self) -> Option<u16> {
+        self.group
+    }
+    pub fn set_group(&mut self, group: u16) {
+        self.group = Some(group)
+    }
+    /// Position among the symbols of the ASCII overlay the list was
+    /// read from
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+    pub fn set_index(&mut self, index: usize) {
+        self.index = Some(index)
+    }
+    pub fn links(&self) -> &Vec<Link> {
+        &self.links
+    }
+    pub fn add_link(&mut self, link: Link) {
+        self.links.push(link);
+    }
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+    pub fn set_attributes(&mut self, attributes: Attributes) {
+        self.attributes = attributes
+    }
+    /// Start a new segment with the next waypoint added. Segments
+    /// split a track at gaps, e.g. when the GPS signal was lost.
+    pub fn start_segment(&mut self) {
+        let len = self.waypoints.len();
+        if len > 0 && self.segments.last() != Some(&len) {
+            self.segments.push(len);
+        }
+    }
+    /// Waypoints split into segments. A list without segment breaks
+    /// yields a single segment.
+    pub fn segments(&self) -> Vec<&[Waypoint]> {
+        let mut result = Vec::with_capacity(self.segments.len() + 1);
+        let mut start = 0;
+        for &end in self
+            .segments
+            .iter()
+            .filter(|&&end| end < self.waypoints.len())
+        {
+            result.push(&self.waypoints[start..end]);
+            start = end;
+        }
+        result.push(&self.waypoints[start..]);
+        result
+    }
+    /// Remove all segment breaks
+    pub fn join_segments(&mut self) {
+        self.segments.clear();
+    }
+    /// Split the list into one list per segment, keeping name and
+    /// attributes
+    pub fn split_segments(self) -> Vec<WaypointList> {
+        let mut result = Vec::with_capacity(self.segments.len() + 1);
+        for segment in self.segments() {
+            result.push(WaypointList {
+                waypoints: segment.to_vec(),
+                name: self.name.clone(),
+                comment: self.comment.clone(),
+                description: self.description.clone(),
+                style: self.style.clone(),
+                area: self.area,
+                group: self.group,
+                links: self.links.clone(),
+                segments: Vec::new(),
+                index: self.index,
+                attributes: self.attributes.clone(),
+            });
+        }
+        result
+    }
+    /// Replace every segment by a Catmull-Rom spline through its
+    /// points with the given number of steps between two points.
+    /// Areas are smoothed as closed rings.
+    pub fn smooth(&mut self, steps: usize) {
+        let mut waypoints = Vec::new();
+        let mut segments = Vec::new();
+        for segment in self.segments() {
+            if !waypoints.is_empty() {
+                segments.push(waypoints.len());
+            }
+            waypoints.extend(geodata_spline(segment, steps, self.area));
+        }
+        self.waypoints = waypoints;
+        self.segments = segments;
+    }
     pub fn extract_first_waypoint(&self) -> &Waypoint {
         &self.waypoints[0]
     }
@@ -96,12 +613,98 @@ impl WaypointList {
     pub fn len(&self) -> usize {
         self.waypoints.len()
     }
+    /// Cumulative distance in meters from the first waypoint to each
+    /// waypoint of the list
+    pub fn distances(&self) -> Vec<f64> {
+        let mut total = 0.0;
+        let mut result = Vec::with_capacity(self.waypoints.len());
+        for (i, waypoint) in self.waypoints.iter().enumerate() {
+            if i > 0 {
+                total += self.waypoints[i - 1].distance(waypoint);
+            }
+            result.push(total);
+        }
+        result
+    }
+    /// Times of all waypoints, if every waypoint has one
+    pub fn times(&self) -> Option<Vec<DateTime<Utc>>> {
+        if self.waypoints.is_empty() {
+            return None;
+        }
+        self.waypoints.iter().map(|w| w.time).collect()
+    }
+    /// Times for travelling along the list at a constant speed (km/h)
+    /// starting at start, rounded to seconds. Times out of range are
+    /// capped at the latest representable time.
+    pub fn synthetic_times(&self, start: DateTime<Utc>, speed: f64) -> Vec<DateTime<Utc>> {
+        self.distances()
+            .iter()
+            .map(|d| {
+                TimeDelta::try_seconds((d / speed * 3.6).round() as i64)
+                    .and_then(|delta| start.checked_add_signed(delta))
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC)
+            })
+            .collect()
+    }
+    /// Index of and distance in meters to the waypoint of the list
+    /// closest to the given waypoint
+    pub fn nearest(&self, waypoint: &Waypoint) -> Option<(usize, f64)> {
+        self.waypoints
+            .iter()
+            .map(|p| p.distance(waypoint))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+    /// Named waypoints within proximity (m) of the list, with the index
+    /// of the closest point of the list, in list order
+    pub fn course_points<'a>(
+        &self,
+        waypoints: &'a [Waypoint],
+        proximity: f64,
+    ) -> Vec<(usize, &'a Waypoint)> {
+        let mut result: Vec<(usize, &Waypoint)> = waypoints
+            .iter()
+            .filter(|w| !w.name().is_empty())
+            .filter_map(|w| match self.nearest(w) {
+                Some((index, distance)) if distance <= proximity => Some((index, w)),
+                _ => None,
+            })
+            .collect();
+        result.sort_by_key(|p| p.0);
+        result
+    }
+    /// Total ascent and descent in meters within segments, skipping
+    /// waypoints without elevation
+    pub fn ascent_descent(&self) -> (f64, f64) {
+        let (mut ascent, mut descent) = (0.0, 0.0);
+        for segment in self.segments() {
+            let elevations: Vec<f64> = segment
+                .iter()
+                .map(|w| w.elevation)
+                .filter(|e| !e.is_nan())
+                .collect();
+            for pair in elevations.windows(2) {
+                let delta = pair[1] - pair[0];
+                if delta > 0.0 {
+                    ascent += delta;
+                } else {
+                    descent -= delta;
+                }
+            }
+        }
+        (ascent, descent)
+    }
 }
 
+/// Binary data stored with the overlay. Attachments keep the file name
+/// they had in the overlay and the name of the object referencing
+/// them, if any.
 #[derive(Debug)]
 pub struct Data {
     kind: String,
     data: Vec<u8>,
+    name: String,
+    object: String,
 }
 
 impl Data {
@@ -111,25 +714,213 @@ impl Data {
     pub fn data(&self) -> &Vec<u8> {
         &self.data
     }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn object(&self) -> &str {
+        &self.object
+    }
+}
+
+/// Image placed on the map. The position is the centre of the image,
+/// width and height are in screen pixels and the angle is the rotation
+/// in degrees. The file name is set once the image is extracted and
+/// lets output formats link to it.
+#[derive(Debug, Default, Clone)]
+pub struct Bitmap {
+    name: String,
+    kind: String,
+    data: Vec<u8>,
+    position: Waypoint,
+    width: u32,
+    height: u32,
+    angle: u16,
+    filename: String,
+    index: Option<usize>,
+    attributes: Attributes,
+}
+
+impl Bitmap {
+    pub fn new(kind: &str, data: Vec<u8>, position: Waypoint) -> Self {
+        Self {
+            kind: kind.to_string(),
+            data,
+            position,
+            ..Default::default()
+        }
+    }
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+    pub fn with_angle(mut self, angle: u16) -> Self {
+        self.angle = angle;
+        self
+    }
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+    pub fn with_attributes(mut self, attributes: Attributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+    pub fn data(&self) -> &Vec<u8> {
+        &self.data
+    }
+    pub fn position(&self) -> &Waypoint {
+        &self.position
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn angle(&self) -> u16 {
+        self.angle
+    }
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+    pub fn set_filename(&mut self, filename: &str) {
+        self.filename = filename.to_string();
+    }
+    /// Position among the symbols of the ASCII overlay the bitmap was
+    /// read from
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+}
+
+/// Information about the file as a whole. The map fields describe the
+/// map view an overlay was drawn on: name of the raster map, centre of
+/// the view, zoom factor in percent and map scale (1:scale).
+#[derive(Debug, Default, Clone)]
+pub struct Metadata {
+    name: String,
+    description: String,
+    author: String,
+    generator: String,
+    time: Option<DateTime<Utc>>,
+    links: Vec<Link>,
+    map_name: String,
+    center: Option<Waypoint>,
+    zoom: Option<f64>,
+    scale: Option<u32>,
+    attributes: Attributes,
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    pub fn set_description(&mut self, description: &str) {
+        self.description = description.to_string();
+    }
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+    pub fn set_author(&mut self, author: &str) {
+        self.author = author.to_string();
+    }
+    /// Program that created the original file
+    pub fn generator(&self) -> &str {
+        &self.generator
+    }
+    pub fn set_generator(&mut self, generator: &str) {
+        self.generator = generator.to_string();
+    }
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        self.time
+    }
+    pub fn set_time(&mut self, time: DateTime<Utc>) {
+        self.time = Some(time);
+    }
+    pub fn links(&self) -> &Vec<Link> {
+        &self.links
+    }
+    pub fn add_link(&mut self, link: Link) {
+        self.links.push(link);
+    }
+    pub fn map_name(&self) -> &str {
+        &self.map_name
+    }
+    pub fn set_map_name(&mut self, map_name: &str) {
+        self.map_name = map_name.to_string();
+    }
+    pub fn center(&self) -> Option<&Waypoint> {
+        self.center.as_ref()
+    }
+    pub fn set_center(&mut self, center: Waypoint) {
+        self.center = Some(center);
+    }
+    pub fn zoom(&self) -> Option<f64> {
+        self.zoom
+    }
+    pub fn set_zoom(&mut self, zoom: f64) {
+        self.zoom = Some(zoom);
+    }
+    pub fn scale(&self) -> Option<u32> {
+        self.scale
+    }
+    pub fn set_scale(&mut self, scale: u32) {
+        self.scale = Some(scale);
+    }
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+    pub fn add_attribute(&mut self, key: &str, value: &str) {
+        self.attributes.push((key.to_string(), value.to_string()));
+    }
 }
 
 #[derive(Debug)]
 pub struct Geodata {
     debug: u8,
+    metadata: Metadata,
     waypoints: Vec<WaypointList>,
     routes: Vec<WaypointList>,
     tracks: Vec<WaypointList>,
+    bitmaps: Vec<Bitmap>,
     data: Vec<Data>,
+    groups: BTreeMap<u16, String>,
 }
 
 impl Geodata {
     pub fn new() -> Self {
         Self {
             debug: 0,
+            metadata: Metadata::new(),
             waypoints: vec![WaypointList::default()],
             routes: Vec::new(),
             tracks: Vec::new(),
+            bitmaps: Vec::new(),
             data: Vec::new(),
+            groups: BTreeMap::new(),
         }
     }
     pub fn with_debug(mut self, value: u8) -> Self {
@@ -172,11 +963,157 @@ impl Geodata {
         self.data.push(Data {
             kind: kind.to_string(),
             data: data,
+            name: String::new(),
+            object: String::new(),
         });
     }
+    /// Add a file attached to the overlay, the kind is taken from the
+    /// file name extension
+    pub fn add_attachment(&mut self, name: &str, data: Vec<u8>, object: &str) {
+        let kind = match name.rsplit_once('.') {
+            Some((_, extension)) => extension.to_lowercase(),
+            None => "dat".to_string(),
+        };
+        if self.debug >= 1 {
+            eprintln!(
+                "geodata: add data  (len: {:3}, kind: {}, name: \"{}\", object: \"{}\")",
+                data.len(),
+                kind,
+                name,
+                object
+            );
+        }
+        self.data.push(Data {
+            kind,
+            data,
+            name: name.to_string(),
+            object: object.to_string(),
+        });
+    }
+    pub fn add_bitmap(&mut self, bitmap: Bitmap) {
+        if self.debug >= 1 {
+            eprintln!(
+                "geodata: add bmp   (len: {:3}, kind: {}, lat: {:.5}, lon: {:.5}, size: {}x{}, angle: {})",
+                bitmap.data().len(),
+                bitmap.kind(),
+                bitmap.position().latitude(),
+                bitmap.position().longitude(),
+                bitmap.width(),
+                bitmap.height(),
+                bitmap.angle()
+            );
+        }
+        self.bitmaps.push(bitmap);
+    }
+    /// Name an overlay group, objects refer to it by number
+    pub fn add_group(&mut self, group: u16, name: &str) {
+        if self.debug >= 1 {
+            eprintln!("geodata: add group {} (name: \"{}\")", group, name);
+        }
+        self.groups.insert(group, name.to_string());
+    }
+    pub fn groups(&self) -> &BTreeMap<u16, String> {
+        &self.groups
+    }
+    pub fn group_name(&self, group: Option<u16>) -> Option<&str> {
+        self.groups.get(&group?).map(|n| n.as_str())
+    }
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
     pub fn waypoints(&self) -> &WaypointList {
         &self.waypoints[0]
     }
+    /// Assign times to tracks without recorded times as if they were
+    /// travelled one after another at a constant speed (km/h)
+    pub fn synthesize_times(&mut self, start: DateTime<Utc>, speed: f64) {
+        let mut start = start;
+        for track in self.tracks.iter_mut() {
+            if track.times().is_some() {
+                continue;
+            }
+            let times = track.synthetic_times(start, speed);
+            for (waypoint, time) in track.waypoints.iter_mut().zip(times.iter()) {
+                waypoint.set_time(*time);
+            }
+            if self.debug >= 1 {
+                eprintln!(
+                    "geodata: track times (len: {:3}, name: \"{}\", start: {})",
+                    track.len(),
+                    track.name(),
+                    start.to_rfc3339()
+                );
+            }
+            start = *times.last().unwrap_or(&start);
+        }
+    }
+    /// Smooth tracks and routes drawn with rounded lines
+    pub fn smooth_rounded(&mut self, steps: usize) {
+        for list in self.tracks.iter_mut().chain(self.routes.iter_mut()) {
+            if !list.style().rounded() {
+                continue;
+            }
+            let len = list.len();
+            list.smooth(steps);
+            if self.debug >= 1 {
+                eprintln!(
+                    "geodata: smoothed (len: {:3} -> {:3}, name: \"{}\")",
+                    len,
+                    list.len(),
+                    list.name()
+                );
+            }
+        }
+    }
+    /// Set elevations of waypoints, tracks and routes from lookup by
+    /// latitude and longitude. Only missing elevations are set unless
+    /// forced. Returns the number of elevations set.
+    pub fn fill_elevation(&mut self, lookup: impl Fn(f64, f64) -> f64, force: bool) -> usize {
+        let mut count = 0;
+        for list in self
+            .waypoints
+            .iter_mut()
+            .chain(self.tracks.iter_mut())
+            .chain(self.routes.iter_mut())
+        {
+            for waypoint in list.waypoints.iter_mut() {
+                if !force && !waypoint.elevation.is_nan() {
+                    continue;
+                }
+                let ele = lookup(waypoint.latitude, waypoint.longitude);
+                if !ele.is_nan() {
+                    waypoint.set_elevation(ele);
+                    count += 1;
+                }
+            }
+        }
+        if self.debug >= 1 {
+            eprintln!("geodata: filled {} elevations", count);
+        }
+        count
+    }
+    /// Remove segment breaks from all tracks
+    pub fn join_segments(&mut self) {
+        for track in self.tracks.iter_mut() {
+            track.join_segments();
+        }
+    }
+    /// Turn every track segment into a track of its own
+    pub fn split_segments(&mut self) {
+        let tracks = std::mem::take(&mut self.tracks);
+        for track in tracks {
+            self.tracks.extend(track.split_segments());
+        }
+        if self.debug >= 1 {
+            eprintln!("geodata: split segments into {} tracks", self.tracks.len());
+        }
+    }
     pub fn waypoints_len(&self) -> usize {
         if self.waypoints.len() > 0 {
             self.waypoints[0].len()
@@ -196,8 +1133,14 @@ impl Geodata {
     pub fn data(&self) -> &Vec<Data> {
         &self.data
     }
+    pub fn bitmaps(&self) -> &Vec<Bitmap> {
+        &self.bitmaps
+    }
+    pub fn bitmaps_mut(&mut self) -> &mut Vec<Bitmap> {
+        &mut self.bitmaps
+    }
     pub fn get_bounds(&self) -> Option<(Waypoint, Waypoint)> {
-        let min_lat = 0.0;
+        let min_lat = -90.0;
         let max_lat = 90.0;
         let min_lon = -180.0;
         let max_lon = 180.0;
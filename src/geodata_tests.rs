@@ -0,0 +1,98 @@
+///
+///  geodata test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::geodata::*;
+
+    #[test]
+    fn test_smooth_rounded() {
+        let mut geodata = Geodata::new();
+        let mut line = WaypointList::new();
+        line.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        line.add_waypoint(Waypoint::new().with_lat(50.1).with_lon(10.1));
+        line.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.2));
+        line.set_style(Style::new().with_rounded(true));
+        geodata.add_track(line);
+        let mut area = WaypointList::new();
+        area.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        area.add_waypoint(Waypoint::new().with_lat(50.1).with_lon(10.0));
+        area.add_waypoint(Waypoint::new().with_lat(50.1).with_lon(10.1));
+        area.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        area.set_area(true);
+        area.set_style(Style::new().with_rounded(true));
+        geodata.add_track(area);
+        let mut plain = WaypointList::new();
+        plain.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        plain.add_waypoint(Waypoint::new().with_lat(50.1).with_lon(10.1));
+        plain.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.2));
+        geodata.add_track(plain);
+
+        geodata.smooth_rounded(4);
+        // The line passes through the original points
+        let line = geodata.tracks()[0].waypoints();
+        assert_eq!(line.len(), 2 * 4 + 1);
+        assert_eq!(line[4].latitude(), 50.1);
+        assert_eq!(line[8].longitude(), 10.2);
+        assert!(line[2].latitude() > 50.05);
+        // The ring is closed around without repeating the first point
+        assert_eq!(geodata.tracks()[1].len(), 3 * 4);
+        assert_eq!(geodata.tracks()[2].len(), 3);
+    }
+
+    #[test]
+    fn test_fill_elevation() {
+        let mut geodata = Geodata::new();
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        track.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.1)
+                .with_lon(10.0)
+                .with_elevation(42.0),
+        );
+        track.add_waypoint(Waypoint::new().with_lat(60.0).with_lon(10.0));
+        geodata.add_track(track);
+        let lookup = |lat: f64, _lon: f64| if lat < 55.0 { lat * 10.0 } else { f64::NAN };
+
+        assert_eq!(geodata.fill_elevation(lookup, false), 1);
+        let elevations: Vec<f64> = geodata.tracks()[0]
+            .waypoints()
+            .iter()
+            .map(|w| w.elevation())
+            .collect();
+        assert_eq!(elevations[..2], [500.0, 42.0]);
+        assert!(elevations[2].is_nan());
+
+        assert_eq!(geodata.fill_elevation(lookup, true), 2);
+        assert_eq!(geodata.tracks()[0].waypoints()[1].elevation(), 501.0);
+    }
+
+    #[test]
+    fn test_ascent_descent() {
+        let mut track = WaypointList::new();
+        for ele in [100.0, 150.0, 120.0, f64::NAN, 200.0] {
+            track.add_waypoint(Waypoint::new().with_elevation(ele));
+        }
+        assert_eq!(track.ascent_descent(), (130.0, 30.0));
+        assert_eq!(WaypointList::new().ascent_descent(), (0.0, 0.0));
+    }
+}
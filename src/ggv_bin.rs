@@ -28,11 +28,22 @@ use nom::{
 
 use encoding_rs::mem::decode_latin1;
 
+use crate::dib::dib_bmp_file;
+use crate::dib::dib_decode;
 use crate::error::CustomError;
 use crate::format::Format;
+use crate::geodata::Bitmap;
+use crate::geodata::Color;
 use crate::geodata::Geodata;
+use crate::geodata::Link;
+use crate::geodata::Metadata;
+use crate::geodata::Shape;
+use crate::geodata::ShapeKind;
+use crate::geodata::Style;
 use crate::geodata::Waypoint;
 use crate::geodata::WaypointList;
+use crate::ggv_ovl::ggv_ovl_label;
+use crate::ggv_ovl::ggv_ovl_palette_color;
 
 static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
 
@@ -44,6 +55,24 @@ fn set_debug(debug: u8) {
     DEBUG_LEVEL.store(debug, Ordering::Relaxed);
 }
 
+/// Record flags of v3/v4 files
+const GGV_BIN_FLAG_ZOOM: u32 = 0x0001;
+const GGV_BIN_FLAG_NOZOOM: u32 = 0x0002;
+const GGV_BIN_FLAG_ROUNDED: u32 = 0x0800;
+const GGV_BIN_FLAG_CLOSED: u32 = 0x10000;
+/// Missing elevation, as in XML overlays
+const GGV_BIN_NO_ELEVATION: f64 = -32768.0;
+/// Elevations outside this range are taken as garbage
+const GGV_BIN_ELEVATION_RANGE: std::ops::RangeInclusive<f64> = -500.0..=9000.0;
+/// Prefixes of words in entry objects that are taken as links
+const GGV_BIN_URL_PREFIXES: [&str; 5] = ["http://", "https://", "ftp://", "mailto:", "file:"];
+const GGV_BIN_FLAGS: [(u32, &str); 4] = [
+    (GGV_BIN_FLAG_ZOOM, "ZOOM"),
+    (GGV_BIN_FLAG_NOZOOM, "NOZOOM"),
+    (GGV_BIN_FLAG_ROUNDED, "ROUNDED"),
+    (GGV_BIN_FLAG_CLOSED, "CLOSED"),
+];
+
 fn ggv_bin_read_bytes<'a>(
     i: &'a [u8],
     len: u32,
@@ -79,6 +108,33 @@ fn ggv_bin_read32<'a>(
     Ok((i, val))
 }
 
+/// Record flags of v3/v4 files, printed by name at debug level 2
+fn ggv_bin_read_flags<'a>(
+    i: &'a [u8],
+    descr: &'static str,
+) -> nom::IResult<&'a [u8], u32, CustomError> {
+    let (i, val) = context(descr, le_u32).parse(i)?;
+    if get_debug() >= 2 {
+        eprintln!("bin: {:<15} {}", descr, ggv_bin_flag_names(val));
+    }
+    Ok((i, val))
+}
+
+fn ggv_bin_flag_names(flags: u32) -> String {
+    let mut names: Vec<String> = GGV_BIN_FLAGS
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| name.to_string())
+        .collect();
+    let unknown = GGV_BIN_FLAGS
+        .iter()
+        .fold(flags, |rest, (flag, _)| rest & !flag);
+    if unknown != 0 || names.is_empty() {
+        names.push(format!("0x{:x}", unknown));
+    }
+    names.join("|")
+}
+
 fn ggv_bin_read_text16<'a>(
     i: &'a [u8],
     descr: &'static str,
@@ -102,10 +158,10 @@ fn ggv_bin_read_text16<'a>(
     Ok((i, decoded.to_owned()))
 }
 
-fn ggv_bin_read_text32<'a>(
+fn ggv_bin_read_bytes32<'a>(
     i: &'a [u8],
     descr: &'static str,
-) -> nom::IResult<&'a [u8], String, CustomError> {
+) -> nom::IResult<&'a [u8], &'a [u8], CustomError> {
     let (i, len) = ggv_bin_read32(i, descr)?;
     // The following check prevents passing an unsigned int with a value
     // greater than INT32_MAX to a signed int parameter in
@@ -118,7 +174,14 @@ fn ggv_bin_read_text32<'a>(
         let err = nom::Err::Failure(nom::error::make_error(i, nom::error::ErrorKind::TooLarge));
         return Err(err);
     }
-    let (i, buf) = ggv_bin_read_bytes(i, len, descr)?;
+    ggv_bin_read_bytes(i, len, descr)
+}
+
+fn ggv_bin_read_text32<'a>(
+    i: &'a [u8],
+    descr: &'static str,
+) -> nom::IResult<&'a [u8], String, CustomError> {
+    let (i, buf) = ggv_bin_read_bytes32(i, descr)?;
     let (_, text) = context(descr, take_till(|c| c == b'\0')).parse(buf)?;
     let decoded: String = decode_latin1(text)
         .replace("\r\n", " ")
@@ -135,6 +198,32 @@ fn ggv_bin_read_text32<'a>(
     Ok((i, decoded.to_owned()))
 }
 
+/// Third coordinate of v3/v4 records. It holds the elevation in
+/// meters, but is often left uninitialized by Geogrid-Viewer.
+fn ggv_bin_read_elevation<'a>(
+    i: &'a [u8],
+    descr: &'static str,
+) -> nom::IResult<&'a [u8], f64, CustomError> {
+    let (i, val) = context(descr, le_f64).parse(i)?;
+    if get_debug() >= 3 {
+        eprintln!("bin: {:<15} {}", descr, val);
+    }
+    Ok((i, val))
+}
+
+/// Elevation if the value is plausible: within range and at
+/// centimeter precision. Like in XML overlays -32768 marks a missing
+/// elevation, 0 and tiny values are taken as missing as well.
+fn ggv_bin_elevation(val: f64) -> Option<f64> {
+    if val == GGV_BIN_NO_ELEVATION || val.abs() < 0.01 || !GGV_BIN_ELEVATION_RANGE.contains(&val) {
+        return None;
+    }
+    if ((val * 100.0).round() / 100.0 - val).abs() > 1e-6 {
+        return None;
+    }
+    Some(val)
+}
+
 fn ggv_bin_parse_magic(buf: &[u8]) -> nom::IResult<&[u8], (u8, String), CustomError> {
     // example: "DOMGVCRD Ovlfile V3.0"
     let (_, magic) = context("magic", take(22usize)).parse(buf)?;
@@ -161,43 +250,54 @@ fn ggv_bin_read_double<'a>(
     Ok((i, val))
 }
 
-fn ggv_bin_write_bitmap<'a>(
-    bitmap: &'a [u8],
-    geodata: &mut Geodata,
-) -> nom::IResult<&'a [u8], (), CustomError> {
-    let (i, bmp_dib_size) = ggv_bin_read32(bitmap, "bmp dib size")?;
-    if bmp_dib_size != 40 {
-        return Ok((bitmap, ()));
-    }
-    let (i, _) = ggv_bin_read32(i, "bmp width")?;
-    let (i, _) = ggv_bin_read32(i, "bmp height")?;
-    let (i, _) = ggv_bin_read16(i, "bmp color plane")?;
-    let (i, bmp_pixel_bits) = ggv_bin_read16(i, "bmp pixel bits")?;
-    let (i, _) = ggv_bin_read32(i, "bmp compression")?;
-    let (i, _) = ggv_bin_read32(i, "bmp image size")?;
-    let (i, _) = ggv_bin_read32(i, "bmp x res")?;
-    let (i, _) = ggv_bin_read32(i, "bmp y res")?;
-    let (i, _) = ggv_bin_read32(i, "bmp num col")?;
-    let (_, _) = ggv_bin_read32(i, "bmp imp col")?;
-    let bmp_size: u32 = (bitmap.len() + 14) as u32;
-    let bmp_reserved1: u16 = 0x00;
-    let bmp_reserved2: u16 = 0x00;
-    let bmp_offset: u32;
-    // Files with 16bpp and above do not have a color table.
-    if bmp_pixel_bits >= 16 {
-        bmp_offset = 14 + bmp_dib_size;
+/// Convert 0x80bbggrr color values used by version 3.0 and 4.0
+fn ggv_bin_color(val: u32) -> Color {
+    Color::new(
+        (val & 0xff) as u8,
+        ((val >> 8) & 0xff) as u8,
+        ((val >> 16) & 0xff) as u8,
+    )
+}
+
+/// Line widths are stored as 101-115 in most places, but some version
+/// 2.0 files store the plain pixel value.
+fn ggv_bin_width(val: u16) -> u16 {
+    if val > 100 {
+        val - 100
     } else {
-        bmp_offset = 14 + bmp_dib_size + 2u32.pow(bmp_pixel_bits.into()) * 4;
+        val
     }
-    let mut data: Vec<u8> = Vec::new();
-    data.append(&mut ("BM".as_bytes()).to_vec());
-    data.append(&mut (bmp_size).to_le_bytes().to_vec());
-    data.append(&mut (bmp_reserved1).to_le_bytes().to_vec());
-    data.append(&mut (bmp_reserved2).to_le_bytes().to_vec());
-    data.append(&mut (bmp_offset).to_le_bytes().to_vec());
-    data.append(&mut bitmap.to_vec());
-    geodata.add_data("bmp", data);
-    Ok((bitmap, ()))
+}
+
+/// Add a bitmap record as BMP file. Without a stored size the pixel
+/// size of the image is used. Bitmaps that don't decode are skipped.
+fn ggv_bin_write_bitmap(
+    bitmap: &[u8],
+    position: Waypoint,
+    size: Option<(u32, u32)>,
+    angle: u16,
+    geodata: &mut Geodata,
+) {
+    let image = match dib_decode(bitmap) {
+        Ok(image) => image,
+        Err(err) => {
+            if get_debug() >= 1 {
+                eprintln!("bin: skipping bitmap: {}", err);
+            }
+            return;
+        }
+    };
+    let Ok(data) = dib_bmp_file(bitmap) else {
+        return;
+    };
+    let (width, height) = size
+        .filter(|(w, h)| *w > 0 && *h > 0)
+        .unwrap_or((image.width(), image.height()));
+    geodata.add_bitmap(
+        Bitmap::new("bmp", data, position)
+            .with_size(width, height)
+            .with_angle(angle),
+    );
 }
 
 //////////////////////////////////////////////////////////////////////
@@ -207,6 +307,7 @@ fn ggv_bin_write_bitmap<'a>(
 fn ggv_bin_read_v2_entries<'a>(
     buf: &'a [u8],
     entry_type: u16,
+    entry_group: u16,
     track_name: &String,
     geodata: &mut Geodata,
 ) -> nom::IResult<&'a [u8], (), CustomError> {
@@ -216,19 +317,32 @@ fn ggv_bin_read_v2_entries<'a>(
             let lat: f64;
             let lon: f64;
             let label: String;
-            (buf, _) = ggv_bin_read16(buf, "text color")?;
-            (buf, _) = ggv_bin_read16(buf, "text size")?;
-            (buf, _) = ggv_bin_read16(buf, "text trans")?;
-            (buf, _) = ggv_bin_read16(buf, "text font")?;
-            (buf, _) = ggv_bin_read16(buf, "text angle")?;
+            let color;
+            let size;
+            let trans;
+            let font;
+            let angle;
+            (buf, color) = ggv_bin_read16(buf, "text color")?;
+            (buf, size) = ggv_bin_read16(buf, "text size")?;
+            (buf, trans) = ggv_bin_read16(buf, "text trans")?;
+            (buf, font) = ggv_bin_read16(buf, "text font")?;
+            (buf, angle) = ggv_bin_read16(buf, "text angle")?;
             (buf, lon) = ggv_bin_read_double(buf, "text lon")?;
             (buf, lat) = ggv_bin_read_double(buf, "text lat")?;
             (buf, label) = ggv_bin_read_text16(buf, "text label")?;
+            let mut style = Style::new();
+            if let Some(color) = ggv_ovl_palette_color(color) {
+                style = style.with_color(color);
+            }
             geodata.add_waypoint(
                 Waypoint::new()
                     .with_lat(lat)
                     .with_lon(lon)
-                    .with_name(&label),
+                    .with_name(&label)
+                    .with_comment(track_name)
+                    .with_style(style)
+                    .with_label(ggv_ovl_label(font, size, trans, angle))
+                    .with_group(entry_group),
             );
         }
         3 | 4 => {
@@ -236,13 +350,22 @@ fn ggv_bin_read_v2_entries<'a>(
             let line_points;
             let mut lat: f64;
             let mut lon: f64;
-            (buf, _) = ggv_bin_read16(buf, "line color")?;
-            (buf, _) = ggv_bin_read16(buf, "line width")?;
+            let color;
+            let width;
+            (buf, color) = ggv_bin_read16(buf, "line color")?;
+            (buf, width) = ggv_bin_read16(buf, "line width")?;
             (buf, _) = ggv_bin_read16(buf, "line type")?;
             (buf, line_points) = ggv_bin_read16(buf, "line points")?;
             if !track_name.is_empty() {
                 waypoint_list.set_name(&track_name);
             }
+            let mut style = Style::new().with_width(ggv_bin_width(width));
+            if let Some(color) = ggv_ovl_palette_color(color) {
+                style = style.with_color(color);
+            }
+            waypoint_list.set_style(style);
+            waypoint_list.set_area(entry_type == 4);
+            waypoint_list.set_group(entry_group);
             for _ in 1..=line_points {
                 (buf, lon) = ggv_bin_read_double(buf, "text lon")?;
                 (buf, lat) = ggv_bin_read_double(buf, "text lat")?;
@@ -262,12 +385,14 @@ fn ggv_bin_read_v2_entries<'a>(
         }
         9 => {
             let bmp_len;
+            let lon;
+            let lat;
             (buf, _) = ggv_bin_read16(buf, "bmp color")?;
             (buf, _) = ggv_bin_read16(buf, "bmp prop1")?;
             (buf, _) = ggv_bin_read16(buf, "bmp prop2")?;
             (buf, _) = ggv_bin_read16(buf, "bmp prop3")?;
-            (buf, _) = ggv_bin_read_double(buf, "bmp lon")?;
-            (buf, _) = ggv_bin_read_double(buf, "bmp lat")?;
+            (buf, lon) = ggv_bin_read_double(buf, "bmp lon")?;
+            (buf, lat) = ggv_bin_read_double(buf, "bmp lat")?;
             (buf, bmp_len) = ggv_bin_read32(buf, "bmp len")?;
             // The following check prevents passing an unsigned int with a value
             // greater than INT32_MAX to a signed int parameter in
@@ -283,7 +408,8 @@ fn ggv_bin_read_v2_entries<'a>(
             }
             let bmp_data;
             (buf, bmp_data) = ggv_bin_read_bytes(buf, bmp_len, "bmp data")?;
-            let _ = ggv_bin_write_bitmap(bmp_data, geodata);
+            let position = Waypoint::new().with_lat(lat).with_lon(lon);
+            ggv_bin_write_bitmap(bmp_data, position, None, 0, geodata);
         }
         _ => {
             eprintln!("bin: Unsupported type: {:x}", entry_type);
@@ -294,19 +420,52 @@ fn ggv_bin_read_v2_entries<'a>(
     Ok((buf, ()))
 }
 
-fn ggv_bin_read_header_v2(buf: &[u8]) -> nom::IResult<&[u8], String, CustomError> {
+/// Map view stored in the header block. The block starts with four
+/// unknown bytes followed by the NUL terminated map name. The view
+/// centre is stored as lon/lat doubles `center_offset` bytes before
+/// the end of the block, preceded by the dimm and zoom factors.
+fn ggv_bin_read_map<'a>(
+    block: &'a [u8],
+    center_offset: usize,
+    metadata: &mut Metadata,
+) -> nom::IResult<&'a [u8], (), CustomError> {
+    let (buf, _) = take(4usize)(block)?;
+    let (_, name) = take_till(|c| c == b'\0')(buf)?;
+    let name = decode_latin1(name);
+    if get_debug() >= 2 {
+        eprintln!("bin: name = {:?}", name);
+    }
+    metadata.set_map_name(&name);
+    if block.len() < center_offset + 8 {
+        return Ok((block, ()));
+    }
+    let mut buf;
+    let zoom;
+    let lon;
+    let lat;
+    (buf, _) = take(block.len() - center_offset - 8)(block)?;
+    (buf, _) = ggv_bin_read32(buf, "map dimm")?;
+    (buf, zoom) = ggv_bin_read32(buf, "map zoom")?;
+    (buf, lon) = ggv_bin_read_double(buf, "map lon")?;
+    (_, lat) = ggv_bin_read_double(buf, "map lat")?;
+    if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) && zoom > 0 {
+        metadata.set_center(Waypoint::new().with_lat(lat).with_lon(lon));
+        metadata.set_zoom(zoom.into());
+    }
+    Ok((block, ()))
+}
+
+fn ggv_bin_read_header_v2<'a>(
+    buf: &'a [u8],
+    metadata: &mut Metadata,
+) -> nom::IResult<&'a [u8], (), CustomError> {
     let (buf, header_len) = ggv_bin_read16(buf, "map name len")?;
     if header_len > 0 {
-        let (buf, _) = take(4usize)(buf)?;
-        let (buf, name) = take(header_len - 4)(buf)?;
-        let (_, name) = take_till(|c| c == b'\0')(name)?;
-        let name = decode_latin1(name);
-        if get_debug() >= 2 {
-            eprintln!("bin: name = {:?}", name);
-        }
-        Ok((buf, name.into_owned()))
+        let (buf, block) = take(header_len)(buf)?;
+        ggv_bin_read_map(block, 100, metadata)?;
+        Ok((buf, ()))
     } else {
-        Ok((buf, String::new()))
+        Ok((buf, ()))
     }
 }
 
@@ -321,16 +480,17 @@ fn ggv_bin_read_v2<'a>(
     if get_debug() >= 2 {
         eprintln!("bin: header = {}", magic);
     }
-    (buf, _) = ggv_bin_read_header_v2(buf)?;
+    (buf, _) = ggv_bin_read_header_v2(buf, geodata.metadata_mut())?;
     while buf.len() > 0 {
         let pos = length - buf.len();
         let entry_type: u16;
+        let entry_group: u16;
         let entry_subtype: u16;
         if get_debug() >= 2 {
             eprintln!("------------------------------------ 0x{:x}", pos);
         }
         (buf, entry_type) = ggv_bin_read16(buf, "entry type")?;
-        (buf, _) = ggv_bin_read16(buf, "entry group")?;
+        (buf, entry_group) = ggv_bin_read16(buf, "entry group")?;
         (buf, _) = ggv_bin_read16(buf, "entry zoom")?;
         (buf, entry_subtype) = ggv_bin_read16(buf, "entry subtype")?;
 
@@ -340,7 +500,7 @@ fn ggv_bin_read_v2<'a>(
             (buf, val) = ggv_bin_read_text32(buf, "track name")?;
             track_name = val;
         }
-        (buf, _) = ggv_bin_read_v2_entries(buf, entry_type, &track_name, geodata)?;
+        (buf, _) = ggv_bin_read_v2_entries(buf, entry_type, entry_group, &track_name, geodata)?;
     }
     Ok((buf, ()))
 }
@@ -349,7 +509,10 @@ fn ggv_bin_read_v2<'a>(
 //            OVL Version 3.0 and 4.0
 //////////////////////////////////////////////////////////////////////
 
-fn ggv_bin_read_header_v34(buf: &[u8]) -> nom::IResult<&[u8], (u32, u32), CustomError> {
+fn ggv_bin_read_header_v34<'a>(
+    buf: &'a [u8],
+    metadata: &mut Metadata,
+) -> nom::IResult<&'a [u8], (u32, u32), CustomError> {
     let mut buf = buf;
     let label_count;
     let record_count;
@@ -366,36 +529,114 @@ fn ggv_bin_read_header_v34(buf: &[u8]) -> nom::IResult<&[u8], (u32, u32), Custom
     (buf, _) = ggv_bin_read16(buf, "unknown")?;
     (buf, _) = ggv_bin_read16(buf, "unknown")?;
     if header_len > 0 {
-        let mut map_name;
-        (buf, map_name) = ggv_bin_read_bytes(buf, header_len.into(), "map name")?;
-        (map_name, _) = take(4usize)(map_name)?;
-        (_, map_name) = take_till(|c| c == b'\0')(map_name)?;
-        if get_debug() >= 2 {
-            eprintln!("bin: name = {:?}", decode_latin1(map_name));
-        }
+        let block;
+        (buf, block) = ggv_bin_read_bytes(buf, header_len.into(), "map name")?;
+        ggv_bin_read_map(block, 52, metadata)?;
     }
     Ok((buf, (label_count, record_count)))
 }
 
-fn ggv_bin_read_label_v34(buf: &[u8], pos: usize) -> nom::IResult<&[u8], (), CustomError> {
+/// Labels name the groups records refer to with their entry group.
+/// The group number is kept in the first two bytes of the label
+/// number, labels without one are numbered in file order.
+fn ggv_bin_read_label_v34<'a>(
+    buf: &'a [u8],
+    pos: usize,
+    index: u16,
+    geodata: &mut Geodata,
+) -> nom::IResult<&'a [u8], (), CustomError> {
     let mut buf = buf;
+    let number;
+    let text;
     if get_debug() >= 2 {
         eprintln!("------------------------------------ 0x{:x}", pos);
     }
     (buf, _) = ggv_bin_read_bytes(buf, 0x08, "label header")?;
-    (buf, _) = ggv_bin_read_bytes(buf, 0x14, "label number")?;
-    (buf, _) = ggv_bin_read_text16(buf, "label text")?;
+    (buf, number) = ggv_bin_read_bytes(buf, 0x14, "label number")?;
+    (buf, text) = ggv_bin_read_text16(buf, "label text")?;
     (buf, _) = ggv_bin_read16(buf, "label flag1")?;
     (buf, _) = ggv_bin_read16(buf, "label flag2")?;
+    let group = match u16::from_le_bytes([number[0], number[1]]) {
+        0 => index + 1,
+        n => n,
+    };
+    if get_debug() >= 2 {
+        eprintln!("bin: label group {}: \"{}\"", group, text);
+    }
+    if !text.is_empty() {
+        geodata.add_group(group, &text);
+    }
     Ok((buf, ()))
 }
 
-fn ggv_bin_read_common_v34<'a>(buf: &'a [u8]) -> nom::IResult<&'a [u8], String, CustomError> {
+/// Text of an entry object, either UTF-16LE or latin1. UTF-16 is
+/// only assumed when all code units up to the terminator are in the
+/// latin1 range, short NUL terminated latin1 text would pass a looser
+/// check. Payloads with control characters other than line breaks are
+/// not text.
+fn ggv_bin_object_text(data: &[u8]) -> Option<String> {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    let utf16 =
+        data.len().is_multiple_of(2) && !units.is_empty() && units.iter().all(|&u| u < 0x100);
+    let text = if utf16 {
+        String::from_utf16(&units).ok()?
+    } else {
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        decode_latin1(&data[..end]).into_owned()
+    };
+    if text.trim().is_empty()
+        || text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\r' | '\n' | '\t'))
+    {
+        return None;
+    }
+    Some(text)
+}
+
+/// Description and links found in the entry objects of a record.
+/// URLs become links, the remaining text the description.
+fn ggv_bin_decode_objects(objects: &[&[u8]]) -> (String, Vec<Link>) {
+    let mut words = Vec::new();
+    let mut links = Vec::new();
+    for text in objects.iter().filter_map(|o| ggv_bin_object_text(o)) {
+        for word in text.split_whitespace() {
+            let lower = word.to_lowercase();
+            if GGV_BIN_URL_PREFIXES.iter().any(|p| lower.starts_with(p)) {
+                links.push(Link::new(word, word));
+            } else if lower.starts_with("www.") {
+                links.push(Link::new(&format!("http://{}", word), word));
+            } else {
+                words.push(word.to_string());
+            }
+        }
+    }
+    if get_debug() >= 2 && !objects.is_empty() {
+        eprintln!(
+            "bin: entry objects: {} text, {} links",
+            words.len(),
+            links.len()
+        );
+    }
+    (words.join(" "), links)
+}
+
+/// Text, group, flags and objects common to all v3/v4 records
+type EntryV34<'a> = (String, u16, u32, Vec<&'a [u8]>);
+
+fn ggv_bin_read_common_v34<'a>(buf: &'a [u8]) -> nom::IResult<&'a [u8], EntryV34<'a>, CustomError> {
     let mut buf = buf;
+    let mut entry_objects = Vec::new();
+    let entry_group;
+    let entry_flags;
     let entry_text;
     let entry_type1;
     let entry_type2;
-    (buf, _) = ggv_bin_read16(buf, "entry group")?;
+    (buf, entry_group) = ggv_bin_read16(buf, "entry group")?;
     (buf, _) = ggv_bin_read16(buf, "entry prop2")?;
     (buf, _) = ggv_bin_read16(buf, "entry prop3")?;
     (buf, _) = ggv_bin_read16(buf, "entry prop4")?;
@@ -403,18 +644,27 @@ fn ggv_bin_read_common_v34<'a>(buf: &'a [u8]) -> nom::IResult<&'a [u8], String,
     (buf, _) = ggv_bin_read16(buf, "entry prop6")?;
     (buf, _) = ggv_bin_read16(buf, "entry prop7")?;
     (buf, _) = ggv_bin_read16(buf, "entry prop8")?;
-    (buf, _) = ggv_bin_read16(buf, "entry zoom")?;
-    (buf, _) = ggv_bin_read16(buf, "entry prop10")?;
+    (buf, entry_flags) = ggv_bin_read_flags(buf, "entry flags")?;
     (buf, entry_text) = ggv_bin_read_text16(buf, "entry txt")?;
     (buf, entry_type1) = ggv_bin_read16(buf, "entry type1")?;
     if entry_type1 != 1 {
-        (buf, _) = ggv_bin_read_text32(buf, "entry object")?;
+        let object;
+        (buf, object) = ggv_bin_read_bytes32(buf, "entry object")?;
+        entry_objects.push(object);
     }
     (buf, entry_type2) = ggv_bin_read16(buf, "entry type2")?;
     if entry_type2 != 1 {
-        (buf, _) = ggv_bin_read_text32(buf, "entry object")?;
+        let object;
+        (buf, object) = ggv_bin_read_bytes32(buf, "entry object")?;
+        entry_objects.push(object);
     }
-    Ok((buf, entry_text.to_owned()))
+    let entry = (
+        entry_text.to_owned(),
+        entry_group,
+        entry_flags,
+        entry_objects,
+    );
+    Ok((buf, entry))
 }
 
 fn ggv_bin_read_record_v34<'a>(
@@ -425,40 +675,68 @@ fn ggv_bin_read_record_v34<'a>(
     let mut buf = buf;
     let entry_type;
     let label;
+    let group;
+    let flags;
+    let objects;
     if get_debug() >= 2 {
         eprintln!("------------------------------------ 0x{:x}", pos);
     }
     (buf, entry_type) = ggv_bin_read16(buf, "entry type")?;
-    (buf, label) = ggv_bin_read_common_v34(buf)?;
+    (buf, (label, group, flags, objects)) = ggv_bin_read_common_v34(buf)?;
+    let (description, links) = ggv_bin_decode_objects(&objects);
+    // Name of the object the payloads are attached to
+    let mut object = label.clone();
 
     match entry_type {
         0x02 => {
             // text
             let lat;
             let lon;
+            let ele;
             let txt;
+            let color;
+            let size;
+            let back;
+            let font;
+            let angle;
             (buf, _) = ggv_bin_read16(buf, "text prop1")?;
             (buf, _) = ggv_bin_read32(buf, "text prop2")?;
             (buf, _) = ggv_bin_read16(buf, "text prop3")?;
-            (buf, _) = ggv_bin_read32(buf, "text prop4")?;
-            (buf, _) = ggv_bin_read16(buf, "text ltype")?;
-            (buf, _) = ggv_bin_read16(buf, "text angle")?;
-            (buf, _) = ggv_bin_read16(buf, "text size")?;
-            (buf, _) = ggv_bin_read16(buf, "text area")?;
+            (buf, color) = ggv_bin_read32(buf, "text color")?;
+            (buf, size) = ggv_bin_read16(buf, "text size")?;
+            (buf, back) = ggv_bin_read16(buf, "text back")?;
+            (buf, font) = ggv_bin_read16(buf, "text font")?;
+            (buf, angle) = ggv_bin_read16(buf, "text angle")?;
             (buf, lon) = ggv_bin_read_double(buf, "text lon")?;
             (buf, lat) = ggv_bin_read_double(buf, "text lat")?;
-            (buf, _) = ggv_bin_read_double(buf, "text unk")?;
+            (buf, ele) = ggv_bin_read_elevation(buf, "text ele")?;
             (buf, txt) = ggv_bin_read_text16(buf, "text label")?;
-            geodata.add_waypoint(Waypoint::new().with_lat(lat).with_lon(lon).with_name(&txt));
+            let mut waypoint = Waypoint::new()
+                .with_lat(lat)
+                .with_lon(lon)
+                .with_elevation(ggv_bin_elevation(ele).unwrap_or(f64::NAN))
+                .with_name(&txt)
+                .with_comment(&label)
+                .with_description(&description)
+                .with_style(ggv_bin_flag_style(flags).with_color(ggv_bin_color(color)))
+                .with_label(ggv_ovl_label(font, size, back, angle))
+                .with_group(group);
+            for link in links {
+                waypoint = waypoint.with_link(link);
+            }
+            object = txt;
+            geodata.add_waypoint(waypoint);
         }
         //   area|line
         0x03 | 0x04 | 0x17 => {
             let line_points;
+            let color;
+            let width;
             (buf, _) = ggv_bin_read16(buf, "line prop1")?;
             (buf, _) = ggv_bin_read32(buf, "line prop2")?;
             (buf, _) = ggv_bin_read16(buf, "line prop3")?;
-            (buf, _) = ggv_bin_read32(buf, "line color")?;
-            (buf, _) = ggv_bin_read16(buf, "line size")?;
+            (buf, color) = ggv_bin_read32(buf, "line color")?;
+            (buf, width) = ggv_bin_read16(buf, "line size")?;
             (buf, _) = ggv_bin_read16(buf, "line stroke")?;
             (buf, line_points) = ggv_bin_read16(buf, "line points")?;
 
@@ -471,42 +749,110 @@ fn ggv_bin_read_record_v34<'a>(
             if !label.is_empty() {
                 track.set_name(&label);
             }
+            track.set_style(
+                ggv_bin_flag_style(flags)
+                    .with_color(ggv_bin_color(color))
+                    .with_width(ggv_bin_width(width)),
+            );
+            track.set_area(entry_type == 0x04 || flags & GGV_BIN_FLAG_CLOSED != 0);
+            track.set_group(group);
+            track.set_description(&description);
+            for link in links {
+                track.add_link(link);
+            }
+            // Elevations are only kept when all points have a plausible
+            // one or none at all
+            let mut points = Vec::new();
             for _ in 0..line_points {
                 let lon;
                 let lat;
+                let ele;
                 (buf, lon) = ggv_bin_read_double(buf, "line lon")?;
                 (buf, lat) = ggv_bin_read_double(buf, "line lat")?;
-                (buf, _) = ggv_bin_read_double(buf, "line unk")?;
-                track.add_waypoint(Waypoint::new().with_lat(lat).with_lon(lon));
+                (buf, ele) = ggv_bin_read_elevation(buf, "line ele")?;
+                points.push((lat, lon, ele));
+            }
+            let plausible = points
+                .iter()
+                .all(|p| p.2 == GGV_BIN_NO_ELEVATION || ggv_bin_elevation(p.2).is_some());
+            for (lat, lon, ele) in points {
+                let ele = match plausible {
+                    true => ggv_bin_elevation(ele).unwrap_or(f64::NAN),
+                    false => f64::NAN,
+                };
+                track.add_waypoint(
+                    Waypoint::new()
+                        .with_lat(lat)
+                        .with_lon(lon)
+                        .with_elevation(ele),
+                );
             }
             geodata.add_track(track);
         }
+        //   rectangle|circle|triangle
         0x05 | 0x06 | 0x07 => {
+            let color;
+            let width;
+            let height;
+            let angle;
+            let lon;
+            let lat;
+            let ele;
             (buf, _) = ggv_bin_read16(buf, "circle prop1")?;
             (buf, _) = ggv_bin_read32(buf, "circle prop2")?;
             (buf, _) = ggv_bin_read16(buf, "circle prop3")?;
-            (buf, _) = ggv_bin_read32(buf, "circle color")?;
-            (buf, _) = ggv_bin_read32(buf, "circle prop5")?;
-            (buf, _) = ggv_bin_read32(buf, "circle prop6")?;
+            (buf, color) = ggv_bin_read32(buf, "circle color")?;
+            (buf, width) = ggv_bin_read32(buf, "circle width")?;
+            (buf, height) = ggv_bin_read32(buf, "circle height")?;
             (buf, _) = ggv_bin_read16(buf, "circle ltype")?;
-            (buf, _) = ggv_bin_read16(buf, "circle angle")?;
+            (buf, angle) = ggv_bin_read16(buf, "circle angle")?;
             (buf, _) = ggv_bin_read16(buf, "circle size")?;
             (buf, _) = ggv_bin_read16(buf, "circle area")?;
-            (buf, _) = ggv_bin_read_double(buf, "circle lon")?;
-            (buf, _) = ggv_bin_read_double(buf, "circle lat")?;
-            (buf, _) = ggv_bin_read_double(buf, "circle unk")?;
+            (buf, lon) = ggv_bin_read_double(buf, "circle lon")?;
+            (buf, lat) = ggv_bin_read_double(buf, "circle lat")?;
+            (buf, ele) = ggv_bin_read_elevation(buf, "circle ele")?;
+            let kind = match entry_type {
+                0x05 => ShapeKind::Rectangle,
+                0x06 => ShapeKind::Circle,
+                _ => ShapeKind::Triangle,
+            };
+            // Size in pixels, the angle in plain degrees
+            let shape = Shape::new(
+                kind,
+                width.min(u16::MAX.into()) as u16,
+                height.min(u16::MAX.into()) as u16,
+                angle % 360,
+            );
+            let mut waypoint = Waypoint::new()
+                .with_lat(lat)
+                .with_lon(lon)
+                .with_elevation(ggv_bin_elevation(ele).unwrap_or(f64::NAN))
+                .with_name(&label)
+                .with_description(&description)
+                .with_style(ggv_bin_flag_style(flags).with_color(ggv_bin_color(color)))
+                .with_shape(shape)
+                .with_group(group);
+            for link in links {
+                waypoint = waypoint.with_link(link);
+            }
+            geodata.add_waypoint(waypoint);
         }
         0x09 => {
             let bmp_len;
+            let lon;
+            let lat;
+            let ele;
+            let width;
+            let height;
             (buf, _) = ggv_bin_read16(buf, "bmp prop1")?;
             (buf, _) = ggv_bin_read32(buf, "bmp prop2")?;
             (buf, _) = ggv_bin_read16(buf, "bmp prop3")?;
             (buf, _) = ggv_bin_read32(buf, "bmp prop4")?;
-            (buf, _) = ggv_bin_read32(buf, "bmp prop5")?;
-            (buf, _) = ggv_bin_read32(buf, "bmp prop6")?;
-            (buf, _) = ggv_bin_read_double(buf, "bmp lon")?;
-            (buf, _) = ggv_bin_read_double(buf, "bmp lat")?;
-            (buf, _) = ggv_bin_read_double(buf, "bmp unk")?;
+            (buf, width) = ggv_bin_read32(buf, "bmp width")?;
+            (buf, height) = ggv_bin_read32(buf, "bmp height")?;
+            (buf, lon) = ggv_bin_read_double(buf, "bmp lon")?;
+            (buf, lat) = ggv_bin_read_double(buf, "bmp lat")?;
+            (buf, ele) = ggv_bin_read_elevation(buf, "bmp ele")?;
             (buf, bmp_len) = ggv_bin_read32(buf, "bmp len")?;
             // The following check prevents passing an unsigned int with a value
             // greater than INT32_MAX to a signed int parameter in
@@ -521,9 +867,17 @@ fn ggv_bin_read_record_v34<'a>(
                 return Err(err);
             }
             let bmp_data;
-            (buf, _) = ggv_bin_read16(buf, "bmp prop")?;
+            let angle;
+            (buf, angle) = ggv_bin_read16(buf, "bmp angle")?;
             (buf, bmp_data) = ggv_bin_read_bytes(buf, bmp_len, "bmp data")?;
-            let _ = ggv_bin_write_bitmap(bmp_data, geodata);
+            let position = Waypoint::new()
+                .with_lat(lat)
+                .with_lon(lon)
+                .with_elevation(ggv_bin_elevation(ele).unwrap_or(f64::NAN));
+            // The angle is stored as 100-460
+            let angle = angle.saturating_sub(100) % 360;
+            let size = Some((width, height));
+            ggv_bin_write_bitmap(bmp_data, position, size, angle, geodata);
         }
         _ => {
             eprintln!("bin: Unsupported type: {:x}", entry_type);
@@ -531,10 +885,27 @@ fn ggv_bin_read_record_v34<'a>(
             return Err(err);
         }
     }
+    // Raw payloads are kept for extraction, named like the attachments
+    // of XML overlays
+    for payload in objects {
+        let name = format!("Object{}.dat", geodata.data().len());
+        geodata.add_attachment(&name, payload.to_vec(), &object);
+    }
 
     Ok((buf, ()))
 }
 
+/// Style with the zoom and rounded flags of a v3/v4 record
+fn ggv_bin_flag_style(flags: u32) -> Style {
+    let mut style = Style::new().with_rounded(flags & GGV_BIN_FLAG_ROUNDED != 0);
+    if flags & GGV_BIN_FLAG_ZOOM != 0 {
+        style = style.with_zoom(true);
+    } else if flags & GGV_BIN_FLAG_NOZOOM != 0 {
+        style = style.with_zoom(false);
+    }
+    style
+}
+
 fn ggv_bin_read_v34<'a>(
     buf: &'a [u8],
     geodata: &mut Geodata,
@@ -549,7 +920,7 @@ fn ggv_bin_read_v34<'a>(
     while buf.len() > 0 {
         let label_count;
         let record_count;
-        (buf, (label_count, record_count)) = ggv_bin_read_header_v34(buf)?;
+        (buf, (label_count, record_count)) = ggv_bin_read_header_v34(buf, geodata.metadata_mut())?;
         if label_count > 0 {
             if get_debug() >= 2 {
                 eprintln!(
@@ -557,8 +928,9 @@ fn ggv_bin_read_v34<'a>(
                     length - buf.len()
                 );
             }
-            for _ in 0..label_count {
-                (buf, _) = ggv_bin_read_label_v34(buf, length - buf.len())?;
+            for index in 0..label_count {
+                let pos = length - buf.len();
+                (buf, _) = ggv_bin_read_label_v34(buf, pos, index as u16, geodata)?;
             }
         }
         if record_count > 0 {
@@ -636,7 +1008,7 @@ impl Format for GgvBinFormat {
             }
         }
     }
-    fn write(&self, _geodata: &Geodata) -> Result<String> {
+    fn write(&self, _geodata: &Geodata) -> Result<Vec<u8>> {
         todo!("ggv_bin write support");
     }
     fn name<'a>(&self) -> &'a str {
@@ -0,0 +1,203 @@
+///
+///  ggv_bin test cases
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::ggv_bin::*;
+
+    #[test]
+    fn test_flags() {
+        let buf = include_bytes!("../testdata/ggv_bin-sample-v3.ovl");
+        let geodata = GgvBinFormat::new().read(buf).unwrap();
+        // All records of the sample are marked NOZOOM
+        for track in geodata.tracks() {
+            assert_eq!(track.style().zoom(), Some(false));
+            assert!(!track.style().rounded());
+        }
+        assert_eq!(
+            geodata.waypoints().waypoints()[0].style().zoom(),
+            Some(false)
+        );
+    }
+
+    fn text16(buf: &mut Vec<u8>, text: &str) {
+        buf.extend_from_slice(&(text.len() as u16).to_le_bytes());
+        buf.extend_from_slice(text.as_bytes());
+    }
+
+    /// Version 3.0 file with an optional label naming group 5 and a
+    /// text record in that group carrying the given entry objects and
+    /// elevation
+    fn v3_record(label: Option<&str>, objects: &[&[u8]], entry_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut buf = b"DOMGVCRD Ovlfile V3.0:\0".to_vec();
+        buf.extend_from_slice(&[0; 8]);
+        buf.extend_from_slice(&(label.is_some() as u32).to_le_bytes());
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        text16(&mut buf, "");
+        buf.extend_from_slice(&[0; 12]);
+        if let Some(label) = label {
+            buf.extend_from_slice(&[0; 8]);
+            let mut number = [0u8; 20];
+            number[0] = 5;
+            buf.extend_from_slice(&number);
+            text16(&mut buf, label);
+            buf.extend_from_slice(&[0; 4]);
+        }
+        buf.extend_from_slice(&entry_type.to_le_bytes());
+        buf.extend_from_slice(&5u16.to_le_bytes());
+        buf.extend_from_slice(&[0; 14]);
+        buf.extend_from_slice(&0x0002u32.to_le_bytes());
+        text16(&mut buf, "");
+        for i in 0..2 {
+            match objects.get(i) {
+                Some(object) => {
+                    buf.extend_from_slice(&2u16.to_le_bytes());
+                    buf.extend_from_slice(&(object.len() as u32).to_le_bytes());
+                    buf.extend_from_slice(object);
+                }
+                None => buf.extend_from_slice(&1u16.to_le_bytes()),
+            }
+        }
+        buf.extend_from_slice(&[0; 12]);
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    fn v3_text(label: Option<&str>, objects: &[&[u8]], ele: f64) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [120u16, 1, 1, 100] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [10.0f64, 50.0, ele] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        text16(&mut data, "Hut");
+        v3_record(label, objects, 2, &data)
+    }
+
+    #[test]
+    fn test_groups() {
+        let buf = v3_text(Some("Wege"), &[], 0.0);
+        let geodata = GgvBinFormat::new().read(&buf).unwrap();
+        assert_eq!(geodata.groups().get(&5).map(|n| n.as_str()), Some("Wege"));
+        let waypoint = &geodata.waypoints().waypoints()[0];
+        assert_eq!(waypoint.name(), "Hut");
+        assert_eq!(geodata.group_name(waypoint.group()), Some("Wege"));
+    }
+
+    #[test]
+    fn test_entry_objects() {
+        let utf16: Vec<u8> = "Hütte www.example.org"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        let binary = [0x01u8, 0x02, 0xff, 0x00, 0x10];
+        let buf = v3_text(None, &[&utf16, &binary], 0.0);
+        let geodata = GgvBinFormat::new().read(&buf).unwrap();
+        let waypoint = &geodata.waypoints().waypoints()[0];
+        assert_eq!(waypoint.description(), "Hütte");
+        assert_eq!(waypoint.links()[0].href(), "http://www.example.org");
+        // Both payloads are kept as they are
+        assert_eq!(geodata.data().len(), 2);
+        assert_eq!(geodata.data()[0].data(), &utf16);
+        assert_eq!(geodata.data()[1].name(), "Object1.dat");
+        assert_eq!(geodata.data()[1].data(), &binary.to_vec());
+        assert_eq!(geodata.data()[1].object(), "Hut");
+
+        // Latin1 text with a link in the middle
+        let buf = v3_text(None, &[b"see https://example.org/a for \xe4"], 0.0);
+        let geodata = GgvBinFormat::new().read(&buf).unwrap();
+        let waypoint = &geodata.waypoints().waypoints()[0];
+        assert_eq!(waypoint.description(), "see for ä");
+        assert_eq!(waypoint.links()[0].href(), "https://example.org/a");
+
+        // Short NUL terminated latin1 text is not taken as UTF-16
+        for (object, text) in [(&b"abc\0"[..], "abc"), (b"Info\0\0\0\0", "Info")] {
+            let buf = v3_text(None, &[object], 0.0);
+            let geodata = GgvBinFormat::new().read(&buf).unwrap();
+            assert_eq!(geodata.waypoints().waypoints()[0].description(), text);
+        }
+    }
+
+    #[test]
+    fn test_shapes() {
+        let mut data = Vec::new();
+        for value in [40u32, 20] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [1u16, 30, 102, 1] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [10.0f64, 50.0, 497.5] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let geodata = GgvBinFormat::new()
+            .read(&v3_record(None, &[], 5, &data))
+            .unwrap();
+        let waypoint = &geodata.waypoints().waypoints()[0];
+        assert_eq!(
+            waypoint.shape(),
+            Some(&Shape::new(ShapeKind::Rectangle, 40, 20, 30))
+        );
+        assert_eq!(waypoint.elevation(), 497.5);
+
+        let buf = include_bytes!("../testdata/ggv_bin-sample-v3.ovl");
+        let geodata = GgvBinFormat::new().read(buf).unwrap();
+        let shapes: Vec<(String, ShapeKind)> = geodata
+            .waypoints()
+            .waypoints()
+            .iter()
+            .filter_map(|w| w.shape().map(|s| (w.name(), s.kind())))
+            .collect();
+        assert_eq!(
+            shapes,
+            [
+                ("Rechteck".to_string(), ShapeKind::Rectangle),
+                ("Kreis".to_string(), ShapeKind::Circle),
+                ("Dreieck".to_string(), ShapeKind::Triangle)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_elevation() {
+        let elevation = |ele: f64| {
+            let geodata = GgvBinFormat::new().read(&v3_text(None, &[], ele)).unwrap();
+            geodata.waypoints().waypoints()[0].elevation()
+        };
+        assert_eq!(elevation(497.25), 497.25);
+        assert!(elevation(-32768.0).is_nan());
+        assert!(elevation(0.0).is_nan());
+        // Uninitialized values as found in the samples
+        assert!(elevation(50.000305175782955).is_nan());
+        assert!(elevation(1.0e-300).is_nan());
+        assert!(elevation(7.1e38).is_nan());
+        assert!(elevation(f64::NAN).is_nan());
+
+        // The sample lines only carry garbage
+        let buf = include_bytes!("../testdata/ggv_bin-sample-v3.ovl");
+        let geodata = GgvBinFormat::new().read(buf).unwrap();
+        for track in geodata.tracks() {
+            assert!(track.waypoints().iter().all(|w| w.elevation().is_nan()));
+        }
+    }
+}
@@ -18,22 +18,31 @@
 ///  along with this program; if not, write to the Free Software
 ///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 ///
-use std::collections::HashMap;
 use std::fmt;
 use std::sync::atomic::{AtomicU8, Ordering};
 
 use anyhow::{anyhow, Context, Result};
-use encoding_rs::mem::decode_latin1;
+use encoding_rs::mem::{decode_latin1, encode_latin1_lossy};
 use nom::{
-    bytes::complete::tag, bytes::complete::take_while, character::complete::alphanumeric1,
-    character::complete::multispace0, character::complete::space0, combinator::map,
-    combinator::opt, error::context, error::Error, multi::many, sequence::delimited,
-    sequence::pair, sequence::separated_pair, sequence::terminated, Err, IResult, Parser,
+    branch::alt, bytes::complete::tag, bytes::complete::take_while,
+    character::complete::alphanumeric1, character::complete::multispace0,
+    character::complete::space0, combinator::map, combinator::opt, error::context, multi::many,
+    sequence::delimited, sequence::pair, sequence::preceded, sequence::separated_pair,
+    sequence::terminated, Err, IResult, Parser,
 };
 
 use crate::error::CustomError;
 use crate::format::Format;
+use crate::geodata::Attributes;
+use crate::geodata::Bitmap;
+use crate::geodata::Color;
 use crate::geodata::Geodata;
+use crate::geodata::Label;
+use crate::geodata::LabelFont;
+use crate::geodata::Metadata;
+use crate::geodata::Shape;
+use crate::geodata::ShapeKind;
+use crate::geodata::Style;
 use crate::geodata::Waypoint;
 use crate::geodata::WaypointList;
 
@@ -48,6 +57,7 @@ fn set_debug(debug: u8) {
 }
 
 #[repr(u8)]
+#[derive(Clone, Copy)]
 enum SymbolType {
     Bitmap = 1,
     Text = 2,
@@ -88,7 +98,162 @@ impl fmt::Display for SymbolType {
     }
 }
 
-fn ggv_ovl_parse_section(i: &[u8]) -> IResult<&[u8], String, CustomError> {
+/// Defaults for objects without style, color index 3 is blue
+const GGV_OVL_DEFAULT_COLOR: u16 = 3;
+const GGV_OVL_TRACK_WIDTH: u16 = 5;
+const GGV_OVL_SHAPE_SIZE: u16 = 20;
+const GGV_OVL_SHAPE_WIDTH: u16 = 2;
+
+/// Color palette selected by Col= in ASCII overlays and by the color
+/// field of binary version 2.0 files.
+const GGV_OVL_PALETTE: [(u8, u8, u8); 8] = [
+    (255, 0, 0),     // 1: red
+    (0, 255, 0),     // 2: green
+    (0, 0, 255),     // 3: blue
+    (255, 255, 0),   // 4: yellow
+    (0, 0, 0),       // 5: black
+    (255, 255, 255), // 6: white
+    (0, 255, 255),   // 7: cyan
+    (255, 0, 255),   // 8: magenta
+];
+
+pub fn ggv_ovl_palette_color(index: u16) -> Option<Color> {
+    let index: usize = index.into();
+    if index == 0 || index > GGV_OVL_PALETTE.len() {
+        return None;
+    }
+    let (r, g, b) = GGV_OVL_PALETTE[index - 1];
+    Some(Color::new(r, g, b))
+}
+
+/// Palette index of the color closest to the given color
+pub fn ggv_ovl_palette_index(color: Color) -> u16 {
+    let distance = |&(r, g, b): &(u8, u8, u8)| {
+        let dr = i32::from(r) - i32::from(color.red());
+        let dg = i32::from(g) - i32::from(color.green());
+        let db = i32::from(b) - i32::from(color.blue());
+        dr * dr + dg * dg + db * db
+    };
+    let (index, _) = GGV_OVL_PALETTE
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, c)| distance(c))
+        .expect("empty palette");
+    (index + 1) as u16
+}
+
+/// Font numbers of text objects, shared by ASCII and binary overlays
+const GGV_OVL_FONTS: [(u16, LabelFont); 4] = [
+    (1, LabelFont::Arial),
+    (3, LabelFont::Courier),
+    (4, LabelFont::Times),
+    (10, LabelFont::Comic),
+];
+const GGV_OVL_LABEL_SIZE: u16 = 20;
+/// Text background: 1 is transparent, 2 solid and 3-8 hatch patterns,
+/// which are approximated by a solid fill
+const GGV_OVL_LABEL_BACKGROUND: Color = Color::new(255, 255, 255);
+
+/// Label from the raw font, size, background and angle fields of text
+/// objects. Size and angle are stored plus 100.
+pub fn ggv_ovl_label(font: u16, size: u16, back: u16, angle: u16) -> Label {
+    let font = GGV_OVL_FONTS
+        .iter()
+        .find(|(n, _)| *n == font)
+        .map_or(LabelFont::Arial, |(_, f)| *f);
+    let size = match size {
+        0 => GGV_OVL_LABEL_SIZE,
+        s if s > 100 => s - 100,
+        s => s,
+    };
+    let label = Label::new(font, size, angle.saturating_sub(100) % 360);
+    if back >= 2 {
+        label.with_background(GGV_OVL_LABEL_BACKGROUND)
+    } else {
+        label
+    }
+}
+
+pub fn ggv_ovl_font_number(font: LabelFont) -> u16 {
+    GGV_OVL_FONTS
+        .iter()
+        .find(|(_, f)| *f == font)
+        .map_or(1, |(n, _)| *n)
+}
+
+/// Build style from Col= and, for lines, Size= keys. Size is the line
+/// width plus 100.
+fn ggv_ovl_read_style(symbol: &OvlSection, line: bool) -> Style {
+    let mut style = Style::new();
+    if let Some(color) = symbol
+        .get("Col")
+        .and_then(|c| c.parse::<u16>().ok())
+        .and_then(ggv_ovl_palette_color)
+    {
+        style = style.with_color(color);
+    }
+    if line && let Some(size) = symbol.get("Size").and_then(|s| s.parse::<u16>().ok()) {
+        style = style.with_width(if size > 100 { size - 100 } else { size });
+    }
+    style
+}
+
+/// Line of a section, either a key/value pair with optional trailing
+/// comment or a comment line
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum OvlEntry {
+    Value {
+        key: String,
+        value: String,
+        comment: Option<String>,
+    },
+    Comment(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OvlSection {
+    name: String,
+    entries: Vec<OvlEntry>,
+}
+
+impl OvlSection {
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+    pub(crate) fn entries(&self) -> &Vec<OvlEntry> {
+        &self.entries
+    }
+    /// Value of the first entry with the given key
+    pub(crate) fn get(&self, key: &str) -> Option<&String> {
+        self.entries.iter().find_map(|e| match e {
+            OvlEntry::Value { key: k, value, .. } if k == key => Some(value),
+            _ => None,
+        })
+    }
+}
+
+/// ASCII overlay file as ordered list of sections. Comments before
+/// the first section are kept in the preamble.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct OvlDocument {
+    preamble: Vec<String>,
+    sections: Vec<OvlSection>,
+}
+
+impl OvlDocument {
+    pub(crate) fn preamble(&self) -> &Vec<String> {
+        &self.preamble
+    }
+    pub(crate) fn sections(&self) -> &Vec<OvlSection> {
+        &self.sections
+    }
+    /// First section with the given name
+    pub(crate) fn section(&self, name: &str) -> Option<&OvlSection> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+}
+
+pub(crate) fn ggv_ovl_parse_section(i: &[u8]) -> IResult<&[u8], String, CustomError> {
     let (i, res) = context(
         "parse section",
         delimited(tag("["), take_while(|c| c != b']'), tag("]")),
@@ -97,11 +262,16 @@ fn ggv_ovl_parse_section(i: &[u8]) -> IResult<&[u8], String, CustomError> {
     Ok((i, decode_latin1(res).into_owned().trim().to_string()))
 }
 
-fn ggv_ovl_parse_key_value(i: &[u8]) -> IResult<&[u8], (String, String), CustomError> {
+/// Comment starting with ';' up to the end of the line
+pub(crate) fn ggv_ovl_parse_comment(i: &[u8]) -> IResult<&[u8], String, CustomError> {
+    let (i, text) = preceded(tag(";"), take_while(|c| c != b'\n')).parse(i)?;
+    Ok((i, decode_latin1(text).into_owned().trim().to_string()))
+}
+
+fn ggv_ovl_parse_value(i: &[u8]) -> IResult<&[u8], (String, String), CustomError> {
     let (i, key) = alphanumeric1(i)?;
     let (i, _) = (space0, tag("="), space0).parse(i)?;
     let (i, val) = take_while(|c| c != b'\n' && c != b';').parse(i)?;
-    let (i, _) = opt(pair(tag(";"), take_while(|c| c != b'\n'))).parse(i)?;
     Ok((
         i,
         (
@@ -111,43 +281,137 @@ fn ggv_ovl_parse_key_value(i: &[u8]) -> IResult<&[u8], (String, String), CustomE
     ))
 }
 
-fn ggv_ovl_parse(
-    i: &[u8],
-) -> IResult<&[u8], HashMap<String, HashMap<String, String>>, CustomError> {
+pub(crate) fn ggv_ovl_parse_entry(i: &[u8]) -> IResult<&[u8], OvlEntry, CustomError> {
+    alt((
+        map(ggv_ovl_parse_comment, OvlEntry::Comment),
+        map(
+            pair(ggv_ovl_parse_value, opt(ggv_ovl_parse_comment)),
+            |((key, value), comment)| OvlEntry::Value {
+                key,
+                value,
+                comment,
+            },
+        ),
+    ))
+    .parse(i)
+}
+
+pub(crate) fn ggv_ovl_parse(i: &[u8]) -> IResult<&[u8], OvlDocument, CustomError> {
     map(
-        many(
-            0..,
-            separated_pair(
-                ggv_ovl_parse_section,
-                multispace0,
+        pair(
+            many(0.., terminated(ggv_ovl_parse_comment, multispace0)),
+            many(
+                0..,
                 map(
-                    context(
-                        "key value",
-                        many(0.., terminated(ggv_ovl_parse_key_value, multispace0)),
+                    separated_pair(
+                        ggv_ovl_parse_section,
+                        multispace0,
+                        context(
+                            "key value",
+                            many(0.., terminated(ggv_ovl_parse_entry, multispace0)),
+                        ),
                     ),
-                    |vec: Vec<_>| vec.into_iter().collect(),
+                    |(name, entries)| OvlSection { name, entries },
                 ),
             ),
         ),
-        |vec: Vec<_>| vec.into_iter().collect(),
+        |(preamble, sections)| OvlDocument { preamble, sections },
     )
     .parse(i)
 }
 
-fn ggv_ovl_process<'a>(ovl: &HashMap<String, HashMap<String, String>>) -> Result<Geodata> {
+/// Entries not taken into the data model, comments use the key ";"
+fn ggv_ovl_passthrough(section: &OvlSection, known: impl Fn(&str) -> bool) -> Attributes {
+    let mut result = Attributes::new();
+    for entry in section.entries() {
+        match entry {
+            OvlEntry::Value {
+                key,
+                value,
+                comment,
+            } => {
+                if !known(key) {
+                    result.push((key.clone(), value.clone()));
+                }
+                if let Some(comment) = comment {
+                    result.push((";".into(), comment.clone()));
+                }
+            }
+            OvlEntry::Comment(comment) => result.push((";".into(), comment.clone())),
+        }
+    }
+    result
+}
+
+/// Keys of symbol sections that are read into the data model
+fn ggv_ovl_known_key(typ: &SymbolType, key: &str) -> bool {
+    let coordinate = |prefix: &str| {
+        key.strip_prefix(prefix)
+            .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()))
+    };
+    match key {
+        "Typ" | "Text" => true,
+        "Group" | "Col" => !matches!(typ, SymbolType::Bitmap),
+        "Punkte" => matches!(typ, SymbolType::Line | SymbolType::Polygon),
+        "Size" => matches!(
+            typ,
+            SymbolType::Line | SymbolType::Polygon | SymbolType::Text
+        ),
+        "Font" | "Area" => matches!(typ, SymbolType::Text),
+        "Dir" if matches!(typ, SymbolType::Text) => true,
+        "Width" | "Height" | "Dir" => matches!(
+            typ,
+            SymbolType::Bitmap | SymbolType::Rectangle | SymbolType::Circle | SymbolType::Triangle
+        ),
+        _ => coordinate("XKoord") || coordinate("YKoord"),
+    }
+}
+
+/// All [Symbol N] sections ordered by number, sections without a
+/// valid number go last
+fn ggv_ovl_symbols(ovl: &OvlDocument) -> Vec<&OvlSection> {
+    let mut result: Vec<&OvlSection> = ovl
+        .sections()
+        .iter()
+        .filter(|s| s.name().starts_with("Symbol "))
+        .collect();
+    result.sort_by_key(|s| {
+        s.name()["Symbol ".len()..]
+            .trim()
+            .parse::<u32>()
+            .unwrap_or(u32::MAX)
+    });
+    result
+}
+
+fn ggv_ovl_process(ovl: &OvlDocument) -> Result<Geodata> {
     let mut geodata = Geodata::new().with_debug(get_debug());
     let mut route_count = 1;
     let mut track_count = 1;
     let mut waypoint_count = 1;
-    let overlay = ovl.get("Overlay").with_context(|| "Overlay missing")?;
-    let symbols = overlay.get("Symbols").with_context(|| "Symbols missing")?;
-    let symbols: u16 = symbols.parse().with_context(|| "Symbols u16")?;
+    // Hand edited files often have gaps in the symbol numbers or a
+    // wrong count, so all symbol sections are read in number order
+    let symbols = ggv_ovl_symbols(ovl);
+    let declared = ovl
+        .section("Overlay")
+        .and_then(|o| o.get("Symbols"))
+        .and_then(|s| s.parse::<usize>().ok());
     if get_debug() >= 2 {
-        eprintln!("ovl: Symbols: {}", symbols)
+        eprintln!("ovl: Symbols: {:?}, found: {}", declared, symbols.len())
     };
-    for i in 1..=symbols {
-        let key = format!("Symbol {}", i);
-        let symbol = ovl.get(&key).with_context(|| format!("{} missing", key))?;
+    match declared {
+        Some(declared) if declared != symbols.len() => eprintln!(
+            "ovl: warning: Symbols={} but {} symbol sections found",
+            declared,
+            symbols.len()
+        ),
+        None if get_debug() >= 1 => {
+            eprintln!("ovl: no symbol count, {} symbols found", symbols.len())
+        }
+        _ => (),
+    }
+    for (index, symbol) in symbols.into_iter().enumerate() {
+        let key = symbol.name();
         if get_debug() >= 2 {
             eprintln!("ovl: === {} ===", key)
         };
@@ -221,6 +485,12 @@ fn ggv_ovl_process<'a>(ovl: &HashMap<String, HashMap<String, String>>) -> Result
                         }
                     }
                 }
+                waypoint_list.set_style(ggv_ovl_read_style(symbol, true));
+                waypoint_list.set_area(matches!(typ, SymbolType::Polygon));
+                waypoint_list.set_group(group);
+                waypoint_list.set_index(index);
+                waypoint_list
+                    .set_attributes(ggv_ovl_passthrough(symbol, |k| ggv_ovl_known_key(&typ, k)));
                 if group > 1 {
                     geodata.add_route(waypoint_list);
                 } else {
@@ -249,23 +519,344 @@ fn ggv_ovl_process<'a>(ovl: &HashMap<String, HashMap<String, String>>) -> Result
                         ykoord, xkoord
                     )
                 }
-                let mut waypoint = Waypoint::new().with_lat(ykoord).with_lon(xkoord);
+                let mut waypoint = Waypoint::new()
+                    .with_lat(ykoord)
+                    .with_lon(xkoord)
+                    .with_style(ggv_ovl_read_style(symbol, false))
+                    .with_index(index);
+                if let Some(shape) = ggv_ovl_read_shape(symbol, &typ) {
+                    waypoint = waypoint.with_shape(shape);
+                }
+                if matches!(typ, SymbolType::Text) {
+                    waypoint = waypoint.with_label(ggv_ovl_read_label(symbol));
+                }
+                if let Some(group) = symbol.get("Group").and_then(|g| g.parse().ok()) {
+                    waypoint = waypoint.with_group(group);
+                }
                 match symbol.get("Text") {
                     Some(text) => {
                         waypoint.set_name(text);
                     }
                     None => {
-                        waypoint.set_name(&key);
+                        waypoint.set_name(key);
                     }
                 }
+                waypoint = waypoint
+                    .with_attributes(ggv_ovl_passthrough(symbol, |k| ggv_ovl_known_key(&typ, k)));
                 geodata.add_waypoint(waypoint);
             }
-            SymbolType::Bitmap => {}
+            SymbolType::Bitmap => {
+                // ASCII overlays only refer to the image, the reference
+                // is kept with the passthrough entries
+                let ykoord = symbol.get("YKoord").and_then(|v| v.parse::<f64>().ok());
+                let xkoord = symbol.get("XKoord").and_then(|v| v.parse::<f64>().ok());
+                let (Some(ykoord), Some(xkoord)) = (ykoord, xkoord) else {
+                    if get_debug() >= 1 {
+                        eprintln!("ovl: {} bitmap without position", key);
+                    }
+                    continue;
+                };
+                let value = |key: &str| symbol.get(key).and_then(|v| v.parse::<u32>().ok());
+                let angle = value("Dir").map_or(0, |d| d.saturating_sub(100) % 360);
+                let bitmap = Bitmap::new(
+                    "bmp",
+                    Vec::new(),
+                    Waypoint::new().with_lat(ykoord).with_lon(xkoord),
+                )
+                .with_name(symbol.get("Text").map_or("", |t| t.as_str()))
+                .with_size(value("Width").unwrap_or(0), value("Height").unwrap_or(0))
+                .with_angle(angle as u16)
+                .with_index(index)
+                .with_attributes(ggv_ovl_passthrough(symbol, |k| ggv_ovl_known_key(&typ, k)));
+                geodata.add_bitmap(bitmap);
+            }
         }
     }
+    ggv_ovl_read_sections(ovl, geodata.metadata_mut());
     Ok(geodata)
 }
 
+/// Label of text symbols from Font=, Size=, Area= and Dir=
+fn ggv_ovl_read_label(symbol: &OvlSection) -> Label {
+    let value = |key: &str| symbol.get(key).and_then(|v| v.parse::<u16>().ok());
+    ggv_ovl_label(
+        value("Font").unwrap_or(1),
+        value("Size").unwrap_or(0),
+        value("Area").unwrap_or(1),
+        value("Dir").unwrap_or(100),
+    )
+}
+
+/// Shape of rectangle, circle and triangle symbols. Dir= is the
+/// rotation in degrees plus 100.
+fn ggv_ovl_read_shape(symbol: &OvlSection, typ: &SymbolType) -> Option<Shape> {
+    let kind = match typ {
+        SymbolType::Rectangle => ShapeKind::Rectangle,
+        SymbolType::Circle => ShapeKind::Circle,
+        SymbolType::Triangle => ShapeKind::Triangle,
+        _ => return None,
+    };
+    let value = |key: &str| symbol.get(key).and_then(|v| v.parse::<u16>().ok());
+    let width = value("Width").unwrap_or(GGV_OVL_SHAPE_SIZE);
+    let height = value("Height").unwrap_or(width);
+    let angle = value("Dir").map_or(0, |d| d.saturating_sub(100));
+    Some(Shape::new(kind, width, height, angle))
+}
+
+/// Map view and passthrough entries of all sections other than the
+/// symbols. Passthrough keys are prefixed with the section name, the
+/// preamble uses an empty section name. An empty key records the
+/// number of symbol sections before the section.
+fn ggv_ovl_read_sections(ovl: &OvlDocument, metadata: &mut Metadata) {
+    for comment in ovl.preamble() {
+        metadata.add_attribute(".;", comment);
+    }
+    let mut symbols = 0;
+    for section in ovl.sections() {
+        let name = section.name();
+        if name.starts_with("Symbol ") {
+            symbols += 1;
+            continue;
+        }
+        metadata.add_attribute(&format!("{}.", name), &symbols.to_string());
+        let passthrough = match name {
+            "Overlay" => ggv_ovl_passthrough(section, |k| k == "Symbols"),
+            "MapLage" => {
+                ggv_ovl_read_map(section, metadata);
+                ggv_ovl_passthrough(section, |k| {
+                    matches!(k, "MapName" | "CenterLat" | "CenterLong" | "ZoomFc")
+                })
+            }
+            _ => ggv_ovl_passthrough(section, |_| false),
+        };
+        for (key, value) in passthrough {
+            metadata.add_attribute(&format!("{}.{}", name, key), &value);
+        }
+    }
+}
+
+/// Map view from the [MapLage] section
+fn ggv_ovl_read_map(map: &OvlSection, metadata: &mut Metadata) {
+    if let Some(name) = map.get("MapName") {
+        metadata.set_map_name(name);
+    }
+    let lat = map.get("CenterLat").and_then(|v| v.parse::<f64>().ok());
+    let lon = map.get("CenterLong").and_then(|v| v.parse::<f64>().ok());
+    if let (Some(lat), Some(lon)) = (lat, lon) {
+        metadata.set_center(Waypoint::new().with_lat(lat).with_lon(lon));
+    }
+    if let Some(zoom) = map.get("ZoomFc").and_then(|v| v.parse::<f64>().ok()) {
+        metadata.set_zoom(zoom);
+    }
+    if get_debug() >= 2 {
+        eprintln!(
+            "ovl: map \"{}\" center {:?} zoom {:?}",
+            metadata.map_name(),
+            metadata.center().map(|c| (c.latitude(), c.longitude())),
+            metadata.zoom()
+        );
+    }
+}
+
+/// Values end at the line break
+fn ggv_ovl_text(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+fn ggv_ovl_color(style: &Style) -> u16 {
+    style
+        .color()
+        .map_or(GGV_OVL_DEFAULT_COLOR, ggv_ovl_palette_index)
+}
+
+/// Names the reader makes up for symbols without Text= are not written
+/// back to overlays they were read from
+fn ggv_ovl_write_name(result: &mut Vec<String>, name: &str, read: bool) {
+    let generated = read
+        && ["Track ", "Route ", "Symbol "].iter().any(|prefix| {
+            name.strip_prefix(prefix)
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        });
+    if !name.is_empty() && !generated {
+        result.push(format!("Text={}", ggv_ovl_text(name)));
+    }
+}
+
+/// Write a line (Typ=3) or, for areas, a polygon (Typ=4)
+fn ggv_ovl_write_list(
+    result: &mut Vec<String>,
+    list: &WaypointList,
+    points: &[Waypoint],
+    width: u16,
+    group: u16,
+) -> SymbolType {
+    let typ = if list.area() {
+        SymbolType::Polygon
+    } else {
+        SymbolType::Line
+    };
+    let read = list.index().is_some();
+    result.push(format!("Typ={}", typ as u8));
+    result.push(format!("Group={}", list.group().unwrap_or(group)));
+    // Geogrid writes the line style of polygons before the colour
+    if list.area() {
+        result.push("Art=1".into());
+    }
+    if !read || list.style().color().is_some() {
+        result.push(format!("Col={}", ggv_ovl_color(list.style())));
+    }
+    if list.area() {
+        result.push("Area=1".into());
+    }
+    result.push("Zoom=1".into());
+    if !read || list.style().width().is_some() {
+        result.push(format!(
+            "Size={}",
+            100 + list.style().width().unwrap_or(width)
+        ));
+    }
+    if !list.area() {
+        result.push("Art=1".into());
+    }
+    ggv_ovl_write_name(result, &list.name(), read);
+    result.push(format!("Punkte={}", points.len()));
+    for (i, waypoint) in points.iter().enumerate() {
+        result.push(format!("XKoord{}={:.8}", i, &waypoint.longitude()));
+        result.push(format!("YKoord{}={:.8}", i, &waypoint.latitude()));
+    }
+    typ
+}
+
+/// Write a rectangle, circle or triangle (Typ=5/6/7)
+fn ggv_ovl_write_shape(result: &mut Vec<String>, waypoint: &Waypoint, shape: &Shape) -> SymbolType {
+    let typ = match shape.kind() {
+        ShapeKind::Rectangle => SymbolType::Rectangle,
+        ShapeKind::Circle => SymbolType::Circle,
+        ShapeKind::Triangle => SymbolType::Triangle,
+    };
+    let read = waypoint.index().is_some();
+    result.push(format!("Typ={}", typ as u8));
+    if !read || waypoint.group().is_some() {
+        result.push(format!("Group={}", waypoint.group().unwrap_or(1)));
+    }
+    result.push(format!("Width={}", shape.width()));
+    result.push(format!("Height={}", shape.height()));
+    result.push(format!("Dir={}", 100 + shape.angle()));
+    result.push("Art=1".into());
+    if !read || waypoint.style().color().is_some() {
+        result.push(format!("Col={}", ggv_ovl_color(waypoint.style())));
+    }
+    result.push("Zoom=1".into());
+    result.push(format!(
+        "Size={}",
+        100 + waypoint.style().width().unwrap_or(GGV_OVL_SHAPE_WIDTH)
+    ));
+    result.push("Area=1".into());
+    result.push(format!("XKoord={:.8}", &waypoint.longitude()));
+    result.push(format!("YKoord={:.8}", &waypoint.latitude()));
+    ggv_ovl_write_name(result, &waypoint.name(), read);
+    typ
+}
+
+/// Write a bitmap (Typ=1). The image itself is not part of ASCII
+/// overlays.
+fn ggv_ovl_write_bitmap(result: &mut Vec<String>, bitmap: &Bitmap) -> SymbolType {
+    result.push(format!("Typ={}", SymbolType::Bitmap as u8));
+    result.push("Group=1".into());
+    if bitmap.width() > 0 && bitmap.height() > 0 {
+        result.push(format!("Width={}", bitmap.width()));
+        result.push(format!("Height={}", bitmap.height()));
+    }
+    result.push(format!("Dir={}", 100 + bitmap.angle()));
+    if !bitmap.name().is_empty() {
+        result.push(format!("Text={}", ggv_ovl_text(bitmap.name())));
+    }
+    result.push(format!("XKoord={:.8}", bitmap.position().longitude()));
+    result.push(format!("YKoord={:.8}", bitmap.position().latitude()));
+    SymbolType::Bitmap
+}
+
+/// Write a text (Typ=2) with the waypoint name. Waypoints without a
+/// label get a default one.
+fn ggv_ovl_write_text(result: &mut Vec<String>, waypoint: &Waypoint) -> SymbolType {
+    let label =
+        waypoint
+            .label()
+            .cloned()
+            .unwrap_or(Label::new(LabelFont::Arial, GGV_OVL_LABEL_SIZE, 0));
+    let read = waypoint.index().is_some();
+    result.push(format!("Typ={}", SymbolType::Text as u8));
+    if !read || waypoint.group().is_some() {
+        result.push(format!("Group={}", waypoint.group().unwrap_or(1)));
+    }
+    if !read || waypoint.style().color().is_some() {
+        result.push(format!("Col={}", ggv_ovl_color(waypoint.style())));
+    }
+    let area = if label.background().is_some() { 2 } else { 1 };
+    result.push(format!("Area={}", area));
+    result.push("Zoom=1".into());
+    result.push(format!("Size={}", 100 + label.size()));
+    result.push(format!("Font={}", ggv_ovl_font_number(label.font())));
+    result.push(format!("Dir={}", 100 + label.angle()));
+    result.push(format!("XKoord={:.8}", &waypoint.longitude()));
+    result.push(format!("YKoord={:.8}", &waypoint.latitude()));
+    ggv_ovl_write_name(result, &waypoint.name(), read);
+    SymbolType::Text
+}
+
+/// Apply passthrough entries to the section starting at line start.
+/// The first entry of a key written by ggvtogpx replaces that line,
+/// all others are appended. Returns the keys replaced.
+fn ggv_ovl_write_attributes(
+    result: &mut Vec<String>,
+    start: usize,
+    attributes: &Attributes,
+) -> Vec<String> {
+    let end = result.len();
+    let mut replaced = Vec::new();
+    for (key, value) in attributes {
+        if key == ";" {
+            result.push(format!(";{}", ggv_ovl_text(value)));
+            continue;
+        }
+        let prefix = format!("{}=", key);
+        let line = format!("{}{}", prefix, ggv_ovl_text(value));
+        match result[start..end]
+            .iter()
+            .position(|l| l.starts_with(&prefix))
+        {
+            Some(i) if !replaced.contains(key) => {
+                result[start + i] = line;
+                replaced.push(key.clone());
+            }
+            _ => result.push(line),
+        }
+    }
+    replaced
+}
+
+/// Symbol section with its passthrough entries. Symbols read from an
+/// ASCII overlay only keep lines for keys the reader doesn't model if
+/// they came with the passthrough entries, so no defaults are added.
+fn ggv_ovl_write_symbol(
+    mut lines: Vec<String>,
+    typ: SymbolType,
+    read: bool,
+    attributes: &Attributes,
+) -> Vec<String> {
+    let generated = lines.len();
+    let replaced = ggv_ovl_write_attributes(&mut lines, 0, attributes);
+    if read {
+        let mut i = 0;
+        lines.retain(|line| {
+            i += 1;
+            let key = line.split_once('=').map_or("", |(k, _)| k);
+            i > generated || ggv_ovl_known_key(&typ, key) || replaced.iter().any(|r| r == key)
+        });
+    }
+    lines
+}
+
 //////////////////////////////////////////////////////////////////////
 //            entry points called by ggvtogpx main process
 //////////////////////////////////////////////////////////////////////
@@ -276,13 +867,9 @@ pub struct GgvOvlFormat {
 
 impl Format for GgvOvlFormat {
     fn probe(&self, buf: &[u8]) -> bool {
-        if tag::<_, _, Error<_>>("[Symbol").parse(buf).is_ok()
-            || tag::<_, _, Error<_>>("[Overlay").parse(buf).is_ok()
-        {
-            return true;
-        } else {
-            return false;
-        }
+        // Sections may come in any order and hand edited files may
+        // start with comments, so the whole file is parsed
+        ggv_ovl_parse(buf).is_ok_and(|(_, ovl)| !ggv_ovl_symbols(&ovl).is_empty())
     }
     fn read(&self, buf: &[u8]) -> Result<Geodata> {
         let ovl = match ggv_ovl_parse(buf) {
@@ -314,119 +901,170 @@ impl Format for GgvOvlFormat {
         };
         Ok(geodata)
     }
-    fn write(&self, geodata: &Geodata) -> Result<String> {
-        let mut result: Vec<String> = Vec::new();
-        let mut symbol = 0;
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        // Symbols are collected first and written in the order they
+        // had in the overlay they were read from, other objects follow
+        // in the order tracks, routes, waypoints and bitmaps
+        let mut symbols: Vec<(usize, String, Vec<String>)> = Vec::new();
+        // Every track segment becomes a symbol of its own
         for track in geodata.tracks().iter() {
-            symbol += 1;
-            result.push(format!("[Symbol {}]", symbol));
-            result.push(format!("Typ={}", SymbolType::Line as u8));
-            result.push("Group=1".into());
-            result.push("Col=3".into());
-            result.push("Zoom=1".into());
-            result.push("Size=105".into());
-            result.push("Art=1".into());
-            result.push(format!("Punkte={}", track.len()));
-            for (i, waypoint) in track.waypoints().iter().enumerate() {
-                result.push(format!("XKoord{}={:.8}", i, &waypoint.longitude()));
-                result.push(format!("YKoord{}={:.8}", i, &waypoint.latitude()));
-            }
-            if self.debug >= 1 {
-                eprintln!(
-                    "ovl: writing symbol {:2}: track, len: {:3}, name: \"{}\"",
-                    symbol,
-                    track.len(),
-                    track.name()
-                );
+            for segment in track.segments() {
+                let mut lines = Vec::new();
+                let typ = ggv_ovl_write_list(&mut lines, track, segment, GGV_OVL_TRACK_WIDTH, 1);
+                symbols.push((
+                    track.index().unwrap_or(usize::MAX),
+                    format!(
+                        "track, len: {:3}, name: \"{}\"",
+                        segment.len(),
+                        track.name()
+                    ),
+                    ggv_ovl_write_symbol(lines, typ, track.index().is_some(), track.attributes()),
+                ));
             }
         }
+        // The reader takes lines with a group number above one as
+        // routes, so routes without group are put into group 2
         for route in geodata.routes().iter() {
-            symbol += 1;
-            result.push(format!("[Symbol {}]", symbol));
-            result.push(format!("Typ={}", SymbolType::Line as u8));
-            result.push("Group=1".into());
-            result.push("Col=3".into());
-            result.push("Zoom=1".into());
-            result.push("Size=100".into());
-            result.push("Art=1".into());
-            result.push(format!("Punkte={}", route.len()));
-            for (i, waypoint) in route.waypoints().iter().enumerate() {
-                result.push(format!("XKoord{}={:.8}", i, &waypoint.longitude()));
-                result.push(format!("YKoord{}={:.8}", i, &waypoint.latitude()));
-            }
-            if self.debug >= 1 {
-                eprintln!(
-                    "ovl: writing symbol {:2}: route, len: {:3}, name: \"{}\")",
-                    symbol,
-                    route.len(),
-                    route.name()
-                );
-            }
+            let mut lines = Vec::new();
+            let typ = ggv_ovl_write_list(&mut lines, route, route.waypoints(), 0, 2);
+            symbols.push((
+                route.index().unwrap_or(usize::MAX),
+                format!("route, len: {:3}, name: \"{}\"", route.len(), route.name()),
+                ggv_ovl_write_symbol(lines, typ, route.index().is_some(), route.attributes()),
+            ));
         }
         for waypoint in geodata.waypoints().waypoints().iter() {
-            // For waypoints with text, generate a circle and a text
-            // object to have some visual indication where the point
-            // is located. For waypoints without text, only generate
-            // a circle.
-            symbol += 1;
-            result.push(format!("[Symbol {}]", symbol));
-            result.push(format!("Typ={}", SymbolType::Circle as u8));
-            result.push("Group=1".into());
-            result.push("Width=20".into());
-            result.push("Height=20".into());
-            result.push("Dir=100".into());
-            result.push("Col=3".into());
-            result.push("Zoom=1".into());
-            result.push("Size=102".into());
-            result.push("Area=1".into());
-            result.push(format!("XKoord={:.8}", &waypoint.longitude()));
-            result.push(format!("YKoord={:.8}", &waypoint.latitude()));
-            if self.debug >= 1 {
-                eprintln!(
-                    "ovl: writing symbol {:2}: waypt, len:   1, name: \"{}\", type: {}",
-                    symbol,
+            // Labels are written as text only. Waypoints without a
+            // shape of their own get a circle to have some visual
+            // indication where the point is located, plus a text object
+            // when they have a name. Passthrough entries go to the last
+            // symbol written.
+            let index = waypoint.index().unwrap_or(usize::MAX);
+            let read = waypoint.index().is_some();
+            let description = |typ: &dyn fmt::Display| {
+                format!(
+                    "waypt, len:   1, name: \"{}\", type: {}",
                     waypoint.name(),
-                    SymbolType::Circle
-                );
+                    typ
+                )
+            };
+            let mut lines = Vec::new();
+            if waypoint.label().is_some() {
+                let typ = ggv_ovl_write_text(&mut lines, waypoint);
+                symbols.push((
+                    index,
+                    description(&typ),
+                    ggv_ovl_write_symbol(lines, typ, read, waypoint.attributes()),
+                ));
+                continue;
             }
-            if !waypoint.name().is_empty() {
-                symbol += 1;
-                result.push(format!("[Symbol {}]", symbol));
-                result.push(format!("Typ={}", SymbolType::Text as u8));
-                result.push("Group=1".into());
-                result.push("Col=3".into());
-                result.push("Area=1".into());
-                result.push("Zoom=1".into());
-                result.push("Size=120".into());
-                result.push("Font=1".into());
-                result.push("Dir=100".into());
-                result.push(format!("Text={}", waypoint.name()));
-                result.push(format!("XKoord={:.8}", &waypoint.longitude()));
-                result.push(format!("YKoord={:.8}", &waypoint.latitude()));
-                if self.debug >= 1 {
-                    eprintln!(
-                        "ovl: writing symbol {:2}: waypt, len:   1, name: \"{}\", type: {}",
-                        symbol,
-                        waypoint.name(),
-                        SymbolType::Text
-                    );
+            let shape = waypoint.shape().cloned().unwrap_or(Shape::new(
+                ShapeKind::Circle,
+                GGV_OVL_SHAPE_SIZE,
+                GGV_OVL_SHAPE_SIZE,
+                0,
+            ));
+            let typ = ggv_ovl_write_shape(&mut lines, waypoint, &shape);
+            if waypoint.shape().is_none() && !waypoint.name().is_empty() {
+                symbols.push((index, description(&typ), lines));
+                lines = Vec::new();
+                let typ = ggv_ovl_write_text(&mut lines, waypoint);
+                symbols.push((
+                    index,
+                    description(&typ),
+                    ggv_ovl_write_symbol(lines, typ, read, waypoint.attributes()),
+                ));
+            } else {
+                symbols.push((
+                    index,
+                    description(&typ),
+                    ggv_ovl_write_symbol(lines, typ, read, waypoint.attributes()),
+                ));
+            }
+        }
+        for bitmap in geodata.bitmaps().iter() {
+            let mut lines = Vec::new();
+            let typ = ggv_ovl_write_bitmap(&mut lines, bitmap);
+            symbols.push((
+                bitmap.index().unwrap_or(usize::MAX),
+                format!("bitmap, name: \"{}\"", bitmap.name()),
+                ggv_ovl_write_symbol(lines, typ, bitmap.index().is_some(), bitmap.attributes()),
+            ));
+        }
+        symbols.sort_by_key(|(index, _, _)| *index);
+        let symbol = symbols.len();
+        // Passthrough entries of other sections are keyed by section
+        // name and key, the preamble has an empty section name. The
+        // position of a section among the symbols has an empty key.
+        let metadata = geodata.metadata();
+        let mut sections: Vec<(&str, Option<usize>, Attributes)> = Vec::new();
+        for name in ["Overlay", "MapLage"] {
+            sections.push((name, None, Vec::new()));
+        }
+        for (key, value) in metadata.attributes() {
+            let Some((name, key)) = key.split_once('.') else {
+                continue;
+            };
+            let index = match sections.iter().position(|(n, _, _)| *n == name) {
+                Some(index) => index,
+                None => {
+                    sections.push((name, None, Vec::new()));
+                    sections.len() - 1
                 }
+            };
+            if key.is_empty() {
+                // Sections are written in the order they were read
+                let section = sections.remove(index);
+                let read = sections.iter().filter(|(_, p, _)| p.is_some()).count();
+                sections.insert(read, (section.0, value.parse().ok(), section.2));
+            } else {
+                sections[index].2.push((key.to_string(), value.clone()));
             }
         }
-        result.push("[Overlay]".into());
-        result.push(format!("Symbols={}", symbol));
-        result.push("[MapLage]".into());
-        result.push("MapName=Karte".into());
-        result.push("DimmFc=100".into());
-        result.push("ZoomFc=100".into());
-        result.push("CenterLat=".into());
-        result.push("CenterLong=".into());
-        result.push("RefOn=0".into());
-        Ok(result.join("\r\n") + "\r\n")
+        let mut result: Vec<String> = Vec::new();
+        let mut blocks: Vec<(usize, Vec<String>)> = Vec::new();
+        for (name, position, attributes) in sections.iter() {
+            let mut lines = Vec::new();
+            match *name {
+                "" => {
+                    ggv_ovl_write_attributes(&mut result, 0, attributes);
+                    continue;
+                }
+                "Overlay" => {
+                    lines.push("[Overlay]".into());
+                    lines.push(format!("Symbols={}", symbol));
+                }
+                "MapLage" => self.write_map(&mut lines, geodata),
+                _ => lines.push(format!("[{}]", name)),
+            }
+            ggv_ovl_write_attributes(&mut lines, 0, attributes);
+            blocks.push((position.unwrap_or(symbol).min(symbol), lines));
+        }
+        // Sections without a position from the input follow the symbols
+        blocks.sort_by_key(|(position, _)| *position);
+        let mut blocks = blocks.into_iter().peekable();
+        for (i, (_, description, lines)) in symbols.iter().enumerate() {
+            while let Some((_, block)) = blocks.next_if(|(position, _)| *position <= i) {
+                result.extend(block);
+            }
+            if self.debug >= 1 {
+                eprintln!("ovl: writing symbol {:2}: {}", i + 1, description);
+            }
+            result.push(format!("[Symbol {}]", i + 1));
+            result.extend(lines.iter().cloned());
+        }
+        for (_, block) in blocks {
+            result.extend(block);
+        }
+        // ASCII overlays are latin1 encoded like all Geogrid files
+        Ok(encode_latin1_lossy(&(result.join("\r\n") + "\r\n")).into_owned())
     }
     fn name<'a>(&self) -> &'a str {
         return "ggv_ovl";
     }
+    fn extension<'a>(&self) -> &'a str {
+        "ovl"
+    }
     fn can_read(&self) -> bool {
         true
     }
@@ -443,4 +1081,35 @@ impl GgvOvlFormat {
     pub fn new() -> Self {
         Self { debug: 0 }
     }
+
+    /// Map view, centred on the data without a view from the input
+    fn write_map(&self, result: &mut Vec<String>, geodata: &Geodata) {
+        let metadata = geodata.metadata();
+        let center = metadata.center().cloned().or_else(|| {
+            geodata.get_bounds().map(|(min, max)| {
+                Waypoint::new()
+                    .with_lat((min.latitude() + max.latitude()) / 2.0)
+                    .with_lon((min.longitude() + max.longitude()) / 2.0)
+            })
+        });
+        result.push("[MapLage]".into());
+        if metadata.map_name().is_empty() {
+            result.push("MapName=Karte".into());
+        } else {
+            result.push(format!("MapName={}", metadata.map_name()));
+        }
+        result.push("DimmFc=100".into());
+        result.push(format!("ZoomFc={:.0}", metadata.zoom().unwrap_or(100.0)));
+        match center {
+            Some(center) => {
+                result.push(format!("CenterLat={:.8}", center.latitude()));
+                result.push(format!("CenterLong={:.8}", center.longitude()));
+            }
+            None => {
+                result.push("CenterLat=".into());
+                result.push("CenterLong=".into());
+            }
+        }
+        result.push("RefOn=0".into());
+    }
 }
@@ -22,7 +22,10 @@
 #[cfg(test)]
 mod tests {
 
-    use super::*;
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::ggv_ovl::*;
+    use encoding_rs::mem::decode_latin1;
 
     #[test]
     fn test_parse() {
@@ -36,25 +39,45 @@ mod tests {
     #[test]
     fn test_parse_key_value() {
         let tests = [
-            ("foo=bar", "foo", "bar", ""),
-            ("foo = bar", "foo", "bar", ""),
-            ("foo = bar; \n  ", "foo", "bar", "\n  "),
+            ("foo=bar", "foo", "bar", None, ""),
+            ("foo = bar", "foo", "bar", None, ""),
+            ("foo = bar; \n  ", "foo", "bar", Some(""), "\n  "),
+            ("foo=bar;baz\n", "foo", "bar", Some("baz"), "\n"),
         ];
-        for (t, k, v, r) in tests {
-            let (rem, (key, val)) = ggv_ovl_parse_key_value(t.as_bytes()).unwrap();
+        for (t, k, v, c, r) in tests {
+            let (rem, entry) = ggv_ovl_parse_entry(t.as_bytes()).unwrap();
             println!(
-                "test = {:?}, key = {:?}, val = {:?}, rem = {:?}",
+                "test = {:?}, entry = {:?}, rem = {:?}",
                 t,
-                key,
-                val,
+                entry,
                 decode_latin1(rem)
             );
-            assert_eq!(key, k);
-            assert_eq!(val, v);
+            assert_eq!(
+                entry,
+                OvlEntry::Value {
+                    key: k.to_string(),
+                    value: v.to_string(),
+                    comment: c.map(String::from),
+                }
+            );
             assert_eq!(rem, r.as_bytes());
         }
     }
 
+    #[test]
+    fn test_parse_document() {
+        let test = "; exported\r\n[Symbol 1]\r\nTyp=2\r\n; note\r\nFoo=1\r\nFoo=2\r\n\
+                    [Extra]\r\nKey=Value\r\n";
+        let (_, document) = ggv_ovl_parse(test.as_bytes()).unwrap();
+        assert_eq!(document.preamble(), &vec!["exported".to_string()]);
+        let names: Vec<&str> = document.sections().iter().map(|s| s.name()).collect();
+        assert_eq!(names, vec!["Symbol 1", "Extra"]);
+        let symbol = document.section("Symbol 1").unwrap();
+        assert_eq!(symbol.entries().len(), 4);
+        assert_eq!(symbol.entries()[1], OvlEntry::Comment("note".to_string()));
+        assert_eq!(symbol.get("Foo"), Some(&"1".to_string()));
+    }
+
     #[test]
     fn test_parse_section() {
         let tests = [("[Foo]", "Foo", ""), ("[Foo]  ", "Foo", "  ")];
@@ -70,4 +93,268 @@ mod tests {
             assert_eq!(rem, r.as_bytes());
         }
     }
+
+    #[test]
+    fn test_map_view() {
+        let ovl = "[Symbol 1]\r\nTyp=2\r\nGroup=1\r\nCol=1\r\nText=Hut\r\n\
+                   XKoord=10.5\r\nYKoord=47.25\r\n\
+                   [Overlay]\r\nSymbols=1\r\n\
+                   [MapLage]\r\nMapName=Top50\r\nDimmFc=100\r\nZoomFc=141\r\n\
+                   CenterLat=47.5\r\nCenterLong=10.75\r\nRefOn=0\r\n";
+        let format = GgvOvlFormat::new();
+        let geodata = format.read(ovl.as_bytes()).unwrap();
+        let metadata = geodata.metadata();
+        assert_eq!(metadata.map_name(), "Top50");
+        assert_eq!(metadata.zoom(), Some(141.0));
+        assert_eq!(metadata.center().unwrap().latitude(), 47.5);
+        assert_eq!(metadata.center().unwrap().longitude(), 10.75);
+
+        let written = String::from_utf8(format.write(&geodata).unwrap()).unwrap();
+        assert!(written.contains("MapName=Top50\r\n"));
+        assert!(written.contains("ZoomFc=141\r\n"));
+        assert!(written.contains("CenterLat=47.50000000\r\nCenterLong=10.75000000\r\n"));
+    }
+
+    #[test]
+    fn test_palette_index() {
+        assert_eq!(ggv_ovl_palette_index(Color::new(250, 10, 10)), 1);
+        assert_eq!(ggv_ovl_palette_index(Color::new(0, 0, 100)), 5);
+        assert_eq!(ggv_ovl_palette_index(Color::new(0, 0, 200)), 3);
+        assert_eq!(ggv_ovl_palette_index(Color::new(240, 240, 240)), 6);
+    }
+
+    #[test]
+    fn test_write_styles() {
+        let mut geodata = Geodata::new();
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(47.0)
+                .with_lon(10.0)
+                .with_style(Style::new().with_color(Color::new(0, 200, 0)))
+                .with_shape(Shape::new(ShapeKind::Rectangle, 40, 30, 45)),
+        );
+        let mut area = WaypointList::new();
+        area.add_waypoint(Waypoint::new().with_lat(47.0).with_lon(10.0));
+        area.add_waypoint(Waypoint::new().with_lat(47.0).with_lon(10.1));
+        area.add_waypoint(Waypoint::new().with_lat(47.1).with_lon(10.1));
+        area.set_name("Wiese");
+        area.set_area(true);
+        area.set_group(4);
+        area.set_style(
+            Style::new()
+                .with_color(Color::new(255, 0, 255))
+                .with_width(3),
+        );
+        geodata.add_track(area);
+
+        let format = GgvOvlFormat::new();
+        let written = format.write(&geodata).unwrap();
+        let text = String::from_utf8(written.clone()).unwrap();
+        assert!(
+            text.contains("Typ=4\r\nGroup=4\r\nArt=1\r\nCol=8\r\nArea=1\r\nZoom=1\r\nSize=103\r\n")
+        );
+        assert!(text.contains("Text=Wiese\r\n"));
+        assert!(text.contains(
+            "Typ=5\r\nGroup=1\r\nWidth=40\r\nHeight=30\r\nDir=145\r\nArt=1\r\nCol=2\r\n"
+        ));
+
+        let reread = format.read(&written).unwrap();
+        let route = &reread.routes()[0];
+        assert_eq!(route.name(), "Wiese");
+        assert!(route.area());
+        assert_eq!(route.style().width(), Some(3));
+        let waypoint = &reread.waypoints().waypoints()[0];
+        assert_eq!(
+            waypoint.shape(),
+            Some(&Shape::new(ShapeKind::Rectangle, 40, 30, 45))
+        );
+    }
+
+    #[test]
+    fn test_write_passthrough() {
+        let test = "; exported by hand\r\n\
+                    [Symbol 1]\r\nTyp=3\r\nGroup=1\r\nCol=1\r\nZoom=2\r\nSize=104\r\n\
+                    Art=7\r\nLabel=foo\r\n; second point\r\nPunkte=2\r\n\
+                    XKoord0=10.0\r\nYKoord0=47.0\r\nXKoord1=10.1\r\nYKoord1=47.1\r\n\
+                    [Symbol 2]\r\nTyp=2\r\nGroup=1\r\nCol=3\r\nFont=3\r\nText=Gipfel\r\n\
+                    XKoord=10.0\r\nYKoord=47.0\r\n\
+                    [Overlay]\r\nSymbols=2\r\nExtra=1\r\n\
+                    [MapLage]\r\nMapName=Top50\r\nDimmFc=80\r\nZoomFc=100\r\n\
+                    CenterLat=47.0\r\nCenterLong=10.0\r\nRefOn=0\r\n\
+                    [Info]\r\nAuthor=me\r\n";
+        let format = GgvOvlFormat::new();
+        let geodata = format.read(test.as_bytes()).unwrap();
+        let written = format.write(&geodata).unwrap();
+        let text = String::from_utf8(written.clone()).unwrap();
+        assert!(text.starts_with(";exported by hand\r\n[Symbol 1]\r\n"));
+        assert!(text.contains("Zoom=2\r\nSize=104\r\nArt=7\r\n"));
+        assert!(text.contains("Label=foo\r\n;second point\r\n"));
+        assert!(text.contains("Font=3\r\n"));
+        assert!(text.contains("Extra=1\r\n[MapLage]\r\n"));
+        assert!(text.contains("DimmFc=80\r\n"));
+        assert!(text.ends_with("[Info]\r\nAuthor=me\r\n"));
+
+        let reread = format.read(&written).unwrap();
+        assert_eq!(
+            reread.tracks()[0].attributes(),
+            geodata.tracks()[0].attributes()
+        );
+        assert_eq!(
+            reread.metadata().attributes(),
+            geodata.metadata().attributes()
+        );
+    }
+
+    #[test]
+    fn test_symbol_discovery() {
+        // Gap in the numbers, wrong count, unordered sections
+        let test = "[Symbol 3]\r\nTyp=2\r\nText=drei\r\nXKoord=10.0\r\nYKoord=47.0\r\n\
+                    [Symbol 1]\r\nTyp=2\r\nText=eins\r\nXKoord=10.0\r\nYKoord=47.0\r\n\
+                    [Overlay]\r\nSymbols=5\r\n";
+        let format = GgvOvlFormat::new();
+        let geodata = format.read(test.as_bytes()).unwrap();
+        let names: Vec<String> = geodata
+            .waypoints()
+            .waypoints()
+            .iter()
+            .map(|w| w.name())
+            .collect();
+        assert_eq!(names, vec!["eins", "drei"]);
+
+        // No [Overlay] section at all
+        let test = "[Symbol 1]\r\nTyp=3\r\nGroup=1\r\nPunkte=2\r\n\
+                    XKoord0=10.0\r\nYKoord0=47.0\r\nXKoord1=10.1\r\nYKoord1=47.1\r\n";
+        let geodata = format.read(test.as_bytes()).unwrap();
+        assert_eq!(geodata.tracks().len(), 1);
+        assert_eq!(geodata.tracks()[0].len(), 2);
+    }
+
+    #[test]
+    fn test_bitmap() {
+        let test = "[Symbol 1]\r\nTyp=1\r\nGroup=1\r\nDir=130\r\nWidth=32\r\nHeight=16\r\n\
+                    Datei=bild.bmp\r\nXKoord=10.5\r\nYKoord=47.5\r\n\
+                    [Overlay]\r\nSymbols=1\r\n";
+        let format = GgvOvlFormat::new();
+        let geodata = format.read(test.as_bytes()).unwrap();
+        let bitmap = &geodata.bitmaps()[0];
+        assert_eq!(bitmap.position().latitude(), 47.5);
+        assert_eq!((bitmap.width(), bitmap.height()), (32, 16));
+        assert_eq!(bitmap.angle(), 30);
+        assert!(bitmap.data().is_empty());
+
+        let written = format.write(&geodata).unwrap();
+        let text = String::from_utf8(written.clone()).unwrap();
+        assert!(text.contains("Typ=1\r\nGroup=1\r\nWidth=32\r\nHeight=16\r\nDir=130\r\n"));
+        assert!(text.contains("Datei=bild.bmp\r\n"));
+        let reread = format.read(&written).unwrap();
+        assert_eq!(reread.bitmaps()[0].attributes(), bitmap.attributes());
+    }
+
+    #[test]
+    fn test_label() {
+        let test = "[Symbol 1]\r\nTyp=2\r\nGroup=1\r\nCol=3\r\nArea=2\r\nSize=130\r\n\
+                    Font=4\r\nDir=190\r\nText=Gipfel\r\nXKoord=10.0\r\nYKoord=47.0\r\n\
+                    [Overlay]\r\nSymbols=1\r\n";
+        let format = GgvOvlFormat::new();
+        let geodata = format.read(test.as_bytes()).unwrap();
+        let waypoint = &geodata.waypoints().waypoints()[0];
+        let label = waypoint.label().unwrap();
+        assert_eq!(label.font(), LabelFont::Times);
+        assert_eq!((label.size(), label.angle()), (30, 90));
+        assert_eq!(label.background(), Some(Color::new(255, 255, 255)));
+        assert!(waypoint.attributes().is_empty());
+
+        // Written as a single text symbol without a circle
+        let written = format.write(&geodata).unwrap();
+        let text = String::from_utf8(written.clone()).unwrap();
+        assert!(text.contains("Area=2\r\nSize=130\r\nFont=4\r\nDir=190\r\n"));
+        assert!(!text.contains("[Symbol 2]"));
+        let reread = format.read(&written).unwrap();
+        assert_eq!(reread.waypoints().waypoints()[0].label(), Some(label));
+
+        // Defaults of a bare text symbol
+        let label = ggv_ovl_label(99, 0, 1, 100);
+        assert_eq!(label.font(), LabelFont::Arial);
+        assert_eq!((label.size(), label.angle()), (20, 0));
+        assert_eq!(label.background(), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // A circle before a line, repeated passthrough keys and
+        // symbols without Text=
+        let test = "[Symbol 1]\r\nTyp=6\r\nGroup=1\r\nWidth=20\r\nHeight=20\r\nDir=100\r\n\
+                    Col=1\r\nFoo=1\r\nFoo=2\r\nXKoord=10.00000000\r\nYKoord=47.00000000\r\n\
+                    [Symbol 2]\r\nTyp=3\r\nGroup=1\r\nCol=3\r\nPunkte=2\r\n\
+                    XKoord0=10.00000000\r\nYKoord0=47.00000000\r\n\
+                    XKoord1=10.10000000\r\nYKoord1=47.10000000\r\n\
+                    [Symbol 3]\r\nTyp=1\r\nGroup=4\r\nCol=2\r\nDir=100\r\nDatei=a.bmp\r\n\
+                    XKoord=10.50000000\r\nYKoord=47.50000000\r\n\
+                    [Overlay]\r\nSymbols=3\r\n";
+        let format = GgvOvlFormat::new();
+        let geodata = format.read(test.as_bytes()).unwrap();
+        let written = format.write(&geodata).unwrap();
+        let text = String::from_utf8(written.clone()).unwrap();
+        let symbols = |text: &str| {
+            text.split('[')
+                .filter(|s| s.starts_with("Symbol "))
+                .map(|s| {
+                    let mut lines: Vec<String> = s.lines().skip(1).map(String::from).collect();
+                    lines.sort();
+                    lines
+                })
+                .collect::<Vec<Vec<String>>>()
+        };
+        assert_eq!(symbols(&text), symbols(test));
+        assert!(text.contains("Foo=1\r\nFoo=2\r\n"));
+        assert!(!text.contains("Text="));
+
+        // Writing the result again gives the same file
+        let reread = format.read(&written).unwrap();
+        assert_eq!(format.write(&reread).unwrap(), written);
+    }
+
+    #[test]
+    fn test_round_trip_sections() {
+        // [Overlay] and [MapLage] at the top of the file
+        let sample = include_bytes!("../testdata/ggv_ovl-sample-2.ovl");
+        let format = GgvOvlFormat::new();
+        let geodata = format.read(sample).unwrap();
+        assert_eq!(format.write(&geodata).unwrap(), sample);
+
+        // Sections between symbols stay in place
+        let test = "[Symbol 1]\r\nTyp=2\r\nGroup=1\r\nCol=1\r\nZoom=1\r\nSize=101\r\n\
+                    Art=1\r\nXKoord=10.00000000\r\nYKoord=47.00000000\r\n\
+                    [Extra]\r\nKey=1\r\n\
+                    [Symbol 2]\r\nTyp=2\r\nGroup=1\r\nCol=1\r\nZoom=1\r\nSize=101\r\n\
+                    Art=1\r\nXKoord=10.10000000\r\nYKoord=47.10000000\r\n\
+                    [Overlay]\r\nSymbols=2\r\n";
+        let geodata = format.read(test.as_bytes()).unwrap();
+        let text = String::from_utf8(format.write(&geodata).unwrap()).unwrap();
+        let sections: Vec<&str> = text.lines().filter(|l| l.starts_with('[')).collect();
+        assert_eq!(
+            sections,
+            [
+                "[Symbol 1]",
+                "[Extra]",
+                "[Symbol 2]",
+                "[Overlay]",
+                "[MapLage]"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_probe() {
+        let format = GgvOvlFormat::new();
+        assert!(format.probe(b"[Symbol 1]\r\nTyp=2\r\n"));
+        assert!(format.probe(
+            b"; exported by hand\r\n;\r\n\r\n[Overlay]\r\nSymbols=1\r\n[Symbol 1]\r\nTyp=2\r\n"
+        ));
+        // No [Overlay] section, symbols after the map view
+        assert!(format.probe(b"[MapLage]\r\nMapName=Top50\r\n[Symbol 1]\r\nTyp=2\r\n"));
+        assert!(!format.probe(b"[MapLage]\r\nMapName=Top50\r\n"));
+        assert!(!format.probe(b"; just a comment\r\n"));
+        assert!(!format.probe(b"<?xml version=\"1.0\"?>"));
+    }
 }
@@ -18,6 +18,7 @@
 ///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 ///
 use std::io::BufReader;
+use std::io::Cursor;
 use std::io::Read;
 
 use anyhow::{anyhow, Context, Result};
@@ -28,7 +29,14 @@ use encoding_rs::mem::encode_latin1_lossy;
 use nom::{bytes::complete::tag, error::Error, Parser};
 
 use crate::format::Format;
+use crate::geodata::Color;
 use crate::geodata::Geodata;
+use crate::geodata::Label;
+use crate::geodata::LabelFont;
+use crate::geodata::Metadata;
+use crate::geodata::Shape;
+use crate::geodata::ShapeKind;
+use crate::geodata::Style;
 use crate::geodata::Waypoint;
 use crate::geodata::WaypointList;
 
@@ -42,6 +50,9 @@ fn set_debug(debug: u8) {
     DEBUG_LEVEL.store(debug, Ordering::Relaxed);
 }
 
+/// Text height when the object has none
+const GGV_XML_LABEL_SIZE: u16 = 20;
+
 /// Parse single coordinate data
 fn ggv_xml_parse_coord(coord: &roxmltree::Node) -> Option<Waypoint> {
     let x_coord = coord.attribute("x")?;
@@ -63,9 +74,25 @@ fn ggv_xml_parse_coord(coord: &roxmltree::Node) -> Option<Waypoint> {
     }
 }
 
+/// Parse <color red=".." green=".." blue=".."/> child element
+fn ggv_xml_parse_color(node: &roxmltree::Node) -> Option<Color> {
+    let color = node.children().find(|c| c.has_tag_name("color"))?;
+    let red = color.attribute("red")?.parse::<u8>().ok()?;
+    let green = color.attribute("green")?.parse::<u8>().ok()?;
+    let blue = color.attribute("blue")?.parse::<u8>().ok()?;
+    Some(Color::new(red, green, blue))
+}
+
+/// Parse text content of a child element
+fn ggv_xml_parse_child<T: std::str::FromStr>(node: &roxmltree::Node, tag: &str) -> Option<T> {
+    let child = node.children().find(|c| c.has_tag_name(tag))?;
+    child.text()?.trim().parse::<T>().ok()
+}
+
 /// Parse attributeList that contains the actuall coordinates
 fn ggv_xml_parse_attributelist(attribute_list: &roxmltree::Node) -> Option<WaypointList> {
     let mut waypoint_list = WaypointList::new();
+    let mut style = Style::new();
     for attribute_tag in attribute_list
         .children()
         .filter(|c| c.has_tag_name("attribute"))
@@ -85,6 +112,34 @@ fn ggv_xml_parse_attributelist(attribute_list: &roxmltree::Node) -> Option<Waypo
             if get_debug() >= 2 {
                 eprintln!("xml: text: {}", waypoint_list.name());
             }
+            if let Some(color) = ggv_xml_parse_color(&attribute_tag) {
+                style = style.with_color(color);
+            }
+        } else if iid_name == "IID_IGraphicTooltip" {
+            if let Some(text) = attribute_tag
+                .children()
+                .find(|c| c.has_tag_name("text"))
+                .and_then(|t| t.text())
+            {
+                waypoint_list.set_description(text);
+                if get_debug() >= 2 {
+                    eprintln!("xml: tooltip: {}", text);
+                }
+            }
+        } else if iid_name == "IID_IGraphicLineAttributes" {
+            // Text objects carry their own color in the text attributes
+            if style.color().is_none()
+                && let Some(color) = ggv_xml_parse_color(&attribute_tag)
+            {
+                style = style.with_color(color);
+            }
+            if let Some(size) = ggv_xml_parse_child::<u16>(&attribute_tag, "size") {
+                style = style.with_width(size);
+            }
+        } else if iid_name == "IID_IGraphicCloseable" {
+            if let Some(closed) = ggv_xml_parse_child::<bool>(&attribute_tag, "closed") {
+                waypoint_list.set_area(closed);
+            }
         } else if iid_name == "IID_IGraphic" {
             let Some(coord_list) = attribute_tag
                 .children()
@@ -108,6 +163,7 @@ fn ggv_xml_parse_attributelist(attribute_list: &roxmltree::Node) -> Option<Waypo
             }
         }
     }
+    waypoint_list.set_style(style);
     if waypoint_list.waypoints().len() > 0 {
         Some(waypoint_list)
     } else {
@@ -115,6 +171,61 @@ fn ggv_xml_parse_attributelist(attribute_list: &roxmltree::Node) -> Option<Waypo
     }
 }
 
+/// Parse size and rotation of single point objects
+fn ggv_xml_read_shape(attribute_list: &roxmltree::Node, kind: ShapeKind) -> Option<Shape> {
+    let attribute = attribute_list.children().find(|c| {
+        c.has_tag_name("attribute")
+            && c.attribute("iidName") == Some("IID_IGraphicSinglePointAttributes")
+    })?;
+    let width = ggv_xml_parse_child::<u16>(&attribute, "width")?;
+    let height = ggv_xml_parse_child::<u16>(&attribute, "height").unwrap_or(width);
+    let rotation = ggv_xml_parse_child::<f64>(&attribute, "rotation").unwrap_or(0.0);
+    let angle = rotation.round().rem_euclid(360.0) as u16;
+    Some(Shape::new(kind, width, height, angle))
+}
+
+/// Parse font, height, rotation and background of text objects. The
+/// background is only drawn with a fill style other than 0.
+fn ggv_xml_read_label(attribute_list: &roxmltree::Node) -> Label {
+    let attribute = |iid: &str| {
+        attribute_list
+            .children()
+            .find(|c| c.has_tag_name("attribute") && c.attribute("iidName") == Some(iid))
+    };
+    let font = attribute("IID_IGraphicTextAttributes")
+        .and_then(|a| ggv_xml_parse_child::<String>(&a, "fontName"))
+        .map_or(LabelFont::Arial, |name| {
+            let name = name.to_lowercase();
+            if name.starts_with("courier") {
+                LabelFont::Courier
+            } else if name.starts_with("times") {
+                LabelFont::Times
+            } else if name.starts_with("comic") {
+                LabelFont::Comic
+            } else {
+                LabelFont::Arial
+            }
+        });
+    let single = attribute("IID_IGraphicSinglePointAttributes");
+    let size = single
+        .and_then(|a| ggv_xml_parse_child::<u16>(&a, "height"))
+        .filter(|h| *h > 0)
+        .unwrap_or(GGV_XML_LABEL_SIZE);
+    let rotation = single
+        .and_then(|a| ggv_xml_parse_child::<f64>(&a, "rotation"))
+        .unwrap_or(0.0);
+    let label = Label::new(font, size, rotation.round().rem_euclid(360.0) as u16);
+    match attribute("IID_IGraphicFillAttributes") {
+        Some(fill) if ggv_xml_parse_child::<u16>(&fill, "fillStyle").unwrap_or(0) != 0 => {
+            match ggv_xml_parse_color(&fill) {
+                Some(color) => label.with_background(color),
+                None => label,
+            }
+        }
+        _ => label,
+    }
+}
+
 /// Parse name out of <base><name>...</name></base>
 fn ggv_xml_read_name(object: &roxmltree::Node) -> Option<String> {
     let base = object.children().find(|c| c.has_tag_name("base"))?;
@@ -130,8 +241,13 @@ fn ggv_xml_read_name(object: &roxmltree::Node) -> Option<String> {
     }
 }
 
-/// Parse object elements from objectList
-fn ggv_xml_read_object(object: &roxmltree::Node, geodata: &mut Geodata) {
+/// Parse object elements from objectList. Attachments are noted with
+/// the name of the object referencing them.
+fn ggv_xml_read_object(
+    object: &roxmltree::Node,
+    geodata: &mut Geodata,
+    references: &mut Vec<(String, String)>,
+) {
     let cls_name = object.attribute("clsName").unwrap_or("");
     let uid = object.attribute("uid").unwrap_or("");
 
@@ -151,6 +267,7 @@ fn ggv_xml_read_object(object: &roxmltree::Node, geodata: &mut Geodata) {
     if get_debug() >= 2 {
         eprintln!("xml: name: {}", name);
     }
+    let attachments = ggv_xml_read_attachments(object);
 
     let Some(attribute_list) = object.children().find(|c| c.has_tag_name("attributeList")) else {
         return;
@@ -174,69 +291,211 @@ fn ggv_xml_read_object(object: &roxmltree::Node, geodata: &mut Geodata) {
         } else {
             waypoint_list.set_name(&name);
         }
+        references.extend(attachments.into_iter().map(|a| (a, waypoint_list.name())));
         geodata.add_track(waypoint_list);
     } else if cls_name == "CLSID_GraphicCircle" {
-        let mut waypoint = waypoint_list.extract_first_waypoint().clone();
+        let mut waypoint = waypoint_list
+            .extract_first_waypoint()
+            .clone()
+            .with_description(waypoint_list.description())
+            .with_style(waypoint_list.style().clone());
+        if let Some(shape) = ggv_xml_read_shape(&attribute_list, ShapeKind::Circle) {
+            waypoint = waypoint.with_shape(shape);
+        }
         if name.is_empty() || name == "Circle" {
             waypoint.set_name(&format!("RPT{:03}", geodata.waypoints_len() + 1));
         } else {
             waypoint.set_name(&name);
         }
+        references.extend(attachments.into_iter().map(|a| (a, waypoint.name())));
         geodata.add_waypoint(waypoint);
     } else if cls_name == "CLSID_GraphicText" {
-        let mut waypoint = waypoint_list.extract_first_waypoint().clone();
+        let mut waypoint = waypoint_list
+            .extract_first_waypoint()
+            .clone()
+            .with_description(waypoint_list.description())
+            .with_style(waypoint_list.style().clone())
+            .with_label(ggv_xml_read_label(&attribute_list));
+        // The displayed text becomes the name, the object name is kept
+        // as comment
+        if !name.is_empty() && name != "Text" {
+            waypoint.set_comment(&name);
+        }
         if waypoint_list.name().is_empty() || waypoint_list.name() == "Text" {
             waypoint.set_name(&format!("Text {}", geodata.waypoints_len() + 1));
         } else {
             waypoint.set_name(&waypoint_list.name());
         }
+        references.extend(attachments.into_iter().map(|a| (a, waypoint.name())));
         geodata.add_waypoint(waypoint);
     }
 }
 
+/// File names of the attachments from <base><userDataList><userData>
+fn ggv_xml_read_attachments(object: &roxmltree::Node) -> Vec<String> {
+    let Some(base) = object.children().find(|c| c.has_tag_name("base")) else {
+        return Vec::new();
+    };
+    base.children()
+        .filter(|c| c.has_tag_name("userDataList"))
+        .flat_map(|l| l.children().filter(|c| c.has_tag_name("userData")))
+        .filter_map(|d| ggv_xml_parse_child::<String>(&d, "attachment"))
+        .collect()
+}
+
 /// Parse objectList elements
-fn ggv_xml_read_object_list(object_list: roxmltree::Node, geodata: &mut Geodata) {
+fn ggv_xml_read_object_list(
+    object_list: roxmltree::Node,
+    geodata: &mut Geodata,
+    references: &mut Vec<(String, String)>,
+) {
     for object in object_list.children().filter(|c| c.has_tag_name("object")) {
-        ggv_xml_read_object(&object, geodata);
+        ggv_xml_read_object(&object, geodata, references);
     }
 }
 
-/// Parse geogrid50.xml
-fn ggv_xml_process_xml<'a>(xml: &str) -> Result<Geodata> {
+/// Parse geogrid50.xml, the other zip members are added as attachments
+fn ggv_xml_process_xml<'a>(xml: &str, members: ZipMembers) -> Result<Geodata> {
     let mut geodata = Geodata::new().with_debug(get_debug());
     let doc = roxmltree::Document::parse(xml).with_context(|| "parse xml")?;
     let root = doc.root().first_child().with_context(|| "root node")?;
     root.has_tag_name("geogridOvl")
         .then_some(())
         .ok_or_else(|| anyhow!("geogridOvl tag"))?;
+    ggv_xml_read_map(&root, geodata.metadata_mut());
+    let mut references = Vec::new();
     for object_list in root.children().filter(|c| c.has_tag_name("objectList")) {
-        ggv_xml_read_object_list(object_list, &mut geodata);
+        ggv_xml_read_object_list(object_list, &mut geodata, &mut references);
+    }
+    for (name, data) in members {
+        let object = references
+            .iter()
+            .find(|(attachment, _)| *attachment == name)
+            .map_or("", |(_, object)| object.as_str());
+        geodata.add_attachment(&name, data, object);
     }
     Ok(geodata)
 }
 
-/// Extract geogrid50.xml from zip
-fn ggv_xml_extract_zip<'a>(i: &'a [u8]) -> Result<String> {
+/// Parse generator and mapReference elements into overlay metadata
+fn ggv_xml_read_map(root: &roxmltree::Node, metadata: &mut Metadata) {
+    if let Some(generator) = root.children().find(|c| c.has_tag_name("generator"))
+        && let Some(build) = ggv_xml_parse_child::<String>(&generator, "build")
+    {
+        metadata.set_generator(&format!("Geogrid-Viewer {}", build));
+    }
+    let Some(map) = root.children().find(|c| c.has_tag_name("mapReference")) else {
+        return;
+    };
+    if let Some(rastermap) = map.children().find(|c| c.has_tag_name("rastermap")) {
+        if let Some(name) = ggv_xml_parse_child::<String>(&rastermap, "name") {
+            metadata.set_map_name(&name);
+        }
+        if let Some(scale) = ggv_xml_parse_child::<u32>(&rastermap, "scale") {
+            metadata.set_scale(scale);
+        }
+    }
+    if let Some(zoom) = ggv_xml_parse_child::<f64>(&map, "zoom") {
+        metadata.set_zoom(zoom);
+    }
+    if let Some(center) = map
+        .children()
+        .find(|c| c.has_tag_name("centerPos"))
+        .and_then(|c| c.children().find(|c| c.has_tag_name("coord")))
+        .and_then(|c| ggv_xml_parse_coord(&c))
+    {
+        metadata.set_center(center);
+    }
+    if get_debug() >= 2 {
+        eprintln!(
+            "xml: map \"{}\" scale {:?} zoom {:?} generator \"{}\"",
+            metadata.map_name(),
+            metadata.scale(),
+            metadata.zoom(),
+            metadata.generator()
+        );
+    }
+}
+
+/// Names and contents of zip members
+type ZipMembers = Vec<(String, Vec<u8>)>;
+
+/// Read all zip members through the central directory
+fn ggv_xml_read_zip_archive(i: &[u8]) -> Result<ZipMembers> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(i))?;
+    let mut members = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)
+            .with_context(|| format!("reading {} from zip", name))?;
+        members.push((name, buf));
+    }
+    Ok(members)
+}
+
+/// Read all zip members by walking the local headers, for archives
+/// with a broken central directory
+fn ggv_xml_read_zip_stream(i: &[u8]) -> Result<ZipMembers> {
     let mut buf_reader = BufReader::new(i);
+    let mut members = Vec::new();
     loop {
         match zip::read::read_zipfile_from_stream(&mut buf_reader) {
             Ok(Some(mut file)) => {
-                if file.name() == "geogrid50.xml" {
-                    if get_debug() >= 2 {
-                        eprintln!("xml: found geogrid50.xml");
-                    }
-                    let mut xml_buf = Vec::new();
-                    file.read_to_end(&mut xml_buf)
-                        .with_context(|| "reading geogrid50.xml from zip")?;
-                    let xml_str = decode_latin1(&xml_buf).to_string();
-                    return Ok(xml_str);
+                let name = file.name().to_string();
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf)
+                    .with_context(|| format!("reading {} from zip", name))?;
+                if !name.ends_with('/') {
+                    members.push((name, buf));
                 }
             }
             Ok(None) => break,
-            Err(e) => return Err(anyhow!(e)),
+            Err(e) => {
+                if members.is_empty() {
+                    return Err(anyhow!(e));
+                }
+                // Keep what was read up to the broken entry
+                if get_debug() >= 1 {
+                    eprintln!("xml: stopped reading zip stream: {}", e);
+                }
+                break;
+            }
+        }
+    }
+    Ok(members)
+}
+
+/// Extract geogrid50.xml and all other members from zip
+fn ggv_xml_extract_zip<'a>(i: &'a [u8]) -> Result<(String, ZipMembers)> {
+    let mut members = match ggv_xml_read_zip_archive(i) {
+        Ok(members) => members,
+        Err(e) => {
+            if get_debug() >= 1 {
+                eprintln!(
+                    "xml: zip central directory unusable ({}), reading stream",
+                    e
+                );
+            }
+            ggv_xml_read_zip_stream(i)?
+        }
+    };
+    if get_debug() >= 1 {
+        for (name, buf) in members.iter() {
+            eprintln!("xml: zip member: {} ({} bytes)", name, buf.len());
         }
     }
-    Err(anyhow!("finding geogrid50.xml in zip"))
+    let index = members
+        .iter()
+        .position(|(name, _)| name.eq_ignore_ascii_case("geogrid50.xml"))
+        .with_context(|| "finding geogrid50.xml in zip")?;
+    let (_, buf) = members.remove(index);
+    let xml = decode_latin1(&buf).to_string();
+    Ok((xml, members))
 }
 
 //////////////////////////////////////////////////////////////////////
@@ -259,7 +518,7 @@ impl Format for GgvXmlFormat {
         if self.debug >= 3 {
             eprintln!("xml: input size: {}", buf.len());
         }
-        let xml = match ggv_xml_extract_zip(buf) {
+        let (xml, members) = match ggv_xml_extract_zip(buf) {
             Ok(d) => d,
             Err(e) => {
                 return Err(anyhow!(
@@ -268,7 +527,7 @@ impl Format for GgvXmlFormat {
                 ))
             }
         };
-        let geodata = match ggv_xml_process_xml(&xml) {
+        let geodata = match ggv_xml_process_xml(&xml, members) {
             Ok(x) => x,
             Err(e) => {
                 return Err(anyhow!(
@@ -279,7 +538,7 @@ impl Format for GgvXmlFormat {
         };
         Ok(geodata)
     }
-    fn write(&self, _geodata: &Geodata) -> Result<String> {
+    fn write(&self, _geodata: &Geodata) -> Result<Vec<u8>> {
         todo!("ggv_xml write support");
     }
     fn name<'a>(&self) -> &'a str {
@@ -0,0 +1,71 @@
+///
+///  ggv_xml test cases
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use std::io::Cursor;
+    use std::io::Write;
+
+    use crate::format::Format;
+    use crate::ggv_xml::*;
+
+    #[test]
+    fn test_attachments() {
+        let buf = include_bytes!("../testdata/ggv_xml-sample-1.ovl");
+        let geodata = GgvXmlFormat::new().read(buf).unwrap();
+        assert_eq!(geodata.data().len(), 1);
+        let data = &geodata.data()[0];
+        assert_eq!(data.name(), "Bindata0.dat");
+        assert_eq!(data.kind(), "dat");
+        assert_eq!(data.data().len(), 16);
+        assert_eq!(data.object(), "GPSIESTRACK ON GPSIES.COM");
+    }
+
+    fn zip(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, data) in members {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_zip_members() {
+        let xml = b"<geogridOvl><objectList/></geogridOvl>";
+        let large = vec![0u8; 100000];
+        let buf = zip(&[("large.dat", &large), ("GeoGrid50.XML", xml)]);
+        let format = GgvXmlFormat::new();
+        let geodata = format.read(&buf).unwrap();
+        assert_eq!(geodata.data().len(), 1);
+        assert_eq!(geodata.data()[0].data().len(), 100000);
+
+        // Junk before the archive
+        let mut junk = b"junk".to_vec();
+        junk.extend_from_slice(&buf);
+        assert_eq!(format.read(&junk).unwrap().data().len(), 1);
+
+        // Without central directory the local headers are read
+        let end = buf.len() - 22 - 2 * 46 - "large.dat".len() - "GeoGrid50.XML".len();
+        let geodata = format.read(&buf[..end]).unwrap();
+        assert_eq!(geodata.data().len(), 1);
+    }
+}
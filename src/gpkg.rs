@@ -0,0 +1,319 @@
+///
+///  Support for OGC GeoPackage writing
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{params, Connection, MAIN_DB};
+
+use crate::format::Format;
+use crate::geodata::Geodata;
+use crate::geodata::Style;
+use crate::geodata::Waypoint;
+use crate::geodata::WaypointList;
+use crate::wkt::{wkt_binary, WktKind};
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+/// "GPKG" as required in the SQLite application id
+const GPKG_APPLICATION_ID: i32 = 0x47504b47;
+/// GeoPackage version 1.3
+const GPKG_USER_VERSION: i32 = 10300;
+const GPKG_SRS_ID: i32 = 4326;
+
+const GPKG_WGS84_DEFINITION: &str = "GEOGCS[\"WGS 84\",DATUM[\"WGS_1984\",\
+    SPHEROID[\"WGS 84\",6378137,298.257223563,AUTHORITY[\"EPSG\",\"7030\"]],\
+    AUTHORITY[\"EPSG\",\"6326\"]],PRIMEM[\"Greenwich\",0,AUTHORITY[\"EPSG\",\"8901\"]],\
+    UNIT[\"degree\",0.0174532925199433,AUTHORITY[\"EPSG\",\"9122\"]],\
+    AXIS[\"Latitude\",NORTH],AXIS[\"Longitude\",EAST],AUTHORITY[\"EPSG\",\"4326\"]]";
+
+const GPKG_SCHEMA: &str = "
+CREATE TABLE gpkg_spatial_ref_sys (
+    srs_name TEXT NOT NULL,
+    srs_id INTEGER PRIMARY KEY,
+    organization TEXT NOT NULL,
+    organization_coordsys_id INTEGER NOT NULL,
+    definition TEXT NOT NULL,
+    description TEXT
+);
+CREATE TABLE gpkg_contents (
+    table_name TEXT NOT NULL PRIMARY KEY,
+    data_type TEXT NOT NULL,
+    identifier TEXT UNIQUE,
+    description TEXT DEFAULT '',
+    last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+    min_x DOUBLE,
+    min_y DOUBLE,
+    max_x DOUBLE,
+    max_y DOUBLE,
+    srs_id INTEGER,
+    CONSTRAINT fk_gc_r_srs_id FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+CREATE TABLE gpkg_geometry_columns (
+    table_name TEXT NOT NULL,
+    column_name TEXT NOT NULL,
+    geometry_type_name TEXT NOT NULL,
+    srs_id INTEGER NOT NULL,
+    z TINYINT NOT NULL,
+    m TINYINT NOT NULL,
+    CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name),
+    CONSTRAINT uk_gc_table_name UNIQUE (table_name),
+    CONSTRAINT fk_gc_tn FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name),
+    CONSTRAINT fk_gc_srs FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys (srs_id)
+);
+";
+
+/// Feature tables with their geometry type and extra columns
+const GPKG_TABLES: [(&str, &str, &str); 3] = [
+    ("waypoints", "POINT", ""),
+    ("lines", "LINESTRING", "kind TEXT, "),
+    ("polygons", "POLYGON", ""),
+];
+
+/// GeoPackage geometry blob: "GP" header with SRS id and optional XY
+/// envelope followed by standard WKB.
+fn gpkg_geometry(kind: WktKind, points: &[&Waypoint]) -> Vec<u8> {
+    let z = points.iter().all(|p| !p.elevation().is_nan());
+    let envelope = !matches!(kind, WktKind::Point);
+    // flags: little endian, envelope type 1 ([minx, maxx, miny, maxy])
+    let flags: u8 = if envelope { 0x03 } else { 0x01 };
+    let mut buf = vec![b'G', b'P', 0, flags];
+    buf.extend_from_slice(&GPKG_SRS_ID.to_le_bytes());
+    if envelope {
+        let lon = points.iter().map(|p| p.longitude());
+        let lat = points.iter().map(|p| p.latitude());
+        buf.extend_from_slice(&lon.clone().fold(f64::MAX, f64::min).to_le_bytes());
+        buf.extend_from_slice(&lon.fold(f64::MIN, f64::max).to_le_bytes());
+        buf.extend_from_slice(&lat.clone().fold(f64::MAX, f64::min).to_le_bytes());
+        buf.extend_from_slice(&lat.fold(f64::MIN, f64::max).to_le_bytes());
+    }
+    buf.extend_from_slice(&wkt_binary(kind, points, z, false));
+    buf
+}
+
+fn gpkg_color(style: &Style) -> Option<String> {
+    style
+        .color()
+        .map(|c| format!("#{:02x}{:02x}{:02x}", c.red(), c.green(), c.blue()))
+}
+
+fn gpkg_create(conn: &Connection, geodata: &Geodata) -> rusqlite::Result<()> {
+    conn.pragma_update(None, "application_id", GPKG_APPLICATION_ID)?;
+    conn.pragma_update(None, "user_version", GPKG_USER_VERSION)?;
+    conn.execute_batch(GPKG_SCHEMA)?;
+    let mut srs =
+        conn.prepare("INSERT INTO gpkg_spatial_ref_sys VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+    srs.execute(params![
+        "Undefined cartesian SRS",
+        -1,
+        "NONE",
+        -1,
+        "undefined",
+        "undefined cartesian coordinate reference system"
+    ])?;
+    srs.execute(params![
+        "Undefined geographic SRS",
+        0,
+        "NONE",
+        0,
+        "undefined",
+        "undefined geographic coordinate reference system"
+    ])?;
+    srs.execute(params![
+        "WGS 84 geodetic",
+        GPKG_SRS_ID,
+        "EPSG",
+        4326,
+        GPKG_WGS84_DEFINITION,
+        "longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid"
+    ])?;
+
+    let bounds = geodata.get_bounds();
+    for (table, geometry_type, columns) in GPKG_TABLES {
+        conn.execute_batch(&format!(
+            "CREATE TABLE {} (\
+             fid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, \
+             geom {}, name TEXT, comment TEXT, description TEXT, {}\"group\" INTEGER, \
+             color TEXT, width INTEGER)",
+            table, geometry_type, columns
+        ))?;
+        conn.execute(
+            "INSERT INTO gpkg_contents \
+             (table_name, data_type, identifier, min_x, min_y, max_x, max_y, srs_id) \
+             VALUES (?1, 'features', ?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                table,
+                bounds.as_ref().map(|b| b.0.longitude()),
+                bounds.as_ref().map(|b| b.0.latitude()),
+                bounds.as_ref().map(|b| b.1.longitude()),
+                bounds.as_ref().map(|b| b.1.latitude()),
+                GPKG_SRS_ID
+            ],
+        )?;
+        // z = 2: Z values are optional
+        conn.execute(
+            "INSERT INTO gpkg_geometry_columns VALUES (?1, 'geom', ?2, ?3, 2, 0)",
+            params![table, geometry_type, GPKG_SRS_ID],
+        )?;
+    }
+    Ok(())
+}
+
+fn gpkg_insert_list(conn: &Connection, list: &WaypointList, kind: &str) -> rusqlite::Result<()> {
+    let mut points: Vec<&Waypoint> = list.waypoints().iter().collect();
+    let style = list.style();
+    if list.area() && points.len() > 2 {
+        // Polygon rings are closed by repeating the first point
+        let first = points[0];
+        let last = points[points.len() - 1];
+        if first.latitude() != last.latitude() || first.longitude() != last.longitude() {
+            points.push(first);
+        }
+        conn.execute(
+            "INSERT INTO polygons (geom, name, comment, description, \"group\", color, width) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                gpkg_geometry(WktKind::Polygon, &points),
+                list.name(),
+                list.comment(),
+                list.description(),
+                list.group(),
+                gpkg_color(style),
+                style.width()
+            ],
+        )?;
+    } else {
+        conn.execute(
+            "INSERT INTO lines (geom, name, comment, description, kind, \"group\", color, width) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                gpkg_geometry(WktKind::LineString, &points),
+                list.name(),
+                list.comment(),
+                list.description(),
+                kind,
+                list.group(),
+                gpkg_color(style),
+                style.width()
+            ],
+        )?;
+    }
+    if get_debug() >= 2 {
+        eprintln!(
+            "gpkg: insert {} (len: {:3}, name: \"{}\", area: {})",
+            kind,
+            list.len(),
+            list.name(),
+            list.area()
+        );
+    }
+    Ok(())
+}
+
+fn gpkg_insert(conn: &Connection, geodata: &Geodata) -> rusqlite::Result<()> {
+    for waypoint in geodata.waypoints().waypoints().iter() {
+        let style = waypoint.style();
+        conn.execute(
+            "INSERT INTO waypoints (geom, name, comment, description, \"group\", color, width) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                gpkg_geometry(WktKind::Point, &[waypoint]),
+                waypoint.name(),
+                waypoint.comment(),
+                waypoint.description(),
+                waypoint.group(),
+                gpkg_color(style),
+                style.width()
+            ],
+        )?;
+    }
+    for track in geodata.tracks().iter().filter(|l| l.len() > 0) {
+        gpkg_insert_list(conn, track, "track")?;
+    }
+    for route in geodata.routes().iter().filter(|l| l.len() > 0) {
+        gpkg_insert_list(conn, route, "route")?;
+    }
+    Ok(())
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct GpkgFormat {
+    debug: u8,
+}
+
+impl Format for GpkgFormat {
+    fn probe(&self, _buf: &[u8]) -> bool {
+        false
+    }
+    fn read(&self, _buf: &[u8]) -> Result<Geodata> {
+        Err(anyhow!("gpkg read not supported"))
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        // The database is built in memory and serialized, so the
+        // result can be written like any other output format.
+        let mut conn = Connection::open_in_memory().context("gpkg: open database")?;
+        let tx = conn.transaction().context("gpkg: begin transaction")?;
+        gpkg_create(&tx, geodata).context("gpkg: create tables")?;
+        gpkg_insert(&tx, geodata).context("gpkg: insert features")?;
+        tx.commit().context("gpkg: commit")?;
+        let data = conn.serialize(MAIN_DB).context("gpkg: serialize")?;
+        if self.debug >= 1 {
+            eprintln!("gpkg: database size {} bytes", data.len());
+        }
+        Ok(data.to_vec())
+    }
+    fn name<'a>(&self) -> &'a str {
+        "gpkg"
+    }
+    fn can_read(&self) -> bool {
+        false
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+}
+
+impl Default for GpkgFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GpkgFormat {
+    pub fn new() -> Self {
+        Self { debug: 0 }
+    }
+}
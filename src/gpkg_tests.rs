@@ -0,0 +1,100 @@
+///
+///  gpkg test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use rusqlite::{Connection, MAIN_DB};
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::gpkg::*;
+
+    #[test]
+    fn test_write_gpkg() {
+        let mut geodata = Geodata::new();
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.5)
+                .with_lon(10.5)
+                .with_name("Peak"),
+        );
+        let mut area = WaypointList::new();
+        area.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        area.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(11.0));
+        area.add_waypoint(Waypoint::new().with_lat(51.0).with_lon(11.0));
+        area.set_area(true);
+        area.set_group(2);
+        area.set_style(Style::new().with_color(Color::new(255, 0, 0)));
+        geodata.add_track(area);
+
+        let data = GpkgFormat::new().write(&geodata).unwrap();
+        assert_eq!(&data[..16], b"SQLite format 3\0");
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.deserialize_read_exact(MAIN_DB, data.as_slice(), data.len(), true)
+            .unwrap();
+
+        let id: i32 = conn
+            .query_row("PRAGMA application_id", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(id, 0x47504b47);
+        let (min_x, max_y): (f64, f64) = conn
+            .query_row(
+                "SELECT min_x, max_y FROM gpkg_contents WHERE table_name = 'polygons'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((min_x, max_y), (10.0, 51.0));
+        let (color, group, geom): (String, u16, Vec<u8>) = conn
+            .query_row("SELECT color, \"group\", geom FROM polygons", [], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+            })
+            .unwrap();
+        assert_eq!((color.as_str(), group), ("#ff0000", 2));
+        // header with envelope, WKB polygon with one closed ring of 4 points
+        assert_eq!(&geom[..4], b"GP\x00\x03");
+        assert_eq!(geom.len(), 8 + 32 + 1 + 4 + 4 + 4 + 4 * 16);
+        let count: i32 = conn
+            .query_row("SELECT count(*) FROM waypoints", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_extent_south() {
+        let mut geodata = Geodata::new();
+        geodata.add_waypoint(Waypoint::new().with_lat(-33.9).with_lon(18.4));
+        geodata.add_waypoint(Waypoint::new().with_lat(-34.4).with_lon(18.5));
+
+        let data = GpkgFormat::new().write(&geodata).unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.deserialize_read_exact(MAIN_DB, data.as_slice(), data.len(), true)
+            .unwrap();
+        let (min_y, max_y): (f64, f64) = conn
+            .query_row(
+                "SELECT min_y, max_y FROM gpkg_contents WHERE table_name = 'waypoints'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((min_y, max_y), (-34.4, -33.9));
+    }
+}
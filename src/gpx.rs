@@ -23,12 +23,16 @@ use std::sync::atomic::AtomicU8;
 use std::sync::atomic::Ordering;
 
 use anyhow::{anyhow, Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use quick_xml::events::{BytesDecl, BytesText, Event};
 use quick_xml::writer::Writer;
 
 use crate::format::Format;
+use crate::geodata::Color;
 use crate::geodata::Geodata;
+use crate::geodata::Link;
+use crate::geodata::Metadata;
+use crate::geodata::Style;
 use crate::geodata::Waypoint;
 use crate::geodata::WaypointList;
 
@@ -59,28 +63,149 @@ fn gpx_read_text(node: roxmltree::Node, tag: &str) -> Option<String> {
     Some(String::from(t))
 }
 
+/// Display colors of the Garmin GPX extensions (gpxx:DisplayColor)
+const GPX_GARMIN_COLORS: [(&str, Color); 16] = [
+    ("Black", Color::new(0, 0, 0)),
+    ("DarkRed", Color::new(139, 0, 0)),
+    ("DarkGreen", Color::new(0, 100, 0)),
+    ("DarkYellow", Color::new(128, 128, 0)),
+    ("DarkBlue", Color::new(0, 0, 139)),
+    ("DarkMagenta", Color::new(139, 0, 139)),
+    ("DarkCyan", Color::new(0, 139, 139)),
+    ("LightGray", Color::new(211, 211, 211)),
+    ("DarkGray", Color::new(169, 169, 169)),
+    ("Red", Color::new(255, 0, 0)),
+    ("Green", Color::new(0, 255, 0)),
+    ("Yellow", Color::new(255, 255, 0)),
+    ("Blue", Color::new(0, 0, 255)),
+    ("Magenta", Color::new(255, 0, 255)),
+    ("Cyan", Color::new(0, 255, 255)),
+    ("White", Color::new(255, 255, 255)),
+];
+
+fn gpx_read_time(node: roxmltree::Node) -> Option<DateTime<Utc>> {
+    let time = gpx_read_text(node, "time")?;
+    let time = DateTime::parse_from_rfc3339(time.trim()).ok()?;
+    Some(time.with_timezone(&Utc))
+}
+
+/// Read GPX 1.1 link elements and the GPX 1.0 url/urlname pair
+fn gpx_read_links(node: roxmltree::Node) -> Vec<Link> {
+    let mut links: Vec<Link> = node
+        .children()
+        .filter(|c| c.has_tag_name("link"))
+        .filter_map(|c| {
+            let href = c.attribute("href")?;
+            let text = gpx_read_text(c, "text").unwrap_or_default();
+            Some(Link::new(href, &text))
+        })
+        .collect();
+    if let Some(url) = gpx_read_text(node, "url") {
+        let text = gpx_read_text(node, "urlname").unwrap_or_default();
+        links.push(Link::new(&url, &text));
+    }
+    links
+}
+
+/// Parse "RRGGBB" as used by the gpx_style extension
+fn gpx_parse_color(text: &str) -> Option<Color> {
+    let text = text.trim().trim_start_matches('#');
+    if text.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(text, 16).ok()?;
+    Some(Color::new(
+        (value >> 16) as u8,
+        (value >> 8) as u8,
+        value as u8,
+    ))
+}
+
+/// Read line color and width from the gpx_style (line/color,
+/// line/width) or Garmin (DisplayColor) extensions
+fn gpx_read_style(node: roxmltree::Node) -> Style {
+    let mut style = Style::new();
+    let Some(extensions) = node.children().find(|c| c.has_tag_name("extensions")) else {
+        return style;
+    };
+    for child in extensions.descendants() {
+        if child.has_tag_name("line") {
+            if let Some(color) = gpx_read_text(child, "color").and_then(|c| gpx_parse_color(&c)) {
+                style = style.with_color(color);
+            }
+            if let Some(width) = gpx_read_text(child, "width")
+                .and_then(|w| w.trim().parse::<f64>().ok())
+                .filter(|w| *w > 0.0)
+            {
+                style = style.with_width(width.round().max(1.0) as u16);
+            }
+        } else if child.has_tag_name("DisplayColor")
+            && let Some(text) = child.text()
+            && let Some((_, color)) = GPX_GARMIN_COLORS.iter().find(|(n, _)| *n == text.trim())
+        {
+            style = style.with_color(*color);
+        }
+    }
+    if get_debug() >= 2 && (style.color().is_some() || style.width().is_some()) {
+        eprintln!(
+            "gpx: style color {:?} width {:?}",
+            style.color(),
+            style.width()
+        );
+    }
+    style
+}
+
 fn gpx_read_waypoint(node: roxmltree::Node) -> Option<Waypoint> {
     let lat = node.attribute("lat")?;
     let lat = lat.parse::<f64>().ok()?;
     let lon = node.attribute("lon")?;
     let lon = lon.parse::<f64>().ok()?;
-    let ele = gpx_read_text(node, "ele").and_then(|v| v.parse::<f64>().ok());
-    match ele {
-        Some(ele) => Some(
-            Waypoint::new()
-                .with_lat(lat)
-                .with_lon(lon)
-                .with_elevation(ele),
-        ),
-        _ => Some(Waypoint::new().with_lat(lat).with_lon(lon)),
+    let mut waypoint = Waypoint::new().with_lat(lat).with_lon(lon);
+    if let Some(ele) = gpx_read_text(node, "ele").and_then(|v| v.parse::<f64>().ok()) {
+        waypoint = waypoint.with_elevation(ele);
+    }
+    if let Some(time) = gpx_read_time(node) {
+        waypoint = waypoint.with_time(time);
     }
+    if let Some(name) = gpx_read_text(node, "name") {
+        waypoint.set_name(&name);
+    }
+    if let Some(cmt) = gpx_read_text(node, "cmt") {
+        waypoint = waypoint.with_comment(&cmt);
+    }
+    if let Some(desc) = gpx_read_text(node, "desc") {
+        waypoint = waypoint.with_description(&desc);
+    }
+    if let Some(sym) = gpx_read_text(node, "sym") {
+        waypoint = waypoint.with_symbol(&sym);
+    }
+    if let Some(kind) = gpx_read_text(node, "type") {
+        waypoint = waypoint.with_kind(&kind);
+    }
+    for link in gpx_read_links(node) {
+        waypoint = waypoint.with_link(link);
+    }
+    Some(waypoint)
 }
 
-fn gpx_read_trk(trk: roxmltree::Node, geodata: &mut Geodata) {
-    let name = gpx_read_text(trk, "name").unwrap_or(String::from(""));
+/// Read name, comment, description and style shared by trk and rte
+fn gpx_read_list(node: roxmltree::Node) -> WaypointList {
     let mut list = WaypointList::new();
-    list.set_name(&name);
+    list.set_name(&gpx_read_text(node, "name").unwrap_or_default());
+    list.set_comment(&gpx_read_text(node, "cmt").unwrap_or_default());
+    list.set_description(&gpx_read_text(node, "desc").unwrap_or_default());
+    for link in gpx_read_links(node) {
+        list.add_link(link);
+    }
+    list.set_style(gpx_read_style(node));
+    list
+}
+
+fn gpx_read_trk(trk: roxmltree::Node, geodata: &mut Geodata) {
+    let mut list = gpx_read_list(trk);
     for trkseg in trk.children().filter(|c| c.has_tag_name("trkseg")) {
+        list.start_segment();
         for trkpt in trkseg.children().filter(|c| c.has_tag_name("trkpt")) {
             let Some(waypoint) = gpx_read_waypoint(trkpt) else {
                 continue;
@@ -92,45 +217,60 @@ fn gpx_read_trk(trk: roxmltree::Node, geodata: &mut Geodata) {
 }
 
 fn gpx_read_rte(rte: roxmltree::Node, geodata: &mut Geodata) {
-    let name = gpx_read_text(rte, "name").unwrap_or(String::from(""));
-    let mut list = WaypointList::new();
-    list.set_name(&name);
+    let mut list = gpx_read_list(rte);
     for rtept in rte.children().filter(|c| c.has_tag_name("rtept")) {
-        let Some(mut waypoint) = gpx_read_waypoint(rtept) else {
+        let Some(waypoint) = gpx_read_waypoint(rtept) else {
             continue;
         };
-        let name = gpx_read_text(rtept, "name").unwrap_or(String::from(""));
-        if !name.is_empty() {
-            waypoint.set_name(&name);
-        }
         list.add_waypoint(waypoint);
     }
     geodata.add_route(list);
 }
 
 fn gpx_read_wpt(wpt: roxmltree::Node, geodata: &mut Geodata) {
-    let Some(mut waypoint) = gpx_read_waypoint(wpt) else {
+    let Some(waypoint) = gpx_read_waypoint(wpt) else {
         return;
     };
-    let name = gpx_read_text(wpt, "name").unwrap_or(String::from(""));
-    if !name.is_empty() {
-        waypoint.set_name(&name);
-        geodata.add_waypoint(waypoint);
-        return;
+    geodata.add_waypoint(waypoint);
+}
+
+/// Read GPX 1.1 metadata element or the GPX 1.0 header elements
+fn gpx_read_metadata(root: roxmltree::Node) -> Metadata {
+    let mut metadata = Metadata::new();
+    if let Some(creator) = root.attribute("creator") {
+        metadata.set_generator(creator);
     }
-    let cmt = gpx_read_text(wpt, "cmt").unwrap_or(String::from(""));
-    if !cmt.is_empty() {
-        waypoint.set_name(&cmt);
-        geodata.add_waypoint(waypoint);
-        return;
+    let node = root
+        .children()
+        .find(|c| c.has_tag_name("metadata"))
+        .unwrap_or(root);
+    if let Some(name) = gpx_read_text(node, "name") {
+        metadata.set_name(&name);
     }
-    let desc = gpx_read_text(wpt, "desc").unwrap_or(String::from(""));
-    if !desc.is_empty() {
-        waypoint.set_name(&desc);
-        geodata.add_waypoint(waypoint);
-        return;
+    if let Some(desc) = gpx_read_text(node, "desc") {
+        metadata.set_description(&desc);
     }
-    geodata.add_waypoint(waypoint);
+    // GPX 1.1 uses a person element, GPX 1.0 plain text
+    let author = node.children().find(|c| c.has_tag_name("author"));
+    if let Some(author) =
+        author.and_then(|a| gpx_read_text(a, "name").or(a.text().map(String::from)))
+    {
+        metadata.set_author(author.trim());
+    }
+    if let Some(time) = gpx_read_time(node) {
+        metadata.set_time(time);
+    }
+    for link in gpx_read_links(node) {
+        metadata.add_link(link);
+    }
+    if get_debug() >= 2 {
+        eprintln!(
+            "gpx: metadata name \"{}\" generator \"{}\"",
+            metadata.name(),
+            metadata.generator()
+        );
+    }
+    metadata
 }
 
 /// Parse gpx xml
@@ -141,6 +281,7 @@ fn gpx_process_xml<'a>(xml: &str) -> Result<Geodata> {
     root.has_tag_name("gpx")
         .then_some(())
         .ok_or_else(|| anyhow!("gpx tag"))?;
+    geodata.set_metadata(gpx_read_metadata(root));
     for trk in root.children().filter(|c| c.has_tag_name("trk")) {
         gpx_read_trk(trk, &mut geodata);
     }
@@ -153,6 +294,16 @@ fn gpx_process_xml<'a>(xml: &str) -> Result<Geodata> {
     Ok(geodata)
 }
 
+/// Write a text element, empty values are skipped
+fn gpx_write_text(writer: &mut Writer<&mut Vec<u8>>, tag: &str, text: &str) -> std::io::Result<()> {
+    if !text.is_empty() {
+        writer
+            .create_element(tag)
+            .write_text_content(BytesText::new(text))?;
+    }
+    Ok(())
+}
+
 //////////////////////////////////////////////////////////////////////
 //            entry points called by ggvtogpx main process
 //////////////////////////////////////////////////////////////////////
@@ -177,7 +328,7 @@ impl Format for GpxFormat {
         let str = std::str::from_utf8(buf)?;
         gpx_process_xml(str)
     }
-    fn write(&self, geodata: &Geodata) -> Result<String> {
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
         let mut buffer = Vec::new();
         let mut writer = Writer::new_with_indent(&mut buffer, b' ', 2);
         let epoch = DateTime::from_timestamp_secs(0).expect("invalid timestmap");
@@ -192,6 +343,16 @@ impl Format for GpxFormat {
             .with_attribute(("creator", self.creator.as_str()))
             .with_attribute(("xmlns", "http://www.topografix.com/GPX/1/0"))
             .write_inner_content(|writer| {
+                // GPX 1.0 keeps the file metadata in the header. The map
+                // an overlay was drawn on is noted in the description.
+                let metadata = geodata.metadata();
+                gpx_write_text(writer, "name", metadata.name())?;
+                if metadata.description().is_empty() && !metadata.map_name().is_empty() {
+                    gpx_write_text(writer, "desc", &format!("Map: {}", metadata.map_name()))?;
+                } else {
+                    gpx_write_text(writer, "desc", metadata.description())?;
+                }
+                gpx_write_text(writer, "author", metadata.author())?;
                 if self.testmode {
                     writer
                         .create_element("time")
@@ -225,18 +386,51 @@ impl Format for GpxFormat {
                     _ => (),
                 }
 
+                // Group names go to the type of waypoints and to the
+                // comment of tracks and routes, GPX 1.0 has no better
+                // place for them.
                 for waypoint in geodata.waypoints().waypoints().iter() {
-                    Self::write_waypoint(writer, &waypoint, "wpt", true).expect("write wpt failed");
+                    match geodata.group_name(waypoint.group()) {
+                        Some(group) if waypoint.kind().is_empty() => {
+                            let waypoint = waypoint.clone().with_kind(group);
+                            Self::write_waypoint(writer, &waypoint, "wpt")
+                        }
+                        _ => Self::write_waypoint(writer, waypoint, "wpt"),
+                    }
+                    .expect("write wpt failed");
+                }
+                // Extracted bitmaps become waypoints linking to the image
+                for bitmap in geodata.bitmaps().iter() {
+                    if bitmap.filename().is_empty() {
+                        continue;
+                    }
+                    let name = if bitmap.name().is_empty() {
+                        bitmap.filename()
+                    } else {
+                        bitmap.name()
+                    };
+                    let mut waypoint = bitmap
+                        .position()
+                        .clone()
+                        .with_link(Link::new(bitmap.filename(), name));
+                    waypoint.set_name(name);
+                    Self::write_waypoint(writer, &waypoint, "wpt").expect("write wpt failed");
                 }
                 for route in geodata.routes().iter() {
                     writer.create_element("rte").write_inner_content(|writer| {
-                        if !route.name().is_empty() {
-                            writer
-                                .create_element("name")
-                                .write_text_content(BytesText::new(route.name().as_str()))?;
+                        gpx_write_text(writer, "name", &route.name())?;
+                        let comment = match geodata.group_name(route.group()) {
+                            Some(group) if route.comment().is_empty() => group,
+                            _ => route.comment(),
+                        };
+                        gpx_write_text(writer, "cmt", comment)?;
+                        gpx_write_text(writer, "desc", route.description())?;
+                        if let Some(link) = route.links().first() {
+                            gpx_write_text(writer, "url", link.href())?;
+                            gpx_write_text(writer, "urlname", link.text())?;
                         }
                         for waypoint in route.waypoints().iter() {
-                            Self::write_waypoint(writer, &waypoint, "rtept", false)
+                            Self::write_waypoint(writer, &waypoint, "rtept")
                                 .expect("write rtept failed");
                         }
                         Ok(())
@@ -244,27 +438,35 @@ impl Format for GpxFormat {
                 }
                 for track in geodata.tracks().iter() {
                     writer.create_element("trk").write_inner_content(|writer| {
-                        if !track.name().is_empty() {
+                        gpx_write_text(writer, "name", &track.name())?;
+                        let comment = match geodata.group_name(track.group()) {
+                            Some(group) if track.comment().is_empty() => group,
+                            _ => track.comment(),
+                        };
+                        gpx_write_text(writer, "cmt", comment)?;
+                        gpx_write_text(writer, "desc", track.description())?;
+                        if let Some(link) = track.links().first() {
+                            gpx_write_text(writer, "url", link.href())?;
+                            gpx_write_text(writer, "urlname", link.text())?;
+                        }
+                        for segment in track.segments() {
                             writer
-                                .create_element("name")
-                                .write_text_content(BytesText::new(track.name().as_str()))?;
+                                .create_element("trkseg")
+                                .write_inner_content(|writer| {
+                                    for waypoint in segment.iter() {
+                                        Self::write_waypoint(writer, &waypoint, "trkpt")
+                                            .expect("write trkpt failed");
+                                    }
+                                    Ok(())
+                                })?;
                         }
-                        writer
-                            .create_element("trkseg")
-                            .write_inner_content(|writer| {
-                                for waypoint in track.waypoints().iter() {
-                                    Self::write_waypoint(writer, &waypoint, "trkpt", false)
-                                        .expect("write trkpt failed");
-                                }
-                                Ok(())
-                            })?;
                         Ok(())
                     })?;
                 }
                 Ok(())
             })?;
-        let output = std::str::from_utf8(&buffer)?;
-        Ok(output.to_string() + "\n")
+        buffer.push(b'\n');
+        Ok(buffer)
     }
     fn name<'a>(&self) -> &'a str {
         return "gpx";
@@ -307,45 +509,47 @@ impl GpxFormat {
         writer: &mut Writer<&mut Vec<u8>>,
         waypoint: &Waypoint,
         element: &str,
-        cmt_desc: bool,
     ) -> Result<(), Box<dyn Error>> {
-        if waypoint.name().is_empty() && waypoint.elevation().is_nan() {
-            writer
-                .create_element(element)
-                .with_attribute(("lat", format!("{:.9}", waypoint.latitude()).as_str()))
-                .with_attribute(("lon", format!("{:.9}", waypoint.longitude()).as_str()))
-                .write_empty()?;
-            Ok(())
-        } else {
-            writer
-                .create_element(element)
-                .with_attribute(("lat", format!("{:.9}", waypoint.latitude()).as_str()))
-                .with_attribute(("lon", format!("{:.9}", waypoint.longitude()).as_str()))
-                .write_inner_content(|writer| {
-                    if !waypoint.elevation().is_nan() {
-                        writer
-                            .create_element("ele")
-                            .write_text_content(BytesText::new(&format!(
-                                "{:.9}",
-                                waypoint.elevation()
-                            )))?;
-                    }
-                    if !waypoint.name().is_empty() {
-                        writer
-                            .create_element("name")
-                            .write_text_content(BytesText::new(&waypoint.name()))?;
-                        if cmt_desc {
-                            writer
-                                .create_element("cmt")
-                                .write_text_content(BytesText::new(&waypoint.name()))?;
-                            writer
-                                .create_element("desc")
-                                .write_text_content(BytesText::new(&waypoint.name()))?;
-                        }
-                    }
-                    Ok(())
-                })?;
-            Ok(())
+        let element = writer
+            .create_element(element)
+            .with_attribute(("lat", format!("{:.9}", waypoint.latitude()).as_str()))
+            .with_attribute(("lon", format!("{:.9}", waypoint.longitude()).as_str()));
+        if waypoint.name().is_empty()
+            && waypoint.comment().is_empty()
+            && waypoint.description().is_empty()
+            && waypoint.symbol().is_empty()
+            && waypoint.kind().is_empty()
+            && waypoint.links().is_empty()
+            && waypoint.elevation().is_nan()
+            && waypoint.time().is_none()
+        {
+            element.write_empty()?;
+            return Ok(());
         }
+        // Child elements in the order required by the GPX 1.0 schema
+        element.write_inner_content(|writer| {
+            if !waypoint.elevation().is_nan() {
+                gpx_write_text(writer, "ele", &format!("{:.9}", waypoint.elevation()))?;
+            }
+            if let Some(time) = waypoint.time() {
+                gpx_write_text(
+                    writer,
+                    "time",
+                    &time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                )?;
+            }
+            gpx_write_text(writer, "name", &waypoint.name())?;
+            gpx_write_text(writer, "cmt", waypoint.comment())?;
+            gpx_write_text(writer, "desc", waypoint.description())?;
+            // GPX 1.0 allows a single url per waypoint
+            if let Some(link) = waypoint.links().first() {
+                gpx_write_text(writer, "url", link.href())?;
+                gpx_write_text(writer, "urlname", link.text())?;
+            }
+            gpx_write_text(writer, "sym", waypoint.symbol())?;
+            gpx_write_text(writer, "type", waypoint.kind())?;
+            Ok(())
+        })?;
+        Ok(())
     }
 }
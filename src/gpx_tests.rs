@@ -0,0 +1,166 @@
+///
+///  gpx test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::gpx::*;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="Recorder" xmlns="http://www.topografix.com/GPX/1/1"
+     xmlns:gpxx="http://www.garmin.com/xmlschemas/GpxExtensions/v3">
+  <metadata>
+    <name>Survey</name>
+    <time>2024-05-01T08:00:00Z</time>
+  </metadata>
+  <wpt lat="50.0" lon="10.0">
+    <time>2024-05-01T08:15:30+02:00</time>
+    <name>Well</name>
+    <cmt>dry</cmt>
+    <desc>old well</desc>
+    <link href="https://example.org/well"><text>photo</text></link>
+    <sym>Water Source</sym>
+    <type>poi</type>
+  </wpt>
+  <rte>
+    <extensions>
+      <gpxx:RouteExtension><gpxx:DisplayColor>DarkBlue</gpxx:DisplayColor></gpxx:RouteExtension>
+    </extensions>
+    <rtept lat="50.0" lon="10.0"/>
+  </rte>
+  <trk>
+    <name>Walk</name>
+    <desc>along the river</desc>
+    <extensions>
+      <line xmlns="http://www.topografix.com/GPX/gpx_style/0/2">
+        <color>00FF80</color>
+        <width>3</width>
+      </line>
+    </extensions>
+    <trkseg>
+      <trkpt lat="50.0" lon="10.0"/>
+      <trkpt lat="50.1" lon="10.0"/>
+    </trkseg>
+    <trkseg>
+      <trkpt lat="50.2" lon="10.0"/>
+    </trkseg>
+  </trk>
+</gpx>
+"#;
+
+    #[test]
+    fn test_read_fields() {
+        let geodata = GpxFormat::new().read(GPX.as_bytes()).unwrap();
+        assert_eq!(geodata.metadata().name(), "Survey");
+        assert_eq!(geodata.metadata().generator(), "Recorder");
+
+        let waypoint = &geodata.waypoints().waypoints()[0];
+        assert_eq!(waypoint.name(), "Well");
+        assert_eq!(waypoint.comment(), "dry");
+        assert_eq!(waypoint.description(), "old well");
+        assert_eq!(waypoint.symbol(), "Water Source");
+        assert_eq!(waypoint.kind(), "poi");
+        assert_eq!(
+            waypoint.time().unwrap().to_rfc3339(),
+            "2024-05-01T06:15:30+00:00"
+        );
+        assert_eq!(
+            waypoint.links(),
+            &vec![Link::new("https://example.org/well", "photo")]
+        );
+
+        let route = &geodata.routes()[0];
+        assert_eq!(route.style().color(), Some(Color::new(0, 0, 139)));
+
+        let track = &geodata.tracks()[0];
+        assert_eq!(track.style().color(), Some(Color::new(0, 255, 128)));
+        assert_eq!(track.style().width(), Some(3));
+        let segments = track.segments();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].len(), 2);
+        assert_eq!(segments[1].len(), 1);
+    }
+
+    #[test]
+    fn test_text_fields() {
+        let format = GpxFormat::new();
+        let geodata = format.read(GPX.as_bytes()).unwrap();
+        let written = format.write(&geodata).unwrap();
+        let reread = format.read(&written).unwrap();
+
+        let waypoint = &reread.waypoints().waypoints()[0];
+        assert_eq!(waypoint.name(), "Well");
+        assert_eq!(waypoint.comment(), "dry");
+        assert_eq!(waypoint.description(), "old well");
+        assert_eq!(waypoint.symbol(), "Water Source");
+        assert_eq!(waypoint.links()[0].href(), "https://example.org/well");
+
+        let track = &reread.tracks()[0];
+        assert_eq!(track.name(), "Walk");
+        assert_eq!(track.comment(), "");
+        assert_eq!(track.description(), "along the river");
+
+        // Unnamed points stay unnamed
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.contains(r#"<rtept lat="50.000000000" lon="10.000000000"/>"#));
+    }
+
+    #[test]
+    fn test_group_names() {
+        let mut geodata = Geodata::new();
+        geodata.add_group(1, "Wege");
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.0)
+                .with_name("Hut")
+                .with_group(1),
+        );
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        track.set_group(1);
+        geodata.add_track(track);
+        let written = GpxFormat::new().write(&geodata).unwrap();
+        let reread = GpxFormat::new().read(&written).unwrap();
+        assert_eq!(reread.waypoints().waypoints()[0].kind(), "Wege");
+        assert_eq!(reread.tracks()[0].comment(), "Wege");
+    }
+
+    #[test]
+    fn test_segments() {
+        let format = GpxFormat::new();
+        let mut geodata = format.read(GPX.as_bytes()).unwrap();
+        let written = format.write(&geodata).unwrap();
+        let reread = format.read(&written).unwrap();
+        assert_eq!(reread.tracks()[0].segments().len(), 2);
+        assert_eq!(
+            reread.waypoints().waypoints()[0].time(),
+            geodata.waypoints().waypoints()[0].time()
+        );
+
+        geodata.split_segments();
+        assert_eq!(geodata.tracks().len(), 2);
+        assert_eq!(geodata.tracks()[1].name(), "Walk");
+        geodata.join_segments();
+        assert_eq!(geodata.tracks()[0].segments().len(), 1);
+    }
+}
@@ -0,0 +1,575 @@
+///
+///  Support for Google Earth KML writing
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::fmt::Write as _;
+use std::io;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::writer::Writer;
+
+use crate::format::Format;
+use crate::geodata::Bitmap;
+use crate::geodata::Color;
+use crate::geodata::Geodata;
+use crate::geodata::Label;
+use crate::geodata::Metadata;
+use crate::geodata::Style;
+use crate::geodata::Waypoint;
+use crate::geodata::WaypointList;
+use crate::geodata::EARTH_RADIUS;
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+const KML_NAMESPACE: &str = "http://www.opengis.net/kml/2.2";
+/// Google extensions, used for tracks with times
+const KML_GX_NAMESPACE: &str = "http://www.google.com/kml/ext/2.2";
+const KML_DEFAULT_COLOR: Color = Color::new(0, 0, 255);
+/// Scale of the Top50 maps, used when the overlay has no map scale
+const KML_DEFAULT_SCALE: f64 = 50000.0;
+/// Height of the map window in meters, turns map scale and zoom into
+/// a camera distance
+const KML_WINDOW_HEIGHT: f64 = 0.2;
+/// Opacity of polygon fills
+const KML_FILL_ALPHA: u8 = 0x80;
+/// Size of a screen pixel in meters (96 dpi), turns the pixel size of
+/// bitmaps into a ground size at the map scale
+const KML_PIXEL_SIZE: f64 = 0.0254 / 96.0;
+/// Label size that is shown at scale 1
+const KML_LABEL_SIZE: f64 = 20.0;
+
+/// KML colors are written as aabbggrr
+fn kml_color(color: Color, alpha: u8) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}",
+        alpha,
+        color.blue(),
+        color.green(),
+        color.red()
+    )
+}
+
+fn kml_coordinates(points: &[Waypoint]) -> String {
+    let mut result = String::new();
+    for point in points {
+        if !result.is_empty() {
+            result.push(' ');
+        }
+        let _ = write!(result, "{:.9},{:.9}", point.longitude(), point.latitude());
+        if !point.elevation().is_nan() {
+            let _ = write!(result, ",{:.1}", point.elevation());
+        }
+    }
+    result
+}
+
+fn kml_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Write a text element, empty values are skipped
+fn kml_write_text(writer: &mut Writer<&mut Vec<u8>>, tag: &str, text: &str) -> io::Result<()> {
+    if !text.is_empty() {
+        writer
+            .create_element(tag)
+            .write_text_content(BytesText::new(text))?;
+    }
+    Ok(())
+}
+
+/// Camera looking down on the centre of the map view. The distance
+/// follows from the map scale and zoom factor of the overlay.
+fn kml_write_look_at(writer: &mut Writer<&mut Vec<u8>>, metadata: &Metadata) -> io::Result<()> {
+    let Some(center) = metadata.center() else {
+        return Ok(());
+    };
+    let scale = metadata.scale().map_or(KML_DEFAULT_SCALE, f64::from);
+    let zoom = metadata.zoom().filter(|z| *z > 0.0).unwrap_or(100.0);
+    let range = scale * KML_WINDOW_HEIGHT * 100.0 / zoom;
+    if get_debug() >= 2 {
+        eprintln!(
+            "kml: look at {:.6} {:.6} range {:.0}m",
+            center.latitude(),
+            center.longitude(),
+            range
+        );
+    }
+    writer
+        .create_element("LookAt")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "longitude", &format!("{:.9}", center.longitude()))?;
+            kml_write_text(writer, "latitude", &format!("{:.9}", center.latitude()))?;
+            kml_write_text(writer, "altitude", "0")?;
+            kml_write_text(writer, "heading", "0")?;
+            kml_write_text(writer, "tilt", "0")?;
+            kml_write_text(writer, "range", &format!("{:.0}", range))?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Labels hide the icon and scale the text relative to the default
+/// size. Font, angle and background have no KML equivalent.
+fn kml_write_label_style(
+    writer: &mut Writer<&mut Vec<u8>>,
+    label: &Label,
+    color: Color,
+) -> io::Result<()> {
+    writer
+        .create_element("IconStyle")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "scale", "0")?;
+            Ok(())
+        })?;
+    writer
+        .create_element("LabelStyle")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "color", &kml_color(color, 0xff))?;
+            let scale = label.size() as f64 / KML_LABEL_SIZE;
+            kml_write_text(writer, "scale", &format!("{:.2}", scale))?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn kml_write_style(
+    writer: &mut Writer<&mut Vec<u8>>,
+    style: &Style,
+    point: bool,
+    area: bool,
+    label: Option<&Label>,
+) -> io::Result<()> {
+    let color = style.color().unwrap_or(KML_DEFAULT_COLOR);
+    writer
+        .create_element("Style")
+        .write_inner_content(|writer| {
+            if let Some(label) = label {
+                kml_write_label_style(writer, label, color)?;
+                return Ok(());
+            }
+            if point {
+                writer
+                    .create_element("IconStyle")
+                    .write_inner_content(|writer| {
+                        kml_write_text(writer, "color", &kml_color(color, 0xff))?;
+                        Ok(())
+                    })?;
+                return Ok(());
+            }
+            writer
+                .create_element("LineStyle")
+                .write_inner_content(|writer| {
+                    kml_write_text(writer, "color", &kml_color(color, 0xff))?;
+                    if let Some(width) = style.width() {
+                        kml_write_text(writer, "width", &width.to_string())?;
+                    }
+                    Ok(())
+                })?;
+            if area {
+                writer
+                    .create_element("PolyStyle")
+                    .write_inner_content(|writer| {
+                        kml_write_text(writer, "color", &kml_color(color, KML_FILL_ALPHA))?;
+                        Ok(())
+                    })?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn kml_write_line(writer: &mut Writer<&mut Vec<u8>>, points: &[Waypoint]) -> io::Result<()> {
+    writer
+        .create_element("LineString")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "tessellate", "1")?;
+            kml_write_text(writer, "coordinates", &kml_coordinates(points))?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn kml_write_polygon(writer: &mut Writer<&mut Vec<u8>>, points: &[Waypoint]) -> io::Result<()> {
+    // Linear rings are closed by repeating the first point
+    let mut ring = points.to_vec();
+    let first = &points[0];
+    let last = &points[points.len() - 1];
+    if first.latitude() != last.latitude() || first.longitude() != last.longitude() {
+        ring.push(first.clone());
+    }
+    writer
+        .create_element("Polygon")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "tessellate", "1")?;
+            writer
+                .create_element("outerBoundaryIs")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("LinearRing")
+                        .write_inner_content(|writer| {
+                            kml_write_text(writer, "coordinates", &kml_coordinates(&ring))?;
+                            Ok(())
+                        })?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Track with a time for every point. Points without elevation are
+/// put on the ground.
+fn kml_write_track(
+    writer: &mut Writer<&mut Vec<u8>>,
+    points: &[Waypoint],
+    times: &[DateTime<Utc>],
+) -> io::Result<()> {
+    writer
+        .create_element("gx:Track")
+        .write_inner_content(|writer| {
+            for time in times {
+                kml_write_text(writer, "when", &kml_time(time))?;
+            }
+            for point in points {
+                let elevation = if point.elevation().is_nan() {
+                    0.0
+                } else {
+                    point.elevation()
+                };
+                let coord = format!(
+                    "{:.9} {:.9} {:.1}",
+                    point.longitude(),
+                    point.latitude(),
+                    elevation
+                );
+                kml_write_text(writer, "gx:coord", &coord)?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn kml_write_waypoint(writer: &mut Writer<&mut Vec<u8>>, waypoint: &Waypoint) -> io::Result<()> {
+    writer
+        .create_element("Placemark")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "name", &waypoint.name())?;
+            if waypoint.description().is_empty() {
+                kml_write_text(writer, "description", waypoint.comment())?;
+            } else {
+                kml_write_text(writer, "description", waypoint.description())?;
+            }
+            if let Some(time) = waypoint.time() {
+                writer
+                    .create_element("TimeStamp")
+                    .write_inner_content(|writer| {
+                        kml_write_text(writer, "when", &kml_time(&time))?;
+                        Ok(())
+                    })?;
+            }
+            kml_write_style(writer, waypoint.style(), true, false, waypoint.label())?;
+            writer
+                .create_element("Point")
+                .write_inner_content(|writer| {
+                    kml_write_text(
+                        writer,
+                        "coordinates",
+                        &kml_coordinates(std::slice::from_ref(waypoint)),
+                    )?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn kml_write_list(writer: &mut Writer<&mut Vec<u8>>, list: &WaypointList) -> io::Result<()> {
+    let area = list.area() && list.len() > 2;
+    if get_debug() >= 2 {
+        eprintln!(
+            "kml: placemark (len: {:3}, name: \"{}\", area: {})",
+            list.len(),
+            list.name(),
+            area
+        );
+    }
+    writer
+        .create_element("Placemark")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "name", &list.name())?;
+            if list.description().is_empty() {
+                kml_write_text(writer, "description", list.comment())?;
+            } else {
+                kml_write_text(writer, "description", list.description())?;
+            }
+            kml_write_style(writer, list.style(), false, area, None)?;
+            let segments = list.segments();
+            // Lines with a time on every point become tracks, one per
+            // segment
+            let times = if area { None } else { list.times() };
+            if let Some(times) = times {
+                let mut start = 0;
+                let mut tracks = Vec::new();
+                for segment in segments.iter().filter(|s| !s.is_empty()) {
+                    tracks.push((*segment, &times[start..start + segment.len()]));
+                    start += segment.len();
+                }
+                if tracks.len() > 1 {
+                    writer
+                        .create_element("gx:MultiTrack")
+                        .write_inner_content(|writer| {
+                            for (points, times) in tracks.iter() {
+                                kml_write_track(writer, points, times)?;
+                            }
+                            Ok(())
+                        })?;
+                } else {
+                    kml_write_track(writer, list.waypoints(), &times)?;
+                }
+            } else if area {
+                kml_write_polygon(writer, list.waypoints())?;
+            } else if segments.len() > 1 {
+                writer
+                    .create_element("MultiGeometry")
+                    .write_inner_content(|writer| {
+                        for segment in segments.iter().filter(|s| !s.is_empty()) {
+                            kml_write_line(writer, segment)?;
+                        }
+                        Ok(())
+                    })?;
+            } else {
+                kml_write_line(writer, list.waypoints())?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Bitmaps keep their screen size at the zoom of the map view, the
+/// ground box around the centre follows from the map scale
+fn kml_write_ground_overlay(
+    writer: &mut Writer<&mut Vec<u8>>,
+    bitmap: &Bitmap,
+    metadata: &Metadata,
+) -> io::Result<()> {
+    let scale = metadata.scale().map_or(KML_DEFAULT_SCALE, f64::from);
+    let zoom = metadata.zoom().filter(|z| *z > 0.0).unwrap_or(100.0);
+    let pixel = scale * KML_PIXEL_SIZE * 100.0 / zoom;
+    let center = bitmap.position();
+    let lat = (bitmap.height() as f64 * pixel / 2.0 / EARTH_RADIUS).to_degrees();
+    let lon = lat * bitmap.width() as f64
+        / bitmap.height().max(1) as f64
+        / center.latitude().to_radians().cos();
+    if get_debug() >= 2 {
+        eprintln!(
+            "kml: ground overlay \"{}\" ({}x{} px, {:.1}m/px)",
+            bitmap.filename(),
+            bitmap.width(),
+            bitmap.height(),
+            pixel
+        );
+    }
+    writer
+        .create_element("GroundOverlay")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "name", bitmap.name())?;
+            writer
+                .create_element("Icon")
+                .write_inner_content(|writer| {
+                    kml_write_text(writer, "href", bitmap.filename())?;
+                    Ok(())
+                })?;
+            writer
+                .create_element("LatLonBox")
+                .write_inner_content(|writer| {
+                    let value = |v: f64| format!("{:.9}", v);
+                    kml_write_text(writer, "north", &value(center.latitude() + lat))?;
+                    kml_write_text(writer, "south", &value(center.latitude() - lat))?;
+                    kml_write_text(writer, "east", &value(center.longitude() + lon))?;
+                    kml_write_text(writer, "west", &value(center.longitude() - lon))?;
+                    if bitmap.angle() != 0 {
+                        kml_write_text(writer, "rotation", &bitmap.angle().to_string())?;
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Folder of tracks or routes with a sub folder for every named group
+fn kml_write_folder(
+    writer: &mut Writer<&mut Vec<u8>>,
+    name: &str,
+    lists: &[WaypointList],
+    geodata: &Geodata,
+) -> io::Result<()> {
+    if lists.iter().all(|l| l.len() == 0) {
+        return Ok(());
+    }
+    let lists: Vec<&WaypointList> = lists.iter().filter(|l| l.len() > 0).collect();
+    writer
+        .create_element("Folder")
+        .write_inner_content(|writer| {
+            kml_write_text(writer, "name", name)?;
+            for (group, group_name) in geodata.groups().iter() {
+                let members: Vec<&&WaypointList> =
+                    lists.iter().filter(|l| l.group() == Some(*group)).collect();
+                if members.is_empty() {
+                    continue;
+                }
+                writer
+                    .create_element("Folder")
+                    .write_inner_content(|writer| {
+                        kml_write_text(writer, "name", group_name)?;
+                        for list in members {
+                            kml_write_list(writer, list)?;
+                        }
+                        Ok(())
+                    })?;
+            }
+            for list in lists
+                .iter()
+                .filter(|l| geodata.group_name(l.group()).is_none())
+            {
+                kml_write_list(writer, list)?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct KmlFormat {
+    debug: u8,
+}
+
+impl Format for KmlFormat {
+    fn probe(&self, _buf: &[u8]) -> bool {
+        false
+    }
+    fn read(&self, _buf: &[u8]) -> Result<Geodata> {
+        Err(anyhow!("kml read not supported"))
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 2);
+        let metadata = geodata.metadata();
+        let name = if metadata.name().is_empty() {
+            metadata.map_name()
+        } else {
+            metadata.name()
+        };
+        if self.debug >= 1 {
+            eprintln!("kml: writing document \"{}\"", name);
+        }
+
+        writer
+            .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+            .expect("writing decl");
+        writer
+            .create_element("kml")
+            .with_attribute(("xmlns", KML_NAMESPACE))
+            .with_attribute(("xmlns:gx", KML_GX_NAMESPACE))
+            .write_inner_content(|writer| {
+                writer
+                    .create_element("Document")
+                    .write_inner_content(|writer| {
+                        kml_write_text(writer, "name", name)?;
+                        kml_write_text(writer, "description", metadata.description())?;
+                        kml_write_look_at(writer, metadata)?;
+                        let waypoints = geodata.waypoints().waypoints();
+                        if !waypoints.is_empty() {
+                            writer
+                                .create_element("Folder")
+                                .write_inner_content(|writer| {
+                                    kml_write_text(writer, "name", "Waypoints")?;
+                                    for waypoint in waypoints.iter() {
+                                        kml_write_waypoint(writer, waypoint)?;
+                                    }
+                                    Ok(())
+                                })?;
+                        }
+                        // Ground overlays need the image extracted to a file
+                        let bitmaps: Vec<&Bitmap> = geodata
+                            .bitmaps()
+                            .iter()
+                            .filter(|b| !b.filename().is_empty())
+                            .collect();
+                        if !bitmaps.is_empty() {
+                            writer
+                                .create_element("Folder")
+                                .write_inner_content(|writer| {
+                                    kml_write_text(writer, "name", "Bitmaps")?;
+                                    for bitmap in bitmaps.iter() {
+                                        kml_write_ground_overlay(writer, bitmap, metadata)?;
+                                    }
+                                    Ok(())
+                                })?;
+                        }
+                        kml_write_folder(writer, "Tracks", geodata.tracks(), geodata)?;
+                        kml_write_folder(writer, "Routes", geodata.routes(), geodata)?;
+                        Ok(())
+                    })?;
+                Ok(())
+            })?;
+        buffer.push(b'\n');
+        Ok(buffer)
+    }
+    fn name<'a>(&self) -> &'a str {
+        "kml"
+    }
+    fn can_read(&self) -> bool {
+        false
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+}
+
+impl Default for KmlFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KmlFormat {
+    pub fn new() -> Self {
+        Self { debug: 0 }
+    }
+}
@@ -0,0 +1,147 @@
+///
+///  kml test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::kml::*;
+
+    #[test]
+    fn test_write_kml() {
+        let mut geodata = Geodata::new();
+        let mut metadata = Metadata::new();
+        metadata.set_map_name("Top. Karte 1:25000");
+        metadata.set_center(Waypoint::new().with_lat(48.5).with_lon(10.25));
+        metadata.set_scale(25000);
+        metadata.set_zoom(50.0);
+        geodata.set_metadata(metadata);
+        let mut area = WaypointList::new();
+        area.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        area.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(11.0));
+        area.add_waypoint(Waypoint::new().with_lat(51.0).with_lon(11.0));
+        area.set_name("Area");
+        area.set_area(true);
+        area.set_style(Style::new().with_color(Color::new(255, 0, 0)).with_width(3));
+        geodata.add_track(area);
+
+        let result = String::from_utf8(KmlFormat::new().write(&geodata).unwrap()).unwrap();
+        assert!(result.contains("<name>Top. Karte 1:25000</name>"));
+        assert!(result.contains("<latitude>48.500000000</latitude>"));
+        // 1:25000 at 50% zoom
+        assert!(result.contains("<range>10000</range>"));
+        assert!(result.contains("<color>ff0000ff</color>"));
+        assert!(result.contains("<color>800000ff</color>"));
+        assert!(result.contains(
+            "<coordinates>10.000000000,50.000000000 11.000000000,50.000000000 \
+             11.000000000,51.000000000 10.000000000,50.000000000</coordinates>"
+        ));
+    }
+
+    #[test]
+    fn test_write_groups() {
+        let mut geodata = Geodata::new();
+        geodata.add_group(2, "Wege");
+        for (name, group) in [("eins", 2), ("zwei", 3)] {
+            let mut track = WaypointList::new();
+            track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+            track.set_name(name);
+            track.set_group(group);
+            geodata.add_track(track);
+        }
+        let result = String::from_utf8(KmlFormat::new().write(&geodata).unwrap()).unwrap();
+        let wege = result.find("<name>Wege</name>").unwrap();
+        let eins = result.find("<name>eins</name>").unwrap();
+        let zwei = result.find("<name>zwei</name>").unwrap();
+        assert!(wege < eins && eins < zwei);
+        assert_eq!(result.matches("<Folder>").count(), 2);
+    }
+
+    #[test]
+    fn test_write_times() {
+        let start = chrono::DateTime::parse_from_rfc3339("2024-05-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let mut geodata = Geodata::new();
+        let mut track = WaypointList::new();
+        for (i, lon) in [10.0, 10.1, 10.2].iter().enumerate() {
+            if i == 2 {
+                track.start_segment();
+            }
+            track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(*lon));
+        }
+        geodata.add_track(track);
+        geodata.synthesize_times(start, 10.0);
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.0)
+                .with_time(start),
+        );
+        let result = String::from_utf8(KmlFormat::new().write(&geodata).unwrap()).unwrap();
+        assert!(result.contains("xmlns:gx=\"http://www.google.com/kml/ext/2.2\""));
+        assert!(result.contains("<TimeStamp>\n          <when>2024-05-01T08:00:00Z</when>"));
+        assert_eq!(result.matches("<gx:MultiTrack>").count(), 1);
+        assert_eq!(result.matches("<gx:Track>").count(), 2);
+        assert_eq!(result.matches("<gx:coord>").count(), 3);
+        assert!(result.contains("<gx:coord>10.200000000 50.000000000 0.0</gx:coord>"));
+        assert!(!result.contains("<LineString>"));
+    }
+
+    #[test]
+    fn test_write_label() {
+        let mut geodata = Geodata::new();
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.0)
+                .with_name("Gipfel")
+                .with_style(Style::new().with_color(Color::new(0, 0, 255)))
+                .with_label(Label::new(LabelFont::Arial, 30, 0)),
+        );
+        let result = String::from_utf8(KmlFormat::new().write(&geodata).unwrap()).unwrap();
+        assert!(result.contains("<IconStyle>\n            <scale>0</scale>"));
+        assert!(result.contains(
+            "<LabelStyle>\n            <color>ffff0000</color>\n            <scale>1.50</scale>"
+        ));
+    }
+
+    #[test]
+    fn test_write_ground_overlay() {
+        let mut geodata = Geodata::new();
+        let position = Waypoint::new().with_lat(0.0).with_lon(10.0);
+        geodata.add_bitmap(
+            Bitmap::new("bmp", vec![0; 4], position.clone())
+                .with_size(96, 96)
+                .with_angle(45),
+        );
+        geodata.add_bitmap(Bitmap::new("bmp", vec![0; 4], position));
+        geodata.bitmaps_mut()[0].set_filename("image-001.bmp");
+
+        let result = String::from_utf8(KmlFormat::new().write(&geodata).unwrap()).unwrap();
+        assert_eq!(result.matches("<GroundOverlay>").count(), 1);
+        assert!(result.contains("<href>image-001.bmp</href>"));
+        // 48 pixels of 13.2m at 1:50000
+        assert!(result.contains("<north>0.00571"));
+        assert!(result.contains("<east>10.00571"));
+        assert!(result.contains("<rotation>45</rotation>"));
+    }
+}
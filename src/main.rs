@@ -22,20 +22,54 @@ use std::io;
 use std::io::Read;
 use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::{anyhow, Context, Result};
-use clap::{Arg, Command};
+use chrono::{DateTime, Utc};
+use clap::{Arg, ArgAction, Command};
 
+use crate::dem::{dem_set_debug, Dem};
+use crate::dib::{dib_decode_bmp, dib_png};
+
+mod dem;
+mod dem_tests;
+mod dib;
+mod dib_tests;
 mod error;
+mod fit;
+mod fit_tests;
 mod format;
 mod geodata;
+mod geodata_tests;
 mod ggv_bin;
+mod ggv_bin_tests;
 mod ggv_ovl;
 mod ggv_ovl_tests;
 mod ggv_xml;
+mod ggv_xml_tests;
+mod gpkg;
+mod gpkg_tests;
 mod gpx;
+mod gpx_tests;
+mod kml;
+mod kml_tests;
+mod osm;
+mod osm_tests;
+mod ozi;
+mod ozi_tests;
+mod png;
+mod png_tests;
+mod svg;
+mod svg_tests;
+mod tcx;
+mod tcx_tests;
+mod wkt;
+mod wkt_tests;
 
-pub use crate::{error::*, format::*, geodata::*, ggv_bin::*, ggv_ovl::*, ggv_xml::*, gpx::*};
+pub use crate::{
+    error::*, fit::*, format::*, geodata::*, ggv_bin::*, ggv_ovl::*, ggv_xml::*, gpkg::*, gpx::*,
+    kml::*, osm::*, ozi::*, png::*, svg::*, tcx::*, wkt::*,
+};
 
 fn read_stdin() -> Result<Vec<u8>> {
     let mut buffer = Vec::new();
@@ -55,27 +89,170 @@ fn read_file(filename: &String) -> Result<Vec<u8>> {
     return Ok(buffer);
 }
 
-fn write_stdout(data: &String) -> Result<()> {
+fn write_stdout(data: &[u8]) -> Result<()> {
     io::stdout()
-        .write_all(data.as_bytes())
+        .write_all(data)
         .with_context(|| "couldn't write stdout")?;
     Ok(())
 }
 
-fn write_file(data: &String, filename: &String) -> Result<()> {
+fn write_file(data: &[u8], filename: &str) -> Result<()> {
     let mut out = std::fs::File::create(filename)
         .with_context(|| format!("failed to open file for writin: {}", filename))?;
-    out.write_all(data.as_bytes())
+    out.write_all(data)
         .with_context(|| format!("filed writing to file: {}", filename))?;
     Ok(())
 }
 
+fn find_informat<'a>(
+    formats: &'a [Box<dyn Format>],
+    intype: Option<&String>,
+    indata: &[u8],
+) -> Result<&'a dyn Format> {
+    let informat = match intype {
+        Some(intype) => formats.iter().find(|&f| f.name() == intype),
+        None => formats
+            .iter()
+            .filter(|f| f.can_read())
+            .find(|&f| f.probe(indata)),
+    };
+    match informat {
+        Some(f) => Ok(f.as_ref()),
+        None => Err(anyhow!("input format not given or detected.")),
+    }
+}
+
+/// Points per segment of smoothed rounded lines
+const SPLINE_STEPS: usize = 8;
+
+/// Transformations applied between reading and writing
+#[derive(Debug, Default)]
+struct Filters {
+    segments: Option<String>,
+    smooth: bool,
+    start_time: Option<DateTime<Utc>>,
+    speed: f64,
+    dem: Option<Dem>,
+    force_elevation: bool,
+    debug: u8,
+}
+
+fn apply_filters(geodata: &mut Geodata, filters: &Filters) {
+    match filters.segments.as_deref() {
+        Some("join") => geodata.join_segments(),
+        Some("split") => geodata.split_segments(),
+        _ => (),
+    }
+    if filters.smooth {
+        geodata.smooth_rounded(SPLINE_STEPS);
+    }
+    if let Some(dem) = &filters.dem {
+        geodata.fill_elevation(|lat, lon| dem.elevation(lat, lon), filters.force_elevation);
+        if filters.debug >= 1 {
+            for (kind, list) in geodata
+                .tracks()
+                .iter()
+                .map(|t| ("track", t))
+                .chain(geodata.routes().iter().map(|r| ("route", r)))
+            {
+                let (ascent, descent) = list.ascent_descent();
+                eprintln!(
+                    "main: {} \"{}\": ascent {:.0} m, descent {:.0} m",
+                    kind,
+                    list.name(),
+                    ascent,
+                    descent
+                );
+            }
+        }
+    }
+    if let Some(start_time) = filters.start_time {
+        geodata.synthesize_times(start_time, filters.speed);
+    }
+}
+
+/// Convert all files in indir and write the results to outdir using
+/// the same base name and the extension of the output format.
+fn convert_directory(
+    formats: &[Box<dyn Format>],
+    intype: Option<&String>,
+    outformat: &dyn Format,
+    indir: &Path,
+    outdir: &Path,
+    filters: &Filters,
+    debuglevel: u8,
+) -> Result<()> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(indir)
+        .with_context(|| format!("couldn't read directory: {}", indir.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect();
+    entries.sort();
+    std::fs::create_dir_all(outdir)
+        .with_context(|| format!("couldn't create directory: {}", outdir.display()))?;
+    let mut failed = 0;
+    for infile in entries.iter() {
+        let Some(stem) = infile.file_stem() else {
+            continue;
+        };
+        let mut outfile = outdir.join(stem);
+        outfile.set_extension(outformat.extension());
+        let result = read_file(&infile.to_string_lossy().into_owned())
+            .and_then(|indata| {
+                let informat = find_informat(formats, intype, &indata)?;
+                if debuglevel >= 1 {
+                    eprintln!(
+                        "main: using input format: {} ({})",
+                        informat.name(),
+                        infile.display()
+                    );
+                }
+                informat.read(&indata)
+            })
+            .and_then(|mut geodata| {
+                apply_filters(&mut geodata, filters);
+                outformat.write(&geodata)
+            })
+            .and_then(|outdata| write_file(&outdata, &outfile.to_string_lossy()));
+        match result {
+            Ok(_) => {
+                if debuglevel >= 1 {
+                    eprintln!(
+                        "main: writing {} to: {}",
+                        outformat.name(),
+                        outfile.display()
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("error: {}: {:#}", infile.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!("{} of {} files failed", failed, entries.len()));
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let mut formats: Vec<Box<dyn Format>> = vec![
         Box::new(GgvBinFormat::new()),
         Box::new(GgvOvlFormat::new()),
         Box::new(GgvXmlFormat::new()),
+        Box::new(GpkgFormat::new()),
         Box::new(GpxFormat::new()),
+        Box::new(KmlFormat::new()),
+        Box::new(OsmFormat::new()),
+        Box::new(OziFormat::new(OziKind::Track)),
+        Box::new(OziFormat::new(OziKind::Waypoint)),
+        Box::new(OziFormat::new(OziKind::Route)),
+        Box::new(FitFormat::new()),
+        Box::new(PngFormat::new()),
+        Box::new(SvgFormat::new()),
+        Box::new(TcxFormat::new()),
+        Box::new(WktFormat::new()),
     ];
     let read_format_names: Vec<&str> = formats
         .iter()
@@ -135,6 +312,54 @@ fn main() -> Result<()> {
                 .short('F')
                 .help("output <file>"),
         )
+        .arg(
+            Arg::new("outoption")
+                .value_name("option")
+                .short('O')
+                .action(ArgAction::Append)
+                .help("output <option> as key=value"),
+        )
+        .arg(
+            Arg::new("segments")
+                .value_name("mode")
+                .short('S')
+                .value_parser(["join", "split"])
+                .help("track segments <mode>"),
+        )
+        .arg(
+            Arg::new("smooth")
+                .short('R')
+                .action(ArgAction::SetTrue)
+                .help("smooth rounded lines with splines"),
+        )
+        .arg(
+            Arg::new("starttime")
+                .value_name("time")
+                .short('T')
+                .help("synthesize track times starting at <time>"),
+        )
+        .arg(
+            Arg::new("speed")
+                .value_name("km/h")
+                .short('s')
+                .value_parser(clap::value_parser!(f64))
+                .requires("starttime")
+                .help("<km/h> for synthesized track times (default 10)"),
+        )
+        .arg(
+            Arg::new("dem")
+                .value_name("path")
+                .short('E')
+                .action(ArgAction::Append)
+                .help("elevations from DEM <path> (.hgt directory or GeoTIFF)"),
+        )
+        .arg(
+            Arg::new("force_elevation")
+                .short('z')
+                .action(ArgAction::SetTrue)
+                .requires("dem")
+                .help("replace existing elevations with DEM values"),
+        )
         .arg(
             Arg::new("datafile")
                 .value_name("path")
@@ -145,10 +370,73 @@ fn main() -> Result<()> {
 
     let debuglevel = *matches.get_one::<u8>("debug").unwrap_or(&0);
     formats.iter_mut().for_each(|f| f.set_debug(debuglevel));
+    dem_set_debug(debuglevel);
+
+    let outtype = match matches.get_one::<String>("outtype") {
+        Some(outtype) => outtype.as_str(),
+        _ => "gpx",
+    };
+    let Some(outformat) = formats
+        .iter_mut()
+        .filter(|f| f.can_write())
+        .find(|f| f.name() == outtype)
+    else {
+        return Err(anyhow!("output format not given or detected."));
+    };
+    if let Some(options) = matches.get_many::<String>("outoption") {
+        for option in options {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            outformat.set_option(key, value)?;
+        }
+    }
+
+    let mut filters = Filters {
+        segments: matches.get_one::<String>("segments").cloned(),
+        smooth: matches.get_flag("smooth"),
+        start_time: None,
+        speed: *matches.get_one::<f64>("speed").unwrap_or(&10.0),
+        dem: None,
+        force_elevation: matches.get_flag("force_elevation"),
+        debug: debuglevel,
+    };
+    if let Some(paths) = matches.get_many::<String>("dem") {
+        filters.dem = Some(Dem::open(&paths.cloned().collect::<Vec<String>>())?);
+    }
+    if let Some(start_time) = matches.get_one::<String>("starttime") {
+        let start_time = DateTime::parse_from_rfc3339(start_time)
+            .with_context(|| format!("invalid start time: {}", start_time))?;
+        filters.start_time = Some(start_time.with_timezone(&Utc));
+    }
+    if filters.speed.is_nan() || filters.speed < MIN_SPEED {
+        return Err(anyhow!("invalid speed: {}", filters.speed));
+    }
 
     let infile = matches
         .get_one::<String>("infile")
         .or(matches.get_one::<String>("infile_p"));
+    let outfile = matches
+        .get_one::<String>("outfile")
+        .or(matches.get_one::<String>("outfile_p"));
+
+    if let Some(indir) = infile.map(Path::new).filter(|p| p.is_dir()) {
+        let Some(outdir) = outfile else {
+            return Err(anyhow!("output directory required for directory input."));
+        };
+        let outformat = formats
+            .iter()
+            .find(|f| f.can_write() && f.name() == outtype)
+            .expect("output format");
+        return convert_directory(
+            &formats,
+            matches.get_one::<String>("intype"),
+            outformat.as_ref(),
+            indir,
+            Path::new(outdir),
+            &filters,
+            debuglevel,
+        );
+    }
+
     let indata = match infile {
         Some(p) => {
             if p == "-" {
@@ -160,47 +448,60 @@ fn main() -> Result<()> {
         None => &read_stdin()?,
     };
 
-    let Some(informat) = (match matches.get_one::<String>("intype") {
-        Some(intype) => formats.iter().find(|&f| f.name() == intype),
-        None => formats
-            .iter()
-            .filter(|f| f.can_read())
-            .find(|&f| f.probe(indata)),
-    }) else {
-        return Err(anyhow!("input format not given or detected."));
-    };
+    let informat = find_informat(&formats, matches.get_one::<String>("intype"), indata)?;
     if debuglevel >= 1 {
         eprintln!("main: using input format: {}", informat.name());
     }
 
-    let geodata = informat.read(indata)?;
+    let mut geodata = informat.read(indata)?;
+    apply_filters(&mut geodata, &filters);
+    // Bitmaps get their file names before writing, so output formats
+    // can link to the extracted images. They are extracted as PNG when
+    // they can be decoded.
+    let datafile = matches.get_one::<String>("datafile");
+    let mut extracted: Vec<(String, Vec<u8>)> = Vec::new();
+    if let Some(datafile) = datafile {
+        for bitmap in geodata.bitmaps_mut().iter_mut() {
+            if bitmap.data().is_empty() {
+                continue;
+            }
+            let (kind, data) = match dib_decode_bmp(bitmap.data()).and_then(|i| dib_png(&i)) {
+                Ok(png) => ("png".to_string(), png),
+                Err(err) => {
+                    if debuglevel >= 1 {
+                        eprintln!("main: keeping {}: {}", bitmap.kind(), err);
+                    }
+                    (bitmap.kind().to_string(), bitmap.data().clone())
+                }
+            };
+            let filename = format!("{}-{:03}.{}", datafile, extracted.len() + 1, kind);
+            bitmap.set_filename(&filename);
+            extracted.push((filename, data));
+        }
+        for ele in geodata.data().iter() {
+            let filename = format!("{}-{:03}.{}", datafile, extracted.len() + 1, ele.kind());
+            extracted.push((filename, ele.data().clone()));
+        }
+    }
 
-    let Some(outformat) = (match matches.get_one::<String>("outtype") {
-        Some(outtype) => formats
-            .iter()
-            .filter(|&f| f.can_write())
-            .find(|&f| f.name() == outtype),
-        _ => formats.iter().find(|&f| f.name() == "gpx"),
-    }) else {
-        return Err(anyhow!("input format not given or detected."));
-    };
+    let outformat = formats
+        .iter()
+        .find(|f| f.can_write() && f.name() == outtype)
+        .expect("output format");
 
     let result = outformat.write(&geodata)?;
 
-    match matches
-        .get_one::<String>("outfile")
-        .or(matches.get_one::<String>("outfile_p"))
-    {
+    match outfile {
         Some(outfile) => {
             if outfile == "-" {
                 write_stdout(&result)?;
                 if debuglevel >= 1 {
-                    eprintln!("main: writing gpx to: stdout");
+                    eprintln!("main: writing {} to: stdout", outtype);
                 }
             } else {
                 write_file(&result, outfile)?;
                 if debuglevel >= 1 {
-                    eprintln!("main: writing gpx to: {}", outfile);
+                    eprintln!("main: writing {} to: {}", outtype, outfile);
                 }
             }
         }
@@ -215,24 +516,18 @@ fn main() -> Result<()> {
         }
     }
 
-    match matches.get_one::<String>("datafile") {
-        Some(datafile) => {
-            for (pos, ele) in geodata.data().iter().enumerate() {
-                let filename = format!("{}-{:03}.{}", datafile, pos + 1, ele.kind());
-                match std::fs::File::create(&filename) {
-                    Ok(mut out) => {
-                        let _ = out.write_all(ele.data());
-                        if debuglevel >= 1 {
-                            eprintln!("main: writing data to: \"{}\"", filename);
-                        }
-                    }
-                    _ => {
-                        eprintln!("error: writing data to: \"{}\"", filename);
-                    }
+    for (filename, data) in extracted.iter() {
+        match std::fs::File::create(filename) {
+            Ok(mut out) => {
+                let _ = out.write_all(data);
+                if debuglevel >= 1 {
+                    eprintln!("main: writing data to: \"{}\"", filename);
                 }
             }
+            _ => {
+                eprintln!("error: writing data to: \"{}\"", filename);
+            }
         }
-        _ => (),
     }
     Ok(())
 }
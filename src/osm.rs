@@ -0,0 +1,245 @@
+///
+///  Support for OpenStreetMap XML writing
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::io;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Result};
+use quick_xml::events::{BytesDecl, Event};
+use quick_xml::writer::Writer;
+
+use crate::format::Format;
+use crate::geodata::Geodata;
+use crate::geodata::Waypoint;
+use crate::geodata::WaypointList;
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+const OSM_DEFAULT_SOURCE: &str = "ggvtogpx";
+
+fn osm_write_tag(writer: &mut Writer<&mut Vec<u8>>, key: &str, value: &str) -> io::Result<()> {
+    writer
+        .create_element("tag")
+        .with_attribute(("k", key))
+        .with_attribute(("v", value))
+        .write_empty()?;
+    Ok(())
+}
+
+/// Write a node and return its id
+fn osm_write_node(
+    writer: &mut Writer<&mut Vec<u8>>,
+    id: &mut i64,
+    waypoint: &Waypoint,
+    tags: &[(&str, &str)],
+) -> io::Result<i64> {
+    *id -= 1;
+    let element = writer
+        .create_element("node")
+        .with_attribute(("id", id.to_string().as_str()))
+        .with_attribute(("lat", format!("{:.7}", waypoint.latitude()).as_str()))
+        .with_attribute(("lon", format!("{:.7}", waypoint.longitude()).as_str()));
+    if tags.is_empty() {
+        element.write_empty()?;
+    } else {
+        element.write_inner_content(|writer| {
+            for (key, value) in tags {
+                osm_write_tag(writer, key, value)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(*id)
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct OsmFormat {
+    debug: u8,
+    source: String,
+}
+
+impl Format for OsmFormat {
+    fn probe(&self, _buf: &[u8]) -> bool {
+        false
+    }
+    fn read(&self, _buf: &[u8]) -> Result<Geodata> {
+        Err(anyhow!("osm read not supported"))
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 2);
+
+        writer
+            .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+            .expect("writing decl");
+        writer
+            .create_element("osm")
+            .with_attribute(("version", "0.6"))
+            .with_attribute(("generator", "ggvtogpx"))
+            .with_attribute(("upload", "never"))
+            .write_inner_content(|writer| {
+                // Negative ids mark new objects, nodes and ways share
+                // one counter to keep ids unique within the file.
+                let mut id = 0;
+                for waypoint in geodata.waypoints().waypoints().iter() {
+                    let name = waypoint.name();
+                    let elevation = format!("{:.1}", waypoint.elevation());
+                    let mut tags = Vec::new();
+                    if !name.is_empty() {
+                        tags.push(("name", name.as_str()));
+                    }
+                    if !waypoint.description().is_empty() {
+                        tags.push(("description", waypoint.description()));
+                    }
+                    if !waypoint.comment().is_empty() {
+                        tags.push(("note", waypoint.comment()));
+                    }
+                    if !waypoint.elevation().is_nan() {
+                        tags.push(("ele", elevation.as_str()));
+                    }
+                    if !self.source.is_empty() {
+                        tags.push(("source", self.source.as_str()));
+                    }
+                    osm_write_node(writer, &mut id, waypoint, &tags)?;
+                }
+                let lists = geodata.tracks().iter().chain(geodata.routes().iter());
+                for list in lists.filter(|l| l.len() > 0) {
+                    self.write_way(writer, &mut id, list)?;
+                }
+                Ok(())
+            })?;
+        buffer.push(b'\n');
+        Ok(buffer)
+    }
+    fn name<'a>(&self) -> &'a str {
+        "osm"
+    }
+    fn can_read(&self) -> bool {
+        false
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+    fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "source" => self.source = value.to_string(),
+            _ => return Err(anyhow!("osm: unknown option: {}", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for OsmFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OsmFormat {
+    pub fn new() -> Self {
+        Self {
+            debug: 0,
+            source: OSM_DEFAULT_SOURCE.to_string(),
+        }
+    }
+
+    fn write_way(
+        &self,
+        writer: &mut Writer<&mut Vec<u8>>,
+        id: &mut i64,
+        list: &WaypointList,
+    ) -> io::Result<()> {
+        // Polygons become closed ways ending with the first node. A
+        // repeated first point is dropped to avoid a duplicate node.
+        let first = list.waypoints().first().expect("empty way");
+        let last = list.waypoints().last().expect("empty way");
+        let closed = list.area() && list.len() > 2;
+        let count = if closed
+            && first.latitude() == last.latitude()
+            && first.longitude() == last.longitude()
+        {
+            list.len() - 1
+        } else {
+            list.len()
+        };
+        let mut refs = Vec::with_capacity(count + 1);
+        for waypoint in list.waypoints().iter().take(count) {
+            refs.push(osm_write_node(writer, id, waypoint, &[])?);
+        }
+        if closed {
+            refs.push(refs[0]);
+        }
+        *id -= 1;
+        if get_debug() >= 2 {
+            eprintln!(
+                "osm: way {} (nodes: {:3}, name: \"{}\", area: {})",
+                id,
+                refs.len(),
+                list.name(),
+                list.area()
+            );
+        }
+        let name = list.name();
+        writer
+            .create_element("way")
+            .with_attribute(("id", id.to_string().as_str()))
+            .write_inner_content(|writer| {
+                for node in refs.iter() {
+                    writer
+                        .create_element("nd")
+                        .with_attribute(("ref", node.to_string().as_str()))
+                        .write_empty()?;
+                }
+                if !name.is_empty() {
+                    osm_write_tag(writer, "name", &name)?;
+                }
+                if !list.description().is_empty() {
+                    osm_write_tag(writer, "description", list.description())?;
+                }
+                if !list.comment().is_empty() {
+                    osm_write_tag(writer, "note", list.comment())?;
+                }
+                if closed {
+                    osm_write_tag(writer, "area", "yes")?;
+                }
+                if !self.source.is_empty() {
+                    osm_write_tag(writer, "source", &self.source)?;
+                }
+                Ok(())
+            })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+///
+///  osm test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::osm::*;
+
+    fn write(format: &OsmFormat, geodata: &Geodata) -> String {
+        String::from_utf8(format.write(geodata).unwrap()).unwrap()
+    }
+
+    fn attributes<'a>(xml: &'a str, tag: &str, key: &str) -> Vec<&'a str> {
+        let (open, key) = (format!("<{} ", tag), format!("{}=\"", key));
+        xml.split(open.as_str())
+            .skip(1)
+            .map(|s| s.split('>').next().unwrap())
+            .filter_map(|s| {
+                s.strip_prefix(key.as_str())
+                    .or_else(|| s.split(format!(" {}", key).as_str()).nth(1))
+            })
+            .map(|s| s.split('"').next().unwrap())
+            .collect()
+    }
+
+    fn square(area: bool, repeat: bool) -> WaypointList {
+        let mut list = WaypointList::new();
+        for (lat, lon) in [(50.0, 10.0), (50.0, 10.1), (50.1, 10.1), (50.1, 10.0)] {
+            list.add_waypoint(Waypoint::new().with_lat(lat).with_lon(lon));
+        }
+        if repeat {
+            list.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        }
+        list.set_area(area);
+        list
+    }
+
+    #[test]
+    fn test_ids() {
+        let mut geodata = Geodata::new();
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.0)
+                .with_name("Start"),
+        );
+        geodata.add_track(square(false, false));
+        let xml = write(&OsmFormat::new(), &geodata);
+        assert!(xml.contains("<osm version=\"0.6\" generator=\"ggvtogpx\" upload=\"never\">"));
+        // new objects, nodes and ways from one counter
+        assert_eq!(
+            attributes(&xml, "node", "id"),
+            ["-1", "-2", "-3", "-4", "-5"]
+        );
+        assert_eq!(attributes(&xml, "way", "id"), ["-6"]);
+        assert_eq!(attributes(&xml, "nd", "ref"), ["-2", "-3", "-4", "-5"]);
+        assert!(!xml.contains("k=\"area\""));
+    }
+
+    #[test]
+    fn test_area() {
+        for repeat in [false, true] {
+            let mut geodata = Geodata::new();
+            geodata.add_track(square(true, repeat));
+            let xml = write(&OsmFormat::new(), &geodata);
+            // closed way without a duplicate node for a repeated first point
+            assert_eq!(attributes(&xml, "node", "id").len(), 4);
+            assert_eq!(
+                attributes(&xml, "nd", "ref"),
+                ["-1", "-2", "-3", "-4", "-1"]
+            );
+            assert!(xml.contains("<tag k=\"area\" v=\"yes\"/>"));
+        }
+    }
+
+    #[test]
+    fn test_source() {
+        let mut geodata = Geodata::new();
+        geodata.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        geodata.add_track(square(false, false));
+        let mut format = OsmFormat::new();
+        let xml = write(&format, &geodata);
+        assert_eq!(xml.matches("<tag k=\"source\" v=\"ggvtogpx\"/>").count(), 2);
+
+        format.set_option("source", "survey").unwrap();
+        let xml = write(&format, &geodata);
+        assert_eq!(xml.matches("<tag k=\"source\" v=\"survey\"/>").count(), 2);
+
+        // empty source omits the tag
+        format.set_option("source", "").unwrap();
+        let xml = write(&format, &geodata);
+        assert!(!xml.contains("k=\"source\""));
+        assert!(format.set_option("speed", "10").is_err());
+    }
+}
@@ -0,0 +1,469 @@
+///
+///  Support for OziExplorer track (.plt), waypoint (.wpt) and route
+///  (.rte) files.
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use encoding_rs::mem::decode_latin1;
+use encoding_rs::mem::encode_latin1_lossy;
+
+use crate::format::Format;
+use crate::geodata::Color;
+use crate::geodata::Geodata;
+use crate::geodata::Style;
+use crate::geodata::Waypoint;
+use crate::geodata::WaypointList;
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+const OZI_DATUM: &str = "WGS 84";
+const OZI_FEET_PER_METER: f64 = 3.2808399;
+/// Altitude value marking a missing altitude
+const OZI_NO_ALTITUDE: f64 = -777.0;
+/// Commas are not allowed in text fields and get replaced by this
+/// character (209 in the Windows codepage).
+const OZI_COMMA: char = '\u{d1}';
+/// Dates are Delphi TDateTime values counting days since 1899-12-30,
+/// which is this many seconds before the unix epoch.
+const OZI_EPOCH: i64 = 2209161600;
+const OZI_DEFAULT_COLOR: Color = Color::new(255, 0, 0);
+const OZI_DEFAULT_WIDTH: u16 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OziKind {
+    Track,
+    Waypoint,
+    Route,
+}
+
+impl OziKind {
+    fn header(&self) -> &'static str {
+        match self {
+            OziKind::Track => "OziExplorer Track Point File Version 2.1",
+            OziKind::Waypoint => "OziExplorer Waypoint File Version 1.1",
+            OziKind::Route => "OziExplorer Route File Version 1.0",
+        }
+    }
+    fn magic(&self) -> &'static str {
+        match self {
+            OziKind::Track => "OziExplorer Track Point File",
+            OziKind::Waypoint => "OziExplorer Waypoint File",
+            OziKind::Route => "OziExplorer Route File",
+        }
+    }
+}
+
+/// Colors are stored as Delphi TColor values (0x00bbggrr)
+fn ozi_parse_color(field: &str) -> Option<Color> {
+    let val = field.trim().parse::<u32>().ok()?;
+    Some(Color::new(
+        (val & 0xff) as u8,
+        ((val >> 8) & 0xff) as u8,
+        ((val >> 16) & 0xff) as u8,
+    ))
+}
+
+fn ozi_color(color: Color) -> u32 {
+    color.red() as u32 | (color.green() as u32) << 8 | (color.blue() as u32) << 16
+}
+
+fn ozi_parse_time(field: &str) -> Option<DateTime<Utc>> {
+    let days = field.trim().parse::<f64>().ok().filter(|d| *d > 0.0)?;
+    DateTime::from_timestamp_millis((days * 86400000.0).round() as i64 - OZI_EPOCH * 1000)
+}
+
+/// Date field, blank without time. OziExplorer fills in a preset
+/// date for blank fields.
+fn ozi_time(time: Option<DateTime<Utc>>) -> String {
+    match time {
+        Some(time) => format!(
+            "{:.7}",
+            (time.timestamp_millis() + OZI_EPOCH * 1000) as f64 / 86400000.0
+        ),
+        None => String::new(),
+    }
+}
+
+fn ozi_parse_text(field: &str) -> String {
+    field.trim().replace(OZI_COMMA, ",")
+}
+
+fn ozi_text(text: &str) -> String {
+    text.replace(',', &OZI_COMMA.to_string())
+        .replace(['\r', '\n'], " ")
+}
+
+fn ozi_parse_altitude(field: &str) -> f64 {
+    match field.trim().parse::<f64>() {
+        Ok(alt) if alt != OZI_NO_ALTITUDE => alt / OZI_FEET_PER_METER,
+        _ => f64::NAN,
+    }
+}
+
+fn ozi_altitude(elevation: f64) -> f64 {
+    if elevation.is_nan() {
+        OZI_NO_ALTITUDE
+    } else {
+        elevation * OZI_FEET_PER_METER
+    }
+}
+
+fn ozi_parse_waypoint(lat: &str, lon: &str, alt: &str) -> Result<Waypoint> {
+    let lat = lat
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("latitude: {:?}", lat))?;
+    let lon = lon
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("longitude: {:?}", lon))?;
+    Ok(Waypoint::new()
+        .with_lat(lat)
+        .with_lon(lon)
+        .with_elevation(ozi_parse_altitude(alt)))
+}
+
+/// Split file into lines and check the header. Returns the data lines
+/// following the four header lines.
+fn ozi_read_lines(buf: &[u8], kind: OziKind) -> Result<Vec<String>> {
+    let text = decode_latin1(buf);
+    let mut lines = text.lines().map(|l| l.trim_end_matches('\r').to_string());
+    let header = lines.next().unwrap_or_default();
+    if !header.starts_with(kind.magic()) {
+        return Err(anyhow!("header: {:?}", header));
+    }
+    let datum = lines.next().unwrap_or_default();
+    if datum.trim() != OZI_DATUM {
+        return Err(anyhow!("unsupported datum: {:?}", datum.trim()));
+    }
+    Ok(lines.skip(2).collect())
+}
+
+fn ozi_read_plt(buf: &[u8], geodata: &mut Geodata) -> Result<()> {
+    let lines = ozi_read_lines(buf, OziKind::Track)?;
+    let mut track = WaypointList::new();
+    // line 5 holds the track attributes, line 6 the number of points
+    if let Some(info) = lines.first() {
+        let fields: Vec<&str> = info.split(',').collect();
+        let mut style = Style::new();
+        if let Some(width) = fields.get(1).and_then(|w| w.trim().parse::<u16>().ok()) {
+            style = style.with_width(width);
+        }
+        if let Some(color) = fields.get(2).and_then(|c| ozi_parse_color(c)) {
+            style = style.with_color(color);
+        }
+        track.set_style(style);
+        if let Some(name) = fields.get(3) {
+            track.set_name(&ozi_parse_text(name));
+        }
+    }
+    for (i, line) in lines.iter().enumerate().skip(2) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() < 4 {
+            return Err(anyhow!("line {}: too few fields", i + 5));
+        }
+        let mut waypoint = ozi_parse_waypoint(fields[0], fields[1], fields[3])
+            .with_context(|| format!("line {}", i + 5))?;
+        if let Some(time) = fields.get(4).and_then(|t| ozi_parse_time(t)) {
+            waypoint.set_time(time);
+        }
+        // field 3 marks the first point of a new segment
+        if fields[2].trim() == "1" {
+            track.start_segment();
+        }
+        if get_debug() >= 3 {
+            eprintln!(
+                "ozi: trkpt: {:09.5} {:08.5} {:.1}",
+                waypoint.latitude(),
+                waypoint.longitude(),
+                waypoint.elevation()
+            );
+        }
+        track.add_waypoint(waypoint);
+    }
+    geodata.add_track(track);
+    Ok(())
+}
+
+fn ozi_read_wpt(buf: &[u8], geodata: &mut Geodata) -> Result<()> {
+    let lines = ozi_read_lines(buf, OziKind::Waypoint)?;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() < 4 {
+            return Err(anyhow!("line {}: too few fields", i + 5));
+        }
+        let alt = fields.get(14).unwrap_or(&"");
+        let mut waypoint = ozi_parse_waypoint(fields[2], fields[3], alt)
+            .with_context(|| format!("line {}", i + 5))?;
+        waypoint.set_name(&ozi_parse_text(fields[1]));
+        waypoint.set_description(&ozi_parse_text(fields.get(10).unwrap_or(&"")));
+        if let Some(color) = fields.get(8).and_then(|c| ozi_parse_color(c)) {
+            waypoint = waypoint.with_style(Style::new().with_color(color));
+        }
+        if let Some(time) = fields.get(4).and_then(|t| ozi_parse_time(t)) {
+            waypoint.set_time(time);
+        }
+        geodata.add_waypoint(waypoint);
+    }
+    Ok(())
+}
+
+fn ozi_read_rte(buf: &[u8], geodata: &mut Geodata) -> Result<()> {
+    let lines = ozi_read_lines(buf, OziKind::Route)?;
+    let mut route: Option<WaypointList> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let fields: Vec<&str> = line.split(',').collect();
+        match fields[0].trim() {
+            "R" => {
+                if let Some(route) = route.take() {
+                    geodata.add_route(route);
+                }
+                let mut list = WaypointList::new();
+                list.set_name(&ozi_parse_text(fields.get(2).unwrap_or(&"")));
+                if let Some(color) = fields.get(4).and_then(|c| ozi_parse_color(c)) {
+                    list.set_style(Style::new().with_color(color));
+                }
+                route = Some(list);
+            }
+            "W" => {
+                if fields.len() < 7 {
+                    return Err(anyhow!("line {}: too few fields", i + 5));
+                }
+                let Some(list) = route.as_mut() else {
+                    return Err(anyhow!("line {}: waypoint outside of route", i + 5));
+                };
+                let mut waypoint = ozi_parse_waypoint(fields[5], fields[6], "")
+                    .with_context(|| format!("line {}", i + 5))?;
+                waypoint.set_name(&ozi_parse_text(fields[4]));
+                waypoint.set_description(&ozi_parse_text(fields.get(13).unwrap_or(&"")));
+                if let Some(time) = fields.get(7).and_then(|t| ozi_parse_time(t)) {
+                    waypoint.set_time(time);
+                }
+                list.add_waypoint(waypoint);
+            }
+            _ => (),
+        }
+    }
+    if let Some(route) = route.take() {
+        geodata.add_route(route);
+    }
+    Ok(())
+}
+
+fn ozi_write_header(kind: OziKind) -> Vec<String> {
+    let mut result: Vec<String> = vec![kind.header().into(), OZI_DATUM.into()];
+    match kind {
+        OziKind::Track => {
+            result.push("Altitude is in Feet".into());
+            result.push("Reserved 3".into());
+        }
+        OziKind::Waypoint => {
+            result.push("Reserved 2".into());
+            result.push("Reserved 3".into());
+        }
+        OziKind::Route => {
+            result.push("Reserved 1".into());
+            result.push("Reserved 2".into());
+        }
+    }
+    result
+}
+
+fn ozi_write_plt(geodata: &Geodata) -> Vec<String> {
+    let mut result = ozi_write_header(OziKind::Track);
+    // All tracks go into one file, the first point of each track and
+    // segment is marked as a break. Attributes are taken from the
+    // first track.
+    let first = geodata.tracks().first();
+    let style = first.map(|t| t.style().clone()).unwrap_or_default();
+    result.push(format!(
+        "0,{},{},{},0,0,2,{}",
+        style.width().unwrap_or(OZI_DEFAULT_WIDTH),
+        ozi_color(style.color().unwrap_or(OZI_DEFAULT_COLOR)),
+        ozi_text(&first.map(|t| t.name()).unwrap_or_default()),
+        ozi_color(style.color().unwrap_or(OZI_DEFAULT_COLOR)),
+    ));
+    let points: usize = geodata.tracks().iter().map(|t| t.len()).sum();
+    result.push(format!("{}", points));
+    for segment in geodata.tracks().iter().flat_map(|t| t.segments()) {
+        for (i, waypoint) in segment.iter().enumerate() {
+            // Only the numeric date is written, OziExplorer ignores
+            // the date and time strings.
+            result.push(format!(
+                "{:.6},{:.6},{},{:.1},{},,",
+                waypoint.latitude(),
+                waypoint.longitude(),
+                if i == 0 { 1 } else { 0 },
+                ozi_altitude(waypoint.elevation()),
+                ozi_time(waypoint.time())
+            ));
+        }
+    }
+    result
+}
+
+fn ozi_write_wpt(geodata: &Geodata) -> Vec<String> {
+    let mut result = ozi_write_header(OziKind::Waypoint);
+    for (i, waypoint) in geodata.waypoints().waypoints().iter().enumerate() {
+        let name = if waypoint.name().is_empty() {
+            format!("WPT{:03}", i + 1)
+        } else {
+            ozi_text(&waypoint.name())
+        };
+        result.push(format!(
+            "{},{},{:.6},{:.6},{},0,1,3,{},65535,{},0,0,0,{:.0},6,0,17",
+            i + 1,
+            name,
+            waypoint.latitude(),
+            waypoint.longitude(),
+            ozi_time(waypoint.time()),
+            ozi_color(waypoint.style().color().unwrap_or(Color::new(0, 0, 0))),
+            ozi_text(waypoint.description()),
+            ozi_altitude(waypoint.elevation())
+        ));
+    }
+    result
+}
+
+fn ozi_write_rte(geodata: &Geodata) -> Vec<String> {
+    let mut result = ozi_write_header(OziKind::Route);
+    let mut number = 0;
+    for (r, route) in geodata.routes().iter().enumerate() {
+        let name = if route.name().is_empty() {
+            format!("Route {}", r + 1)
+        } else {
+            ozi_text(&route.name())
+        };
+        result.push(format!(
+            "R,{},{},,{}",
+            r + 1,
+            name,
+            ozi_color(route.style().color().unwrap_or(OZI_DEFAULT_COLOR))
+        ));
+        for (i, waypoint) in route.waypoints().iter().enumerate() {
+            number += 1;
+            let name = if waypoint.name().is_empty() {
+                format!("RPT{:03}", number)
+            } else {
+                ozi_text(&waypoint.name())
+            };
+            result.push(format!(
+                "W,{},{},{},{},{:.6},{:.6},{},0,1,3,0,65535,{},0,0",
+                r + 1,
+                i + 1,
+                number,
+                name,
+                waypoint.latitude(),
+                waypoint.longitude(),
+                ozi_time(waypoint.time()),
+                ozi_text(waypoint.description())
+            ));
+        }
+    }
+    result
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+pub struct OziFormat {
+    kind: OziKind,
+    debug: u8,
+}
+
+impl Format for OziFormat {
+    fn probe(&self, buf: &[u8]) -> bool {
+        buf.starts_with(self.kind.magic().as_bytes())
+    }
+    fn read(&self, buf: &[u8]) -> Result<Geodata> {
+        let mut geodata = Geodata::new().with_debug(self.debug);
+        let result = match self.kind {
+            OziKind::Track => ozi_read_plt(buf, &mut geodata),
+            OziKind::Waypoint => ozi_read_wpt(buf, &mut geodata),
+            OziKind::Route => ozi_read_rte(buf, &mut geodata),
+        };
+        match result {
+            Ok(_) => Ok(geodata),
+            Err(e) => Err(anyhow!(
+                "reading {} failed (context: \"{:#}\")",
+                self.name(),
+                e
+            )),
+        }
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        let result = match self.kind {
+            OziKind::Track => ozi_write_plt(geodata),
+            OziKind::Waypoint => ozi_write_wpt(geodata),
+            OziKind::Route => ozi_write_rte(geodata),
+        };
+        if self.debug >= 1 {
+            eprintln!("ozi: writing {} lines", result.len());
+        }
+        Ok(encode_latin1_lossy(&(result.join("\r\n") + "\r\n")).into_owned())
+    }
+    fn name<'a>(&self) -> &'a str {
+        match self.kind {
+            OziKind::Track => "ozi_plt",
+            OziKind::Waypoint => "ozi_wpt",
+            OziKind::Route => "ozi_rte",
+        }
+    }
+    fn extension<'a>(&self) -> &'a str {
+        match self.kind {
+            OziKind::Track => "plt",
+            OziKind::Waypoint => "wpt",
+            OziKind::Route => "rte",
+        }
+    }
+    fn can_read(&self) -> bool {
+        true
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+}
+
+impl OziFormat {
+    pub fn new(kind: OziKind) -> Self {
+        Self { kind, debug: 0 }
+    }
+}
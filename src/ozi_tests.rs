@@ -0,0 +1,108 @@
+///
+///  ozi test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use encoding_rs::mem::encode_latin1_lossy;
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::ozi::*;
+
+    #[test]
+    fn test_read_plt() {
+        let plt = "OziExplorer Track Point File Version 2.1\r\n\
+                   WGS 84\r\n\
+                   Altitude is in Feet\r\n\
+                   Reserved 3\r\n\
+                   0,3,16711680,Brocken\u{d1} Harz,0,0,2,8421376\r\n\
+                   2\r\n  51.800000,  10.600000,1, 3280.8,36000.0,,\r\n\
+                   51.810000,10.610000,0,-777,,,\r\n";
+        let plt = encode_latin1_lossy(plt);
+        let format = OziFormat::new(OziKind::Track);
+        assert!(format.probe(&plt));
+        let geodata = format.read(&plt).unwrap();
+        let track = &geodata.tracks()[0];
+        assert_eq!(track.name(), "Brocken, Harz");
+        assert_eq!(track.style().color(), Some(Color::new(0, 0, 255)));
+        assert_eq!(track.style().width(), Some(3));
+        assert_eq!(track.len(), 2);
+        let wpt = &track.waypoints()[0];
+        assert_eq!(wpt.latitude(), 51.8);
+        assert!((wpt.elevation() - 1000.0).abs() < 0.1);
+        assert!(track.waypoints()[1].elevation().is_nan());
+        assert_eq!(
+            wpt.time().unwrap().to_rfc3339(),
+            "1998-07-24T00:00:00+00:00"
+        );
+        assert!(track.waypoints()[1].time().is_none());
+    }
+
+    #[test]
+    fn test_read_wpt() {
+        // only number, name and position are required
+        let wpt = b"OziExplorer Waypoint File Version 1.1\r\n\
+                    WGS 84\r\n\
+                    Reserved 2\r\n\
+                    Reserved 3\r\n\
+                    1,A,50.0,10.0\r\n";
+        let geodata = OziFormat::new(OziKind::Waypoint).read(wpt).unwrap();
+        let waypoint = &geodata.waypoints().waypoints()[0];
+        assert_eq!(waypoint.name(), "A");
+        assert_eq!(waypoint.latitude(), 50.0);
+        assert_eq!(waypoint.longitude(), 10.0);
+        assert!(waypoint.time().is_none());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut geodata = Geodata::new();
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(47.5)
+                .with_lon(11.25)
+                .with_name("Hütte, oben")
+                .with_elevation(1500.0),
+        );
+        let mut route = WaypointList::new();
+        route.set_name("Runde");
+        route.set_style(Style::new().with_color(Color::new(0, 128, 0)));
+        route.add_waypoint(Waypoint::new().with_lat(47.5).with_lon(11.25));
+        route.add_waypoint(Waypoint::new().with_lat(47.6).with_lon(11.3));
+        geodata.add_route(route);
+
+        let wpt = OziFormat::new(OziKind::Waypoint);
+        let result = wpt.read(&wpt.write(&geodata).unwrap()).unwrap();
+        let waypoint = &result.waypoints().waypoints()[0];
+        assert_eq!(waypoint.name(), "Hütte, oben");
+        assert!((waypoint.elevation() - 1500.0).abs() < 0.5);
+
+        let rte = OziFormat::new(OziKind::Route);
+        let result = rte.read(&rte.write(&geodata).unwrap()).unwrap();
+        assert_eq!(result.routes().len(), 1);
+        assert_eq!(result.routes()[0].name(), "Runde");
+        assert_eq!(result.routes()[0].len(), 2);
+        assert_eq!(
+            result.routes()[0].style().color(),
+            Some(Color::new(0, 128, 0))
+        );
+    }
+}
@@ -0,0 +1,475 @@
+///
+///  Render geodata to PNG images (e.g. thumbnails of overlay files)
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::dib::dib_decode_bmp;
+use crate::format::Format;
+use crate::geodata::Color;
+use crate::geodata::Geodata;
+use crate::geodata::Waypoint;
+use crate::geodata::WaypointList;
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+const PNG_DEFAULT_SIZE: u32 = 256;
+const PNG_MAX_SIZE: u32 = 8192;
+const PNG_MARGIN: f64 = 8.0;
+const PNG_LINE_COLOR: Color = Color::new(0, 0, 255);
+const PNG_POINT_COLOR: Color = Color::new(255, 0, 0);
+const PNG_LINE_WIDTH: u16 = 2;
+const PNG_POINT_RADIUS: f64 = 3.0;
+const PNG_AREA_ALPHA: f64 = 0.35;
+
+/// RGBA image with straight (non-premultiplied) alpha
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, background: Option<Color>) -> Self {
+        let pixel = match background {
+            Some(c) => [c.red(), c.green(), c.blue(), 255],
+            None => [0, 0, 0, 0],
+        };
+        Self {
+            width,
+            height,
+            pixels: vec![pixel; (width * height) as usize],
+        }
+    }
+
+    /// Composite color with the given opacity over pixel x/y
+    fn blend(&mut self, x: i64, y: i64, color: [f64; 3], alpha: f64) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || alpha <= 0.0 {
+            return;
+        }
+        let dst = &mut self.pixels[(y as u32 * self.width + x as u32) as usize];
+        let dst_alpha = dst[3] as f64 / 255.0;
+        let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+        if out_alpha <= 0.0 {
+            return;
+        }
+        for i in 0..3 {
+            let value = (color[i] * alpha + dst[i] as f64 * dst_alpha * (1.0 - alpha)) / out_alpha;
+            dst[i] = value.round().clamp(0.0, 255.0) as u8;
+        }
+        dst[3] = (out_alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+
+    /// Composite a coverage mask with a single color
+    fn blend_mask(&mut self, mask: &Mask, color: Color, alpha: f64) {
+        let color = [
+            color.red() as f64,
+            color.green() as f64,
+            color.blue() as f64,
+        ];
+        let Some((x0, y0, x1, y1)) = mask.bbox else {
+            return;
+        };
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let coverage = mask.coverage[mask.index(x, y)] as f64;
+                if coverage > 0.0 {
+                    self.blend(x as i64, y as i64, color, coverage * alpha);
+                }
+            }
+        }
+    }
+
+    fn to_rgba(&self) -> &[u8] {
+        self.pixels.as_flattened()
+    }
+}
+
+/// Anti-aliased coverage of one object. Overlapping parts of the same
+/// object use the maximum coverage so line joints are not painted twice.
+/// The mask only spans the canvas pixels the object can touch.
+struct Mask {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    coverage: Vec<f32>,
+    bbox: Option<(u32, u32, u32, u32)>,
+}
+
+impl Mask {
+    /// Mask around points with margin pixels on each side, clipped to
+    /// a canvas of width x height
+    fn new(points: &[(f64, f64)], margin: f64, width: u32, height: u32) -> Self {
+        let (mut min_x, mut min_y) = (f64::MAX, f64::MAX);
+        let (mut max_x, mut max_y) = (f64::MIN, f64::MIN);
+        for (x, y) in points.iter() {
+            (min_x, min_y) = (min_x.min(*x), min_y.min(*y));
+            (max_x, max_y) = (max_x.max(*x), max_y.max(*y));
+        }
+        let x0 = (min_x - margin).floor().max(0.0) as u32;
+        let y0 = (min_y - margin).floor().max(0.0) as u32;
+        let x1 = (max_x + margin).ceil().min(width as f64) as u32;
+        let y1 = (max_y + margin).ceil().min(height as f64) as u32;
+        let (width, height) = (x1.saturating_sub(x0), y1.saturating_sub(y0));
+        Self {
+            x0,
+            y0,
+            width,
+            height,
+            coverage: vec![0.0; (width as usize) * (height as usize)],
+            bbox: None,
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        ((y - self.y0) as usize) * (self.width as usize) + (x - self.x0) as usize
+    }
+
+    fn set(&mut self, x: i64, y: i64, coverage: f64) {
+        if x < self.x0 as i64
+            || y < self.y0 as i64
+            || x >= (self.x0 + self.width) as i64
+            || y >= (self.y0 + self.height) as i64
+        {
+            return;
+        }
+        if coverage <= 0.0 {
+            return;
+        }
+        let (x, y) = (x as u32, y as u32);
+        let index = self.index(x, y);
+        let c = &mut self.coverage[index];
+        *c = c.max(coverage.min(1.0) as f32);
+        self.bbox = match self.bbox {
+            Some((x0, y0, x1, y1)) => Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y))),
+            None => Some((x, y, x, y)),
+        };
+    }
+
+    /// Stroke segment a-b with the given width
+    fn line(&mut self, a: (f64, f64), b: (f64, f64), width: f64) {
+        let half = width / 2.0;
+        let x0 = (a.0.min(b.0) - half - 1.0).floor() as i64;
+        let x1 = (a.0.max(b.0) + half + 1.0).ceil() as i64;
+        let y0 = (a.1.min(b.1) - half - 1.0).floor() as i64;
+        let y1 = (a.1.max(b.1) + half + 1.0).ceil() as i64;
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len2 = dx * dx + dy * dy;
+        let (mx1, my1) = (
+            (self.x0 + self.width) as i64,
+            (self.y0 + self.height) as i64,
+        );
+        for y in y0.max(self.y0 as i64)..=y1.min(my1 - 1) {
+            for x in x0.max(self.x0 as i64)..=x1.min(mx1 - 1) {
+                let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+                let t = if len2 > 0.0 {
+                    (((px - a.0) * dx + (py - a.1) * dy) / len2).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let (cx, cy) = (a.0 + t * dx, a.1 + t * dy);
+                let dist = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+                self.set(x, y, half + 0.5 - dist);
+            }
+        }
+    }
+
+    /// Fill polygon using the even-odd rule, sampling pixel centers
+    fn polygon(&mut self, points: &[(f64, f64)]) {
+        if points.len() < 3 {
+            return;
+        }
+        for y in self.y0..self.y0 + self.height {
+            let py = y as f64 + 0.5;
+            let mut crossings: Vec<f64> = Vec::new();
+            for i in 0..points.len() {
+                let a = points[i];
+                let b = points[(i + 1) % points.len()];
+                if (a.1 <= py && b.1 > py) || (b.1 <= py && a.1 > py) {
+                    crossings.push(a.0 + (py - a.1) / (b.1 - a.1) * (b.0 - a.0));
+                }
+            }
+            crossings.sort_by(|a, b| a.total_cmp(b));
+            for pair in crossings.chunks_exact(2) {
+                let x0 = (pair[0] - 0.5).ceil().max(self.x0 as f64) as i64;
+                let x1 = (pair[1] - 0.5)
+                    .floor()
+                    .min((self.x0 + self.width) as f64 - 1.0) as i64;
+                for x in x0..=x1 {
+                    self.set(x, y as i64, 1.0);
+                }
+            }
+        }
+    }
+
+    fn dot(&mut self, center: (f64, f64), radius: f64) {
+        self.line(center, center, radius * 2.0);
+    }
+}
+
+/// Maps longitude/latitude into image coordinates. Uses an
+/// equirectangular projection scaled by the cosine of the center
+/// latitude, which is good enough for the extent of typical overlays.
+pub(crate) struct Projection {
+    scale: f64,
+    lon_factor: f64,
+    offset_x: f64,
+    offset_y: f64,
+}
+
+impl Projection {
+    pub(crate) fn new(min: &Waypoint, max: &Waypoint, width: u32, height: u32) -> Self {
+        let lon_factor = ((min.latitude() + max.latitude()) / 2.0)
+            .to_radians()
+            .cos()
+            .max(0.01);
+        let extent_x = (max.longitude() - min.longitude()) * lon_factor;
+        let extent_y = max.latitude() - min.latitude();
+        let avail_x = (width as f64 - 2.0 * PNG_MARGIN).max(1.0);
+        let avail_y = (height as f64 - 2.0 * PNG_MARGIN).max(1.0);
+        let scale = match (extent_x > 0.0, extent_y > 0.0) {
+            (true, true) => (avail_x / extent_x).min(avail_y / extent_y),
+            (true, false) => avail_x / extent_x,
+            (false, true) => avail_y / extent_y,
+            (false, false) => 1.0,
+        };
+        Self {
+            scale,
+            lon_factor,
+            offset_x: (width as f64 - extent_x * scale) / 2.0
+                - min.longitude() * lon_factor * scale,
+            offset_y: (height as f64 - extent_y * scale) / 2.0 + max.latitude() * scale,
+        }
+    }
+
+    pub(crate) fn project(&self, waypoint: &Waypoint) -> (f64, f64) {
+        (
+            waypoint.longitude() * self.lon_factor * self.scale + self.offset_x,
+            self.offset_y - waypoint.latitude() * self.scale,
+        )
+    }
+}
+
+pub(crate) fn png_bounds(geodata: &Geodata) -> Option<(Waypoint, Waypoint)> {
+    let positions: Vec<&Waypoint> = geodata.bitmaps().iter().map(|b| b.position()).collect();
+    let (mut min, mut max) = match geodata.get_bounds() {
+        Some(bounds) => bounds,
+        None => {
+            let first = positions.first()?;
+            ((*first).clone(), (*first).clone())
+        }
+    };
+    for p in positions {
+        min = Waypoint::new()
+            .with_lat(min.latitude().min(p.latitude()))
+            .with_lon(min.longitude().min(p.longitude()));
+        max = Waypoint::new()
+            .with_lat(max.latitude().max(p.latitude()))
+            .with_lon(max.longitude().max(p.longitude()));
+    }
+    Some((min, max))
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct PngFormat {
+    debug: u8,
+    width: u32,
+    height: u32,
+    background: Option<Color>,
+}
+
+impl Format for PngFormat {
+    fn probe(&self, _buf: &[u8]) -> bool {
+        false
+    }
+    fn read(&self, _buf: &[u8]) -> Result<Geodata> {
+        Err(anyhow!("png read not supported"))
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        let mut canvas = Canvas::new(self.width, self.height, self.background);
+        if let Some((min, max)) = png_bounds(geodata) {
+            let projection = Projection::new(&min, &max, self.width, self.height);
+            if get_debug() >= 2 {
+                eprintln!(
+                    "png: bounds: {:.5} {:.5} - {:.5} {:.5}, scale: {:.1}",
+                    min.latitude(),
+                    min.longitude(),
+                    max.latitude(),
+                    max.longitude(),
+                    projection.scale
+                );
+            }
+            self.draw(&mut canvas, &projection, geodata);
+        }
+        let mut buffer = Vec::new();
+        let mut encoder = ::png::Encoder::new(&mut buffer, self.width, self.height);
+        encoder.set_color(::png::ColorType::Rgba);
+        encoder.set_depth(::png::BitDepth::Eight);
+        let mut writer = encoder.write_header().with_context(|| "png header")?;
+        writer
+            .write_image_data(canvas.to_rgba())
+            .with_context(|| "png image data")?;
+        writer.finish().with_context(|| "png finish")?;
+        Ok(buffer)
+    }
+    fn name<'a>(&self) -> &'a str {
+        "png"
+    }
+    fn can_read(&self) -> bool {
+        false
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+    fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "size" => {
+                let (w, h) = value.split_once('x').unwrap_or((value, value));
+                let w: u32 = w.parse().with_context(|| format!("png: size: {}", value))?;
+                let h: u32 = h.parse().with_context(|| format!("png: size: {}", value))?;
+                if w == 0 || h == 0 || w > PNG_MAX_SIZE || h > PNG_MAX_SIZE {
+                    return Err(anyhow!("png: size out of range: {}", value));
+                }
+                self.width = w;
+                self.height = h;
+            }
+            "background" => {
+                self.background = match value {
+                    "transparent" => None,
+                    "white" => Some(Color::new(255, 255, 255)),
+                    _ => return Err(anyhow!("png: unknown background: {}", value)),
+                }
+            }
+            _ => return Err(anyhow!("png: unknown option: {}", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for PngFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PngFormat {
+    pub fn new() -> Self {
+        Self {
+            debug: 0,
+            width: PNG_DEFAULT_SIZE,
+            height: PNG_DEFAULT_SIZE,
+            background: None,
+        }
+    }
+
+    fn draw_list(&self, canvas: &mut Canvas, projection: &Projection, list: &WaypointList) {
+        let points: Vec<(f64, f64)> = list
+            .waypoints()
+            .iter()
+            .map(|w| projection.project(w))
+            .collect();
+        let color = list.style().color().unwrap_or(PNG_LINE_COLOR);
+        let width = list.style().width().unwrap_or(PNG_LINE_WIDTH).max(1) as f64;
+        if list.area() {
+            let mut fill = Mask::new(&points, 1.0, canvas.width, canvas.height);
+            fill.polygon(&points);
+            canvas.blend_mask(&fill, color, PNG_AREA_ALPHA);
+        }
+        let margin = width / 2.0 + 2.0;
+        let mut stroke = Mask::new(&points, margin, canvas.width, canvas.height);
+        if list.area() && points.len() > 2 {
+            for pair in points.windows(2) {
+                stroke.line(pair[0], pair[1], width);
+            }
+            stroke.line(points[points.len() - 1], points[0], width);
+        } else {
+            // Segments are not connected, each is stroked on its own
+            let mut start = 0;
+            for segment in list.segments() {
+                let segment_points = &points[start..start + segment.len()];
+                start += segment.len();
+                for pair in segment_points.windows(2) {
+                    stroke.line(pair[0], pair[1], width);
+                }
+                if segment_points.len() == 1 {
+                    stroke.dot(segment_points[0], width / 2.0);
+                }
+            }
+        }
+        canvas.blend_mask(&stroke, color, 1.0);
+    }
+
+    fn draw(&self, canvas: &mut Canvas, projection: &Projection, geodata: &Geodata) {
+        for list in geodata.tracks().iter().chain(geodata.routes().iter()) {
+            self.draw_list(canvas, projection, list);
+        }
+        for bitmap in geodata.bitmaps().iter() {
+            let image = match dib_decode_bmp(bitmap.data()) {
+                Ok(image) => image,
+                Err(err) => {
+                    if get_debug() >= 1 {
+                        eprintln!("png: skipping {} data: {}", bitmap.kind(), err);
+                    }
+                    continue;
+                }
+            };
+            let (width, height) = (image.width(), image.height());
+            let (cx, cy) = projection.project(bitmap.position());
+            let x0 = (cx - width as f64 / 2.0).round() as i64;
+            let y0 = (cy - height as f64 / 2.0).round() as i64;
+            for (i, p) in image.pixels().iter().enumerate() {
+                let x = x0 + (i as u32 % width) as i64;
+                let y = y0 + (i as u32 / width) as i64;
+                canvas.blend(
+                    x,
+                    y,
+                    [p[0] as f64, p[1] as f64, p[2] as f64],
+                    p[3] as f64 / 255.0,
+                );
+            }
+        }
+        for waypoint in geodata.waypoints().waypoints().iter() {
+            let center = projection.project(waypoint);
+            let margin = PNG_POINT_RADIUS + 2.0;
+            let mut mask = Mask::new(&[center], margin, canvas.width, canvas.height);
+            mask.dot(center, PNG_POINT_RADIUS);
+            let color = waypoint.style().color().unwrap_or(PNG_POINT_COLOR);
+            canvas.blend_mask(&mask, color, 1.0);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+///
+///  png test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::png::*;
+
+    fn decode(data: &[u8]) -> (u32, u32, Vec<u8>) {
+        let decoder = ::png::Decoder::new(std::io::Cursor::new(data));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        (info.width, info.height, buf)
+    }
+
+    #[test]
+    fn test_render_track() {
+        let mut geodata = Geodata::new();
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.1));
+        track.set_style(Style::new().with_color(Color::new(0, 255, 0)).with_width(4));
+        geodata.add_track(track);
+
+        let mut format = PngFormat::new();
+        format.set_option("size", "64x32").unwrap();
+        let (width, height, pixels) = decode(&format.write(&geodata).unwrap());
+        assert_eq!((width, height), (64, 32));
+        // horizontal line through the center, transparent elsewhere
+        let center = ((16 * 64 + 32) * 4) as usize;
+        assert_eq!(&pixels[center..center + 4], &[0, 255, 0, 255]);
+        assert_eq!(pixels[3], 0);
+    }
+
+    #[test]
+    fn test_render_segments() {
+        let mut geodata = Geodata::new();
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.04));
+        track.start_segment();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.06));
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.1));
+        track.set_style(Style::new().with_color(Color::new(0, 255, 0)).with_width(4));
+        geodata.add_track(track);
+
+        let mut format = PngFormat::new();
+        format.set_option("size", "64x32").unwrap();
+        let (_, _, pixels) = decode(&format.write(&geodata).unwrap());
+        // both segments are drawn, the gap between them is not
+        let pixel = |x: usize| &pixels[(16 * 64 + x) * 4..(16 * 64 + x) * 4 + 4];
+        assert_eq!(pixel(16), &[0, 255, 0, 255]);
+        assert_eq!(pixel(48), &[0, 255, 0, 255]);
+        assert_eq!(pixel(32)[3], 0);
+    }
+
+    #[test]
+    fn test_options() {
+        let mut format = PngFormat::new();
+        assert!(format.set_option("size", "0").is_err());
+        assert!(format.set_option("size", "foo").is_err());
+        assert!(format.set_option("background", "blue").is_err());
+        assert!(format.set_option("background", "white").is_ok());
+        let (_, _, pixels) = decode(&format.write(&Geodata::new()).unwrap());
+        assert_eq!(&pixels[0..4], &[255, 255, 255, 255]);
+    }
+}
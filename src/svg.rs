@@ -0,0 +1,303 @@
+///
+///  Support for SVG rendering of overlays
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::io;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::writer::Writer;
+
+use crate::format::Format;
+use crate::geodata::Color;
+use crate::geodata::Geodata;
+use crate::geodata::Label;
+use crate::geodata::LabelFont;
+use crate::geodata::Waypoint;
+use crate::geodata::WaypointList;
+use crate::png::{png_bounds, Projection};
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const SVG_DEFAULT_SIZE: u32 = 512;
+const SVG_MAX_SIZE: u32 = 65535;
+const SVG_LINE_COLOR: Color = Color::new(0, 0, 255);
+const SVG_POINT_COLOR: Color = Color::new(255, 0, 0);
+const SVG_LINE_WIDTH: u16 = 2;
+const SVG_POINT_RADIUS: f64 = 3.0;
+const SVG_AREA_OPACITY: f64 = 0.35;
+/// Width of the halo drawn around labels with a background, relative
+/// to the font size
+const SVG_BACKGROUND_WIDTH: f64 = 0.3;
+
+fn svg_color(color: Color) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        color.red(),
+        color.green(),
+        color.blue()
+    )
+}
+
+fn svg_font_family(font: LabelFont) -> &'static str {
+    match font {
+        LabelFont::Arial => "Arial, sans-serif",
+        LabelFont::Courier => "Courier New, monospace",
+        LabelFont::Times => "Times New Roman, serif",
+        LabelFont::Comic => "Comic Sans MS, cursive",
+    }
+}
+
+fn svg_points(projection: &Projection, points: &[Waypoint]) -> String {
+    points
+        .iter()
+        .map(|w| {
+            let (x, y) = projection.project(w);
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn svg_write_list(
+    writer: &mut Writer<&mut Vec<u8>>,
+    projection: &Projection,
+    list: &WaypointList,
+) -> io::Result<()> {
+    let color = svg_color(list.style().color().unwrap_or(SVG_LINE_COLOR));
+    let width = list
+        .style()
+        .width()
+        .unwrap_or(SVG_LINE_WIDTH)
+        .max(1)
+        .to_string();
+    if list.area() && list.len() > 2 {
+        let points = svg_points(projection, list.waypoints());
+        writer
+            .create_element("polygon")
+            .with_attribute(("points", points.as_str()))
+            .with_attribute(("fill", color.as_str()))
+            .with_attribute(("fill-opacity", SVG_AREA_OPACITY.to_string().as_str()))
+            .with_attribute(("stroke", color.as_str()))
+            .with_attribute(("stroke-width", width.as_str()))
+            .write_empty()?;
+    } else {
+        // Segments are not connected, one polyline each
+        for segment in list.segments().iter().filter(|s| !s.is_empty()) {
+            let points = svg_points(projection, segment);
+            writer
+                .create_element("polyline")
+                .with_attribute(("points", points.as_str()))
+                .with_attribute(("fill", "none"))
+                .with_attribute(("stroke", color.as_str()))
+                .with_attribute(("stroke-width", width.as_str()))
+                .with_attribute(("stroke-linecap", "round"))
+                .with_attribute(("stroke-linejoin", "round"))
+                .write_empty()?;
+        }
+    }
+    Ok(())
+}
+
+/// Labels are centered on their position and rotated counterclockwise.
+/// A background is approximated by a halo stroke behind the text.
+fn svg_write_label(
+    writer: &mut Writer<&mut Vec<u8>>,
+    projection: &Projection,
+    waypoint: &Waypoint,
+    label: &Label,
+) -> io::Result<()> {
+    let (x, y) = projection.project(waypoint);
+    let (x, y) = (format!("{:.1}", x), format!("{:.1}", y));
+    let color = svg_color(waypoint.style().color().unwrap_or(SVG_POINT_COLOR));
+    let mut element = writer
+        .create_element("text")
+        .with_attribute(("x", x.as_str()))
+        .with_attribute(("y", y.as_str()))
+        .with_attribute(("font-family", svg_font_family(label.font())))
+        .with_attribute(("font-size", label.size().to_string().as_str()))
+        .with_attribute(("fill", color.as_str()))
+        .with_attribute(("text-anchor", "middle"))
+        .with_attribute(("dominant-baseline", "central"));
+    if label.angle() != 0 {
+        let rotate = format!("rotate(-{} {} {})", label.angle(), x, y);
+        element = element.with_attribute(("transform", rotate.as_str()));
+    }
+    if let Some(background) = label.background() {
+        let width = format!("{:.1}", label.size() as f64 * SVG_BACKGROUND_WIDTH);
+        element = element
+            .with_attribute(("stroke", svg_color(background).as_str()))
+            .with_attribute(("stroke-width", width.as_str()))
+            .with_attribute(("stroke-linejoin", "round"))
+            .with_attribute(("paint-order", "stroke"));
+    }
+    element.write_text_content(BytesText::new(&waypoint.name()))?;
+    Ok(())
+}
+
+fn svg_write_waypoint(
+    writer: &mut Writer<&mut Vec<u8>>,
+    projection: &Projection,
+    waypoint: &Waypoint,
+) -> io::Result<()> {
+    if let Some(label) = waypoint.label() {
+        return svg_write_label(writer, projection, waypoint, label);
+    }
+    let (x, y) = projection.project(waypoint);
+    let color = svg_color(waypoint.style().color().unwrap_or(SVG_POINT_COLOR));
+    let element = writer
+        .create_element("circle")
+        .with_attribute(("cx", format!("{:.1}", x).as_str()))
+        .with_attribute(("cy", format!("{:.1}", y).as_str()))
+        .with_attribute(("r", SVG_POINT_RADIUS.to_string().as_str()))
+        .with_attribute(("fill", color.as_str()));
+    if waypoint.name().is_empty() {
+        element.write_empty()?;
+    } else {
+        element.write_inner_content(|writer| {
+            writer
+                .create_element("title")
+                .write_text_content(BytesText::new(&waypoint.name()))?;
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct SvgFormat {
+    debug: u8,
+    width: u32,
+    height: u32,
+}
+
+impl Format for SvgFormat {
+    fn probe(&self, _buf: &[u8]) -> bool {
+        false
+    }
+    fn read(&self, _buf: &[u8]) -> Result<Geodata> {
+        Err(anyhow!("svg read not supported"))
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 2);
+        let bounds = png_bounds(geodata);
+        let projection = bounds
+            .as_ref()
+            .map(|(min, max)| Projection::new(min, max, self.width, self.height));
+        if let Some((min, max)) = &bounds
+            && get_debug() >= 2
+        {
+            eprintln!(
+                "svg: bounds: {:.5} {:.5} - {:.5} {:.5}",
+                min.latitude(),
+                min.longitude(),
+                max.latitude(),
+                max.longitude()
+            );
+        }
+        let (width, height) = (self.width.to_string(), self.height.to_string());
+        let view_box = format!("0 0 {} {}", self.width, self.height);
+
+        writer
+            .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+            .expect("writing decl");
+        writer
+            .create_element("svg")
+            .with_attribute(("xmlns", SVG_NAMESPACE))
+            .with_attribute(("width", width.as_str()))
+            .with_attribute(("height", height.as_str()))
+            .with_attribute(("viewBox", view_box.as_str()))
+            .write_inner_content(|writer| {
+                let Some(projection) = &projection else {
+                    return Ok(());
+                };
+                for list in geodata.tracks().iter().chain(geodata.routes().iter()) {
+                    if list.len() > 0 {
+                        svg_write_list(writer, projection, list)?;
+                    }
+                }
+                for waypoint in geodata.waypoints().waypoints().iter() {
+                    svg_write_waypoint(writer, projection, waypoint)?;
+                }
+                Ok(())
+            })?;
+        buffer.push(b'\n');
+        Ok(buffer)
+    }
+    fn name<'a>(&self) -> &'a str {
+        "svg"
+    }
+    fn can_read(&self) -> bool {
+        false
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+    fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "size" => {
+                let (w, h) = value.split_once('x').unwrap_or((value, value));
+                let w: u32 = w.parse().with_context(|| format!("svg: size: {}", value))?;
+                let h: u32 = h.parse().with_context(|| format!("svg: size: {}", value))?;
+                if w == 0 || h == 0 || w > SVG_MAX_SIZE || h > SVG_MAX_SIZE {
+                    return Err(anyhow!("svg: size out of range: {}", value));
+                }
+                self.width = w;
+                self.height = h;
+            }
+            _ => return Err(anyhow!("svg: unknown option: {}", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for SvgFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SvgFormat {
+    pub fn new() -> Self {
+        Self {
+            debug: 0,
+            width: SVG_DEFAULT_SIZE,
+            height: SVG_DEFAULT_SIZE,
+        }
+    }
+}
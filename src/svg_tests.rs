@@ -0,0 +1,88 @@
+///
+///  svg test cases
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::svg::*;
+
+    #[test]
+    fn test_write_label() {
+        let mut geodata = Geodata::new();
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        track.add_waypoint(Waypoint::new().with_lat(50.1).with_lon(10.1));
+        geodata.add_track(track);
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.05)
+                .with_lon(10.05)
+                .with_name("Gipfel")
+                .with_style(Style::new().with_color(Color::new(0, 128, 0)))
+                .with_label(
+                    Label::new(LabelFont::Times, 24, 90).with_background(Color::new(255, 255, 255)),
+                ),
+        );
+
+        let mut format = SvgFormat::new();
+        format.set_option("size", "200x100").unwrap();
+        let svg = String::from_utf8(format.write(&geodata).unwrap()).unwrap();
+        assert!(svg.contains("width=\"200\" height=\"100\" viewBox=\"0 0 200 100\""));
+        assert!(svg.contains("<polyline points="));
+        assert!(svg.contains("font-family=\"Times New Roman, serif\" font-size=\"24\""));
+        assert!(svg.contains("fill=\"#008000\""));
+        assert!(svg.contains("transform=\"rotate(-90 100.0 50.0)\""));
+        assert!(svg.contains("stroke=\"#ffffff\""));
+        assert!(svg.contains(">Gipfel</text>"));
+        assert!(!svg.contains("<circle"));
+    }
+
+    #[test]
+    fn test_write_segments() {
+        let mut geodata = Geodata::new();
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        track.add_waypoint(Waypoint::new().with_lat(50.1).with_lon(10.1));
+        track.start_segment();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.2));
+        track.add_waypoint(Waypoint::new().with_lat(50.1).with_lon(10.3));
+        geodata.add_track(track);
+
+        let mut format = SvgFormat::new();
+        format.set_option("size", "300x100").unwrap();
+        let svg = String::from_utf8(format.write(&geodata).unwrap()).unwrap();
+        // no line across the gap between the segments
+        assert_eq!(svg.matches("<polyline points=").count(), 2);
+        for line in svg.split("<polyline points=\"").skip(1) {
+            let points = line.split('"').next().unwrap();
+            assert_eq!(points.split(' ').count(), 2);
+        }
+    }
+
+    #[test]
+    fn test_options() {
+        let mut format = SvgFormat::new();
+        assert!(format.set_option("size", "0").is_err());
+        assert!(format.set_option("size", "64x").is_err());
+        assert!(format.set_option("background", "white").is_err());
+        assert!(format.set_option("size", "64x32").is_ok());
+    }
+}
@@ -0,0 +1,304 @@
+///
+///  Support for Garmin Training Center (TCX) course writing
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::env;
+use std::io;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::writer::Writer;
+
+use crate::format::Format;
+use crate::geodata::Geodata;
+use crate::geodata::Waypoint;
+use crate::geodata::WaypointList;
+use crate::geodata::MIN_SPEED;
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+const TCX_NAMESPACE: &str = "http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2";
+const TCX_SCHEMA_LOCATION: &str = "http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2 \
+                                   http://www.garmin.com/xmlschemas/TrainingCenterDatabasev2.xsd";
+/// Maximum length of course names allowed by the schema
+const TCX_COURSE_NAME_LEN: usize = 15;
+/// Maximum length of course point names allowed by the schema
+const TCX_POINT_NAME_LEN: usize = 10;
+const TCX_DEFAULT_SPEED: f64 = 10.0;
+const TCX_DEFAULT_PROXIMITY: f64 = 50.0;
+
+fn tcx_truncate(text: &str, len: usize) -> String {
+    text.chars().take(len).collect()
+}
+
+fn tcx_time(time: &DateTime<Utc>) -> String {
+    format!("{}", time.format("%Y-%m-%dT%H:%M:%SZ"))
+}
+
+fn tcx_write_text(writer: &mut Writer<&mut Vec<u8>>, tag: &str, text: &str) -> io::Result<()> {
+    writer
+        .create_element(tag)
+        .write_text_content(BytesText::new(text))?;
+    Ok(())
+}
+
+fn tcx_write_position(
+    writer: &mut Writer<&mut Vec<u8>>,
+    tag: &str,
+    waypoint: &Waypoint,
+) -> io::Result<()> {
+    writer.create_element(tag).write_inner_content(|writer| {
+        tcx_write_text(
+            writer,
+            "LatitudeDegrees",
+            &format!("{:.9}", waypoint.latitude()),
+        )?;
+        tcx_write_text(
+            writer,
+            "LongitudeDegrees",
+            &format!("{:.9}", waypoint.longitude()),
+        )?;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct TcxFormat {
+    debug: u8,
+    testmode: bool,
+    speed: f64,
+    proximity: f64,
+}
+
+impl Format for TcxFormat {
+    fn probe(&self, _buf: &[u8]) -> bool {
+        false
+    }
+    fn read(&self, _buf: &[u8]) -> Result<Geodata> {
+        Err(anyhow!("tcx read not supported"))
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 2);
+        let start = if self.testmode {
+            DateTime::from_timestamp_secs(0).expect("invalid timestmap")
+        } else {
+            Utc::now()
+        };
+
+        writer
+            .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+            .expect("writing decl");
+        writer
+            .create_element("TrainingCenterDatabase")
+            .with_attribute(("xmlns", TCX_NAMESPACE))
+            .with_attribute(("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"))
+            .with_attribute(("xsi:schemaLocation", TCX_SCHEMA_LOCATION))
+            .write_inner_content(|writer| {
+                writer
+                    .create_element("Courses")
+                    .write_inner_content(|writer| {
+                        let lists = geodata.tracks().iter().chain(geodata.routes().iter());
+                        for (i, list) in lists.enumerate() {
+                            if list.len() == 0 {
+                                continue;
+                            }
+                            let name = if list.name().is_empty() {
+                                format!("Course {}", i + 1)
+                            } else {
+                                list.name()
+                            };
+                            self.write_course(writer, &name, list, geodata, &start)?;
+                        }
+                        Ok(())
+                    })?;
+                Ok(())
+            })?;
+        buffer.push(b'\n');
+        Ok(buffer)
+    }
+    fn name<'a>(&self) -> &'a str {
+        "tcx"
+    }
+    fn can_read(&self) -> bool {
+        false
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+    fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+        let parsed = value
+            .parse::<f64>()
+            .with_context(|| format!("tcx: {}: {}", key, value))?;
+        match key {
+            "speed" if parsed >= MIN_SPEED => self.speed = parsed,
+            "proximity" if parsed >= 0.0 => self.proximity = parsed,
+            "speed" | "proximity" => return Err(anyhow!("tcx: {} out of range: {}", key, value)),
+            _ => return Err(anyhow!("tcx: unknown option: {}", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for TcxFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TcxFormat {
+    pub fn new() -> Self {
+        Self {
+            debug: 0,
+            testmode: env::var("GGVTOGPX_TESTMODE").is_ok(),
+            speed: TCX_DEFAULT_SPEED,
+            proximity: TCX_DEFAULT_PROXIMITY,
+        }
+    }
+
+    fn write_course(
+        &self,
+        writer: &mut Writer<&mut Vec<u8>>,
+        name: &str,
+        list: &WaypointList,
+        geodata: &Geodata,
+        start: &DateTime<Utc>,
+    ) -> io::Result<()> {
+        // TCX requires a time on every trackpoint. Without recorded
+        // times the course is timed at a constant speed (km/h).
+        let distances = list.distances();
+        let total = *distances.last().unwrap_or(&0.0);
+        let times = list
+            .times()
+            .unwrap_or_else(|| list.synthetic_times(*start, self.speed));
+        let duration = *times.last().expect("empty course") - times[0];
+        let first = list.waypoints().first().expect("empty course");
+        let last = list.waypoints().last().expect("empty course");
+        if self.debug >= 1 {
+            eprintln!(
+                "tcx: writing course (len: {:3}, name: \"{}\", distance: {:.0}m)",
+                list.len(),
+                name,
+                total
+            );
+        }
+        writer
+            .create_element("Course")
+            .write_inner_content(|writer| {
+                tcx_write_text(writer, "Name", &tcx_truncate(name, TCX_COURSE_NAME_LEN))?;
+                writer.create_element("Lap").write_inner_content(|writer| {
+                    tcx_write_text(
+                        writer,
+                        "TotalTimeSeconds",
+                        &format!("{:.1}", duration.num_milliseconds() as f64 / 1000.0),
+                    )?;
+                    tcx_write_text(writer, "DistanceMeters", &format!("{:.1}", total))?;
+                    tcx_write_position(writer, "BeginPosition", first)?;
+                    tcx_write_position(writer, "EndPosition", last)?;
+                    tcx_write_text(writer, "Intensity", "Active")?;
+                    Ok(())
+                })?;
+                writer
+                    .create_element("Track")
+                    .write_inner_content(|writer| {
+                        for (i, waypoint) in list.waypoints().iter().enumerate() {
+                            writer
+                                .create_element("Trackpoint")
+                                .write_inner_content(|writer| {
+                                    tcx_write_text(writer, "Time", &tcx_time(&times[i]))?;
+                                    tcx_write_position(writer, "Position", waypoint)?;
+                                    if !waypoint.elevation().is_nan() {
+                                        tcx_write_text(
+                                            writer,
+                                            "AltitudeMeters",
+                                            &format!("{:.1}", waypoint.elevation()),
+                                        )?;
+                                    }
+                                    tcx_write_text(
+                                        writer,
+                                        "DistanceMeters",
+                                        &format!("{:.1}", distances[i]),
+                                    )?;
+                                    Ok(())
+                                })?;
+                        }
+                        Ok(())
+                    })?;
+                for (index, waypoint) in
+                    list.course_points(geodata.waypoints().waypoints(), self.proximity)
+                {
+                    if get_debug() >= 2 {
+                        eprintln!(
+                            "tcx: course point \"{}\" at index {} ({:.1}m)",
+                            waypoint.name(),
+                            index,
+                            list.waypoints()[index].distance(waypoint)
+                        );
+                    }
+                    writer
+                        .create_element("CoursePoint")
+                        .write_inner_content(|writer| {
+                            tcx_write_text(
+                                writer,
+                                "Name",
+                                &tcx_truncate(&waypoint.name(), TCX_POINT_NAME_LEN),
+                            )?;
+                            tcx_write_text(writer, "Time", &tcx_time(&times[index]))?;
+                            tcx_write_position(writer, "Position", waypoint)?;
+                            if !waypoint.elevation().is_nan() {
+                                tcx_write_text(
+                                    writer,
+                                    "AltitudeMeters",
+                                    &format!("{:.1}", waypoint.elevation()),
+                                )?;
+                            }
+                            tcx_write_text(writer, "PointType", "Generic")?;
+                            let notes = [waypoint.description(), waypoint.comment()]
+                                .into_iter()
+                                .find(|t| !t.is_empty())
+                                .map(String::from)
+                                .unwrap_or_else(|| waypoint.name());
+                            tcx_write_text(writer, "Notes", &notes)?;
+                            Ok(())
+                        })?;
+                }
+                Ok(())
+            })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+///
+///  tcx test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::tcx::*;
+
+    fn course(name: &str) -> Geodata {
+        let mut geodata = Geodata::new();
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.01));
+        track.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.02));
+        track.set_name(name);
+        geodata.add_track(track);
+        geodata
+    }
+
+    fn write(format: &TcxFormat, geodata: &Geodata) -> String {
+        String::from_utf8(format.write(geodata).unwrap()).unwrap()
+    }
+
+    fn values<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+        let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+        xml.split(open.as_str())
+            .skip(1)
+            .map(|s| s.split(close.as_str()).next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_distance() {
+        let geodata = course("Test");
+        let distances = geodata.tracks()[0].distances();
+        let xml = write(&TcxFormat::new(), &geodata);
+        let expected: Vec<String> = distances.iter().map(|d| format!("{:.1}", d)).collect();
+        // lap total first, then one per trackpoint
+        let found = values(&xml, "DistanceMeters");
+        assert_eq!(found.len(), 4);
+        assert_eq!(found[0], expected[2]);
+        assert_eq!(found[1..], expected);
+        assert_eq!(found[1], "0.0");
+    }
+
+    #[test]
+    fn test_synthetic_times() {
+        let geodata = course("Test");
+        let total = *geodata.tracks()[0].distances().last().unwrap();
+        let mut format = TcxFormat::new();
+        format.set_option("speed", "36").unwrap();
+        let xml = write(&format, &geodata);
+        // 36 km/h are 10 m/s
+        let seconds = (total / 10.0).round();
+        assert_eq!(
+            values(&xml, "TotalTimeSeconds"),
+            [format!("{:.1}", seconds)]
+        );
+        let times: Vec<chrono::DateTime<chrono::Utc>> = values(&xml, "Time")
+            .iter()
+            .map(|t| t.parse().unwrap())
+            .collect();
+        assert_eq!(times.len(), 3);
+        assert_eq!((times[2] - times[0]).num_seconds(), seconds as i64);
+        assert!(times[0] < times[1] && times[1] < times[2]);
+        assert!(format.set_option("speed", "0").is_err());
+    }
+
+    #[test]
+    fn test_recorded_times() {
+        let start = chrono::DateTime::parse_from_rfc3339("2024-05-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let mut track = WaypointList::new();
+        for i in 0..2 {
+            track.add_waypoint(
+                Waypoint::new()
+                    .with_lat(50.0)
+                    .with_lon(10.0 + i as f64 * 0.01)
+                    .with_time(start + chrono::TimeDelta::seconds(i * 90)),
+            );
+        }
+        let mut geodata = Geodata::new();
+        geodata.add_track(track);
+        let xml = write(&TcxFormat::new(), &geodata);
+        assert_eq!(
+            values(&xml, "Time"),
+            ["2024-05-01T08:00:00Z", "2024-05-01T08:01:30Z"]
+        );
+        assert_eq!(values(&xml, "TotalTimeSeconds"), ["90.0"]);
+    }
+
+    #[test]
+    fn test_course_points() {
+        let mut geodata = course("Test");
+        // close to the last and the middle point, written in course order
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0001)
+                .with_lon(10.02)
+                .with_name("End"),
+        );
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.0101)
+                .with_name("Middle"),
+        );
+        // too far away and unnamed
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.01)
+                .with_lon(10.0)
+                .with_name("Far"),
+        );
+        geodata.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+
+        let mut format = TcxFormat::new();
+        let xml = write(&format, &geodata);
+        assert_eq!(values(&xml, "PointType"), ["Generic", "Generic"]);
+        assert_eq!(values(&xml, "Name"), ["Test", "Middle", "End"]);
+        // course points take the time of the closest trackpoint
+        let times = values(&xml, "Time");
+        assert_eq!(times[3], times[1]);
+        assert_eq!(times[4], times[2]);
+
+        format.set_option("proximity", "5").unwrap();
+        let xml = write(&format, &geodata);
+        assert_eq!(values(&xml, "Name"), ["Test"]);
+        format.set_option("proximity", "2000").unwrap();
+        let xml = write(&format, &geodata);
+        assert_eq!(values(&xml, "Name"), ["Test", "Far", "Middle", "End"]);
+    }
+
+    #[test]
+    fn test_names() {
+        let mut geodata = course("A very long course name");
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.0)
+                .with_lon(10.0)
+                .with_name("Zwischenstopp"),
+        );
+        let mut track = WaypointList::new();
+        track.add_waypoint(Waypoint::new().with_lat(51.0).with_lon(11.0));
+        geodata.add_track(track);
+        let xml = write(&TcxFormat::new(), &geodata);
+        // truncated to the schema limits, unnamed courses are numbered
+        assert_eq!(
+            values(&xml, "Name"),
+            ["A very long cou", "Zwischenst", "Course 2"]
+        );
+    }
+}
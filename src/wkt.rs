@@ -0,0 +1,265 @@
+///
+///  Support for well-known text and binary geometry writing
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+use std::fmt::Write;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Result};
+
+use crate::format::Format;
+use crate::geodata::Geodata;
+use crate::geodata::Waypoint;
+
+static DEBUG_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+fn get_debug() -> u8 {
+    DEBUG_LEVEL.load(Ordering::Relaxed)
+}
+
+fn set_debug(debug: u8) {
+    DEBUG_LEVEL.store(debug, Ordering::Relaxed);
+}
+
+const WKT_SRID: u32 = 4326;
+/// ISO WKB adds 1000 to the geometry type for Z coordinates
+const WKB_ISO_Z: u32 = 1000;
+const WKB_EWKB_Z: u32 = 0x80000000;
+const WKB_EWKB_SRID: u32 = 0x20000000;
+const WKB_LITTLE_ENDIAN: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WktEncoding {
+    /// Extended WKT text with SRID prefix
+    Wkt,
+    /// ISO WKB as hex string
+    Wkb,
+    /// PostGIS extended WKB with SRID as hex string
+    Ewkb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum WktKind {
+    Point,
+    LineString,
+    Polygon,
+}
+
+impl WktKind {
+    fn name(&self) -> &'static str {
+        match self {
+            WktKind::Point => "POINT",
+            WktKind::LineString => "LINESTRING",
+            WktKind::Polygon => "POLYGON",
+        }
+    }
+    fn code(&self) -> u32 {
+        match self {
+            WktKind::Point => 1,
+            WktKind::LineString => 2,
+            WktKind::Polygon => 3,
+        }
+    }
+}
+
+fn wkt_coordinates(points: &[&Waypoint], z: bool) -> String {
+    let mut result = String::new();
+    for (i, point) in points.iter().enumerate() {
+        if i > 0 {
+            result.push_str(", ");
+        }
+        let _ = write!(result, "{} {}", point.longitude(), point.latitude());
+        if z {
+            let _ = write!(result, " {}", point.elevation());
+        }
+    }
+    result
+}
+
+fn wkt_text(kind: WktKind, points: &[&Waypoint], z: bool) -> String {
+    let coordinates = wkt_coordinates(points, z);
+    let dimension = if z { " Z" } else { "" };
+    let coordinates = match kind {
+        WktKind::Polygon => format!("(({}))", coordinates),
+        _ => format!("({})", coordinates),
+    };
+    format!(
+        "SRID={};{}{} {}",
+        WKT_SRID,
+        kind.name(),
+        dimension,
+        coordinates
+    )
+}
+
+pub(crate) fn wkt_binary(kind: WktKind, points: &[&Waypoint], z: bool, extended: bool) -> Vec<u8> {
+    let mut code = kind.code();
+    if extended {
+        code |= WKB_EWKB_SRID;
+        if z {
+            code |= WKB_EWKB_Z;
+        }
+    } else if z {
+        code += WKB_ISO_Z;
+    }
+    let mut buf = vec![WKB_LITTLE_ENDIAN];
+    buf.extend_from_slice(&code.to_le_bytes());
+    if extended {
+        buf.extend_from_slice(&WKT_SRID.to_le_bytes());
+    }
+    match kind {
+        WktKind::Point => (),
+        WktKind::LineString => buf.extend_from_slice(&(points.len() as u32).to_le_bytes()),
+        WktKind::Polygon => {
+            buf.extend_from_slice(&1u32.to_le_bytes());
+            buf.extend_from_slice(&(points.len() as u32).to_le_bytes());
+        }
+    }
+    for point in points {
+        buf.extend_from_slice(&point.longitude().to_le_bytes());
+        buf.extend_from_slice(&point.latitude().to_le_bytes());
+        if z {
+            buf.extend_from_slice(&point.elevation().to_le_bytes());
+        }
+    }
+    buf
+}
+
+fn wkt_hex(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len() * 2);
+    for byte in data {
+        let _ = write!(result, "{:02X}", byte);
+    }
+    result
+}
+
+//////////////////////////////////////////////////////////////////////
+//            entry points called by ggvtogpx main process
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct WktFormat {
+    debug: u8,
+    encoding: WktEncoding,
+}
+
+impl Format for WktFormat {
+    fn probe(&self, _buf: &[u8]) -> bool {
+        false
+    }
+    fn read(&self, _buf: &[u8]) -> Result<Geodata> {
+        Err(anyhow!("wkt read not supported"))
+    }
+    fn write(&self, geodata: &Geodata) -> Result<Vec<u8>> {
+        let mut result = String::new();
+        for waypoint in geodata.waypoints().waypoints().iter() {
+            self.write_geometry(
+                &mut result,
+                &waypoint.name(),
+                WktKind::Point,
+                vec![waypoint],
+            );
+        }
+        let lists = geodata.tracks().iter().chain(geodata.routes().iter());
+        for list in lists.filter(|l| l.len() > 0) {
+            let mut points: Vec<&Waypoint> = list.waypoints().iter().collect();
+            let kind = if list.area() && points.len() > 2 {
+                // Polygon rings are closed by repeating the first point
+                let first = points[0];
+                let last = points[points.len() - 1];
+                if first.latitude() != last.latitude() || first.longitude() != last.longitude() {
+                    points.push(first);
+                }
+                WktKind::Polygon
+            } else if points.len() > 1 {
+                WktKind::LineString
+            } else {
+                WktKind::Point
+            };
+            self.write_geometry(&mut result, &list.name(), kind, points);
+        }
+        Ok(result.into_bytes())
+    }
+    fn name<'a>(&self) -> &'a str {
+        "wkt"
+    }
+    fn can_read(&self) -> bool {
+        false
+    }
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn set_debug(&mut self, debug: u8) {
+        set_debug(debug);
+        self.debug = debug;
+    }
+    fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+        match (key, value) {
+            ("encoding", "wkt") => self.encoding = WktEncoding::Wkt,
+            ("encoding", "wkb") => self.encoding = WktEncoding::Wkb,
+            ("encoding", "ewkb") => self.encoding = WktEncoding::Ewkb,
+            ("encoding", _) => return Err(anyhow!("wkt: invalid encoding: {}", value)),
+            _ => return Err(anyhow!("wkt: unknown option: {}", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for WktFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WktFormat {
+    pub fn new() -> Self {
+        Self {
+            debug: 0,
+            encoding: WktEncoding::Wkt,
+        }
+    }
+
+    /// Append a name<TAB>geometry line. Z coordinates are only written
+    /// when all points have an elevation.
+    fn write_geometry(
+        &self,
+        result: &mut String,
+        name: &str,
+        kind: WktKind,
+        points: Vec<&Waypoint>,
+    ) {
+        let z = points.iter().all(|p| !p.elevation().is_nan());
+        let name = name.replace(['\t', '\r', '\n'], " ");
+        if get_debug() >= 2 {
+            eprintln!(
+                "wkt: {} (len: {:3}, name: \"{}\", z: {})",
+                kind.name(),
+                points.len(),
+                name,
+                z
+            );
+        }
+        let geometry = match self.encoding {
+            WktEncoding::Wkt => wkt_text(kind, &points, z),
+            WktEncoding::Wkb => wkt_hex(&wkt_binary(kind, &points, z, false)),
+            WktEncoding::Ewkb => wkt_hex(&wkt_binary(kind, &points, z, true)),
+        };
+        let _ = writeln!(result, "{}\t{}", name, geometry);
+    }
+}
@@ -0,0 +1,67 @@
+///
+///  wkt test cases
+///
+///
+///  Copyright (C) 2025 Ralf Horstmann <ralf@ackstorm.de>
+///
+///  This program is free software; you can redistribute it and/or modify
+///  it under the terms of the GNU General Public License as published by
+///  the Free Software Foundation; either version 2 of the License, or
+///  (at your option) any later version.
+///
+///  This program is distributed in the hope that it will be useful,
+///  but WITHOUT ANY WARRANTY; without even the implied warranty of
+///  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+///  GNU General Public License for more details.
+///
+///  You should have received a copy of the GNU General Public License
+///  along with this program; if not, write to the Free Software
+///  Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+///
+#[cfg(test)]
+mod tests {
+
+    use crate::format::Format;
+    use crate::geodata::*;
+    use crate::wkt::*;
+
+    fn sample() -> Geodata {
+        let mut geodata = Geodata::new();
+        geodata.add_waypoint(
+            Waypoint::new()
+                .with_lat(50.5)
+                .with_lon(10.25)
+                .with_elevation(100.0)
+                .with_name("Peak"),
+        );
+        let mut area = WaypointList::new();
+        area.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(10.0));
+        area.add_waypoint(Waypoint::new().with_lat(50.0).with_lon(11.0));
+        area.add_waypoint(Waypoint::new().with_lat(51.0).with_lon(11.0));
+        area.set_name("Area");
+        area.set_area(true);
+        geodata.add_track(area);
+        geodata
+    }
+
+    #[test]
+    fn test_write_wkt() {
+        let result = WktFormat::new().write(&sample()).unwrap();
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "Peak\tSRID=4326;POINT Z (10.25 50.5 100)\n\
+             Area\tSRID=4326;POLYGON ((10 50, 11 50, 11 51, 10 50))\n"
+        );
+    }
+
+    #[test]
+    fn test_write_ewkb() {
+        let mut format = WktFormat::new();
+        format.set_option("encoding", "ewkb").unwrap();
+        let result = String::from_utf8(format.write(&sample()).unwrap()).unwrap();
+        let point = result.lines().next().unwrap();
+        // little endian, point with Z and SRID flags, SRID 4326
+        assert!(point.starts_with("Peak\t01010000A0E6100000"));
+        assert_eq!(point.len(), "Peak\t".len() + 2 * (1 + 4 + 4 + 3 * 8));
+    }
+}